@@ -7,11 +7,27 @@ async function search(query) {
     body: query,
   })
   const json = await response.json();
-  for ([path, rank] of json) {
+  for ([path, rank, metadata, anchor] of json) {
+    // NOTE: the anchor at the end of the path(e.g. "#page=3" of a PDF) is kept in the link, the
+    // other "#" are a part of the file name
+    const [, file, fragment] = path.match(/^(.*?)(#(?:page|row)=\d+)?$/);
+    let link = document.createElement("a");
+    link.href = "/file?path=" + encodeURIComponent(file) + (fragment || "");
+    link.target = "_blank";
+    link.appendChild(document.createTextNode(path));
+
     let item = document.createElement("span");
-    item.appendChild(document.createTextNode(path));
+    item.appendChild(link);
     item.appendChild(document.createTextNode(" | "));
     item.appendChild(document.createTextNode(rank));
+    // NOTE: the anchor is where the document matches best(e.g. the timestamp of a subtitle cue)
+    if (anchor) {
+      item.appendChild(document.createTextNode(" | at " + anchor));
+    }
+    // NOTE: the metadata(e.g. the title and the author) is shown after the rank
+    for (const [name, value] of Object.entries(metadata)) {
+      item.appendChild(document.createTextNode(" | " + name + ": " + value));
+    }
     item.appendChild(document.createElement("br"));
     results.appendChild(item)
   }
@@ -0,0 +1,509 @@
+use encoding_rs::Encoding;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Settings of a folder, written as JSON in the `.loserconfig` file of the folder. They apply to
+/// the sub-folders too, unless they are overridden by the `.loserconfig` in a sub-folder.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FolderConfig {
+    /// The encoding(e.g. "big5") of the text files which are neither marked by a byte order mark
+    /// nor valid UTF-8
+    pub default_encoding: Option<String>,
+    /// External programs used to extract the text from the files, they are only read from the
+    /// user config, never from the folders being indexed
+    #[serde(default)]
+    pub commands: Vec<CommandConfig>,
+    /// How the built-in readers are isolated in subprocesses, `SandboxConfig::default()` is used
+    /// when it's not provided.
+    pub sandbox: Option<SandboxConfig>,
+    /// Whether the outputs of the code cells in the Jupyter notebooks are indexed, they are not by
+    /// default.
+    pub notebook_outputs: Option<bool>,
+    /// How much is read from the archives, `ArchiveConfig::default()` is used when it's not
+    /// provided.
+    pub archive: Option<ArchiveConfig>,
+}
+
+/// An external program(e.g. ["pandoc", "-t", "plain", "{}"]) whose standard output is the text of
+/// the file, "{}" in the arguments is replaced with the path to the file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CommandConfig {
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub mime_types: Vec<String>,
+    /// The program and its arguments
+    pub command: Vec<String>,
+    #[serde(default = "CommandConfig::default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "CommandConfig::default_max_output_bytes")]
+    pub max_output_bytes: usize,
+}
+
+impl CommandConfig {
+    fn default_timeout_secs() -> u64 {
+        30
+    }
+
+    fn default_max_output_bytes() -> usize {
+        16 * 1024 * 1024
+    }
+}
+
+/// The built-in readers listed in `readers`(e.g. "pdf" and "archive") run in a subprocess, which is
+/// killed when it runs longer than `timeout_secs` or uses more memory than `max_memory_bytes`(only
+/// on Unix).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SandboxConfig {
+    pub readers: Vec<String>,
+    pub timeout_secs: u64,
+    pub max_memory_bytes: u64,
+    pub max_output_bytes: usize,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        SandboxConfig {
+            readers: vec!["pdf".to_string()],
+            timeout_secs: 60,
+            max_memory_bytes: 1024 * 1024 * 1024,
+            max_output_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// The limits of the files read from an archive, the archive fails to be read when it holds more
+/// files or bytes than them(e.g. a zip bomb)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    pub max_entries: usize,
+    pub max_entry_bytes: u64,
+    pub max_total_bytes: u64,
+    /// How many levels of the archives inside the archives are opened, the archives nested deeper
+    /// are skipped(e.g. a zip quine)
+    pub max_depth: usize,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        ArchiveConfig {
+            max_entries: 10_000,
+            max_entry_bytes: 64 * 1024 * 1024,
+            max_total_bytes: 512 * 1024 * 1024,
+            max_depth: 2,
+        }
+    }
+}
+
+impl FolderConfig {
+    pub const FILE_NAME: &'static str = ".loserconfig";
+
+    /// Load the config in the folder, the settings missing in it are inherited from `parent`. A
+    /// config which could not be loaded is reported and skipped, so a broken config in a
+    /// sub-folder never stops the whole folder from being indexed.
+    pub fn load(dir_path: &Path, parent: &FolderConfig) -> Self {
+        let config_path = dir_path.join(Self::FILE_NAME);
+        if !config_path.is_file() {
+            return parent.clone();
+        }
+
+        match Self::read(&config_path) {
+            Ok(mut config) => {
+                // NOTE: a folder could be anything(e.g. a download or a network share), so the
+                // programs it names are never run
+                if !config.commands.is_empty() {
+                    eprintln!(
+                        "ERROR: the commands in the config file {config_path} are ignored, they are only read from the user config",
+                        config_path = config_path.display()
+                    );
+                    config.commands.clear();
+                }
+                config.inherit(parent)
+            }
+            Err(()) => {
+                println!(
+                    "INFO: the config of the parent folder is used for {dir_path}",
+                    dir_path = dir_path.display()
+                );
+                parent.clone()
+            }
+        }
+    }
+
+    /// The config of the user, which every folder inherits. It's read once from the file given by
+    /// `LOSER_CONFIG`, or "loser/config.json" in the config folder of the user(e.g.
+    /// "~/.config/loser/config.json").
+    pub fn user() -> Self {
+        static USER_CONFIG: OnceLock<FolderConfig> = OnceLock::new();
+        USER_CONFIG
+            .get_or_init(|| match user_config_path() {
+                Some(config_path) if config_path.is_file() => {
+                    Self::read(&config_path).unwrap_or_default()
+                }
+                _ => FolderConfig::default(),
+            })
+            .clone()
+    }
+
+    fn read(config_path: &Path) -> Result<Self, ()> {
+        let content = fs::read_to_string(config_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not open the config file {config_path}: {err}",
+                config_path = config_path.display()
+            )
+        })?;
+        let config: FolderConfig = serde_json::from_str(&content).map_err(|err| {
+            eprintln!(
+                "ERROR: could not parse the config file {config_path}: {err}",
+                config_path = config_path.display()
+            )
+        })?;
+
+        if let Some(label) = &config.default_encoding {
+            if Encoding::for_label(label.as_bytes()).is_none() {
+                eprintln!(
+                    "ERROR: unknown encoding {label} in the config file {config_path}",
+                    config_path = config_path.display()
+                );
+                return Err(());
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The settings missing in the config are inherited from `parent`
+    fn inherit(self, parent: &FolderConfig) -> Self {
+        // NOTE: the commands of the sub-folder are registered later, so they win over the ones of
+        // the parent folder for the same extensions.
+        let mut commands = parent.commands.clone();
+        commands.extend(self.commands);
+
+        FolderConfig {
+            default_encoding: self
+                .default_encoding
+                .or_else(|| parent.default_encoding.clone()),
+            commands,
+            sandbox: self.sandbox.or_else(|| parent.sandbox.clone()),
+            notebook_outputs: self.notebook_outputs.or(parent.notebook_outputs),
+            archive: self.archive.or_else(|| parent.archive.clone()),
+        }
+    }
+
+    pub fn sandbox(&self) -> SandboxConfig {
+        self.sandbox.clone().unwrap_or_default()
+    }
+
+    pub fn archive(&self) -> ArchiveConfig {
+        self.archive.clone().unwrap_or_default()
+    }
+
+    pub fn encoding(&self) -> Option<&'static Encoding> {
+        self.default_encoding
+            .as_ref()
+            .and_then(|label| Encoding::for_label(label.as_bytes()))
+    }
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    if let Some(config_path) = env::var_os("LOSER_CONFIG") {
+        return Some(PathBuf::from(config_path));
+    }
+
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+
+    Some(config_dir.join("loser").join("config.json"))
+}
+
+/// Which files in the folder are indexed, given by the options of `index` and `server` and stored
+/// in the index file, so the folder is always reindexed by the same rules
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct IndexRules {
+    /// The globs(e.g. "**/*.md") of the files to index, relative to the folder. All the files are
+    /// indexed when it's empty.
+    pub include: Vec<String>,
+    /// The globs(e.g. "**/secrets/**") of the files and the folders never indexed
+    pub exclude: Vec<String>,
+    pub max_file_size: Option<u64>,
+    /// The depth of the files in the folder is 1, and the files in its sub-folders are 2 and so on
+    pub max_depth: Option<usize>,
+    /// Whether the hidden files and folders(e.g. ".cache") are indexed
+    pub hidden: bool,
+}
+
+// NOTE: the hidden files are indexed by default like the older versions did, so the index files
+// without the rules don't lose them
+impl Default for IndexRules {
+    fn default() -> Self {
+        IndexRules {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            max_file_size: None,
+            max_depth: None,
+            hidden: true,
+        }
+    }
+}
+
+impl IndexRules {
+    /// Compile the globs, the paths are matched relative to `root`
+    pub fn filter(&self, root: &Path) -> Result<IndexFilter, ()> {
+        Ok(IndexFilter {
+            root: root.to_path_buf(),
+            include: build_glob_set(&self.include)?,
+            exclude: build_glob_set(&self.exclude)?,
+            rules: self.clone(),
+            index_path: None,
+        })
+    }
+}
+
+fn build_glob_set(globs: &[String]) -> Result<GlobSet, ()> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(
+            GlobBuilder::new(glob)
+                .literal_separator(true)
+                .build()
+                .map_err(|err| eprintln!("ERROR: invalid glob {glob}: {err}"))?,
+        );
+    }
+
+    builder
+        .build()
+        .map_err(|err| eprintln!("ERROR: could not compile the globs: {err}"))
+}
+
+pub struct IndexFilter {
+    root: PathBuf,
+    include: GlobSet,
+    exclude: GlobSet,
+    rules: IndexRules,
+    /// The index file relative to the folder, when it's saved inside the folder(e.g. `server .`)
+    index_path: Option<PathBuf>,
+}
+
+impl IndexFilter {
+    /// Skip the index file and the files next to it(e.g. the backup), when it's in the folder
+    pub fn skip_index(mut self, index_path: &Path) -> Self {
+        self.index_path = relative_index_path(&self.root, index_path);
+        self
+    }
+
+    /// Whether the file or the folder at `depth` is skipped by the rules
+    pub fn is_skipped(&self, path: &Path, metadata: &fs::Metadata, depth: usize) -> bool {
+        let relative_path = path.strip_prefix(&self.root).unwrap_or(path);
+        let is_hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if (is_hidden && !self.rules.hidden) || self.exclude.is_match(relative_path) {
+            return true;
+        }
+        if self
+            .index_path
+            .as_deref()
+            .is_some_and(|index_path| is_index_path(relative_path, index_path))
+        {
+            return true;
+        }
+
+        if metadata.is_dir() {
+            return self
+                .rules
+                .max_depth
+                .is_some_and(|max_depth| depth >= max_depth);
+        }
+
+        if self
+            .rules
+            .max_file_size
+            .is_some_and(|max_file_size| metadata.len() > max_file_size)
+        {
+            return true;
+        }
+
+        !self.include.is_empty() && !self.include.is_match(relative_path)
+    }
+}
+
+/// The index file relative to the folder `root`, nothing when it's not in the folder
+pub fn relative_index_path(root: &Path, index_path: &Path) -> Option<PathBuf> {
+    // NOTE: the index file could be missing yet, so the folder holding it is resolved instead
+    let index_path = match (index_path.parent(), index_path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            parent.canonicalize().ok()?.join(name)
+        }
+        (_, Some(name)) => Path::new(".").canonicalize().ok()?.join(name),
+        _ => return None,
+    };
+
+    index_path
+        .strip_prefix(root.canonicalize().ok()?)
+        .ok()
+        .map(|path| path.to_path_buf())
+}
+
+/// Whether the path relative to the folder is the index file, in it, or the file next to it(e.g.
+/// "data.loser.bak" or "data.loser.db-wal")
+pub fn is_index_path(relative_path: &Path, index_path: &Path) -> bool {
+    if relative_path.starts_with(index_path) {
+        return true;
+    }
+
+    match (relative_path.file_name(), index_path.file_name()) {
+        (Some(name), Some(index_name)) => {
+            relative_path.parent() == index_path.parent()
+                && name
+                    .to_string_lossy()
+                    .starts_with(&*index_name.to_string_lossy())
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_index_path, relative_index_path, FolderConfig, IndexRules};
+    use std::{env, fs, path::Path, process};
+
+    #[test]
+    fn load_ok() -> Result<(), ()> {
+        // arrange
+        let root = env::temp_dir().join(format!("loser-config-test-{pid}", pid = process::id()));
+        let (valid, broken) = (root.join("valid"), root.join("broken"));
+        fs::create_dir_all(&valid).map_err(|err| eprintln!("ERROR: {err}"))?;
+        fs::create_dir_all(&broken).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let configs = [
+            (
+                &root,
+                r#"{ "default_encoding": "big5", "commands": [{ "extensions": ["txt"], "command": ["rm", "{}"] }] }"#,
+            ),
+            (&valid, r#"{ "notebook_outputs": true }"#),
+            (&broken, r#"{ "notebook_outputs": "#),
+        ];
+        for (dir, content) in configs {
+            fs::write(dir.join(FolderConfig::FILE_NAME), content)
+                .map_err(|err| eprintln!("ERROR: {err}"))?;
+        }
+
+        // act
+        let root_config = FolderConfig::load(&root, &FolderConfig::default());
+        let valid_config = FolderConfig::load(&valid, &root_config);
+        let broken_config = FolderConfig::load(&broken, &root_config);
+        fs::remove_dir_all(&root).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert!(root_config.commands.is_empty());
+        assert_eq!(valid_config.default_encoding.as_deref(), Some("big5"));
+        assert_eq!(valid_config.notebook_outputs, Some(true));
+        assert_eq!(broken_config, root_config);
+
+        Ok(())
+    }
+
+    #[test]
+    fn is_index_path_ok() -> Result<(), ()> {
+        // arrange
+        let root =
+            env::temp_dir().join(format!("loser-index-path-test-{pid}", pid = process::id()));
+        let data = root.join("data");
+        fs::create_dir_all(&data).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let inputs = [
+            ("data.loser.db-wal", true),
+            ("data.loser.db", true),
+            ("data.loser.db/a.txt", true),
+            ("notes/data.loser.db", false),
+            ("data.txt", false),
+        ];
+
+        // act
+        let inside = relative_index_path(&root, &data.join("..").join("data.loser.db"));
+        let outside = relative_index_path(&data, &root.join("data.loser.db"));
+        fs::remove_dir_all(&root).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert_eq!(inside.as_deref(), Some(Path::new("data.loser.db")));
+        assert_eq!(outside, None);
+        for (path, expected) in inputs {
+            assert_eq!(
+                is_index_path(Path::new(path), Path::new("data.loser.db")),
+                expected
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn is_skipped_ok() -> Result<(), ()> {
+        // arrange
+        let root = env::temp_dir().join(format!("loser-rules-test-{pid}", pid = process::id()));
+        fs::create_dir_all(root.join("secrets")).map_err(|err| eprintln!("ERROR: {err}"))?;
+        fs::create_dir_all(root.join("notes")).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let files = [
+            ("a.md", "Andy", 1, false),
+            ("a.txt", "Andy", 1, true),
+            ("big.md", "Andy is Andy.", 1, true),
+            (".hidden.md", "Andy", 1, true),
+            ("secrets/a.md", "Andy", 2, true),
+            ("notes/a.md", "Andy", 2, false),
+        ];
+        for (path, content, _, _) in files {
+            fs::write(root.join(path), content).map_err(|err| eprintln!("ERROR: {err}"))?;
+        }
+        let folders = [("notes", 1, false), ("notes", 2, true)];
+
+        let rules = IndexRules {
+            include: vec!["**/*.md".to_string()],
+            exclude: vec!["**/secrets/**".to_string()],
+            max_file_size: Some(8),
+            max_depth: Some(2),
+            hidden: false,
+        };
+
+        // act
+        let filter = rules.filter(&root)?;
+        let mut actual = Vec::new();
+        for (path, _, depth, _) in files {
+            let path = root.join(path);
+            let metadata = path.metadata().map_err(|err| eprintln!("ERROR: {err}"))?;
+            actual.push(filter.is_skipped(&path, &metadata, depth));
+        }
+        for (path, depth, _) in folders {
+            let path = root.join(path);
+            let metadata = path.metadata().map_err(|err| eprintln!("ERROR: {err}"))?;
+            actual.push(filter.is_skipped(&path, &metadata, depth));
+        }
+        let hidden_path = root.join(".hidden.md");
+        let hidden_metadata = hidden_path
+            .metadata()
+            .map_err(|err| eprintln!("ERROR: {err}"))?;
+        let hidden_skipped_by_default =
+            IndexRules::default()
+                .filter(&root)?
+                .is_skipped(&hidden_path, &hidden_metadata, 1);
+        fs::remove_dir_all(&root).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert!(!hidden_skipped_by_default);
+        let expected = files
+            .iter()
+            .map(|(_, _, _, skipped)| *skipped)
+            .chain(folders.iter().map(|(_, _, skipped)| *skipped))
+            .collect::<Vec<bool>>();
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use std::{path::Path, sync::Arc};
+
+/// The rules of the ignore files(`.gitignore`, `.git/info/exclude`, `.loserignore` and the global
+/// ignore file of git) applied to a folder. The rules of the sub-folders win over the rules of the
+/// parent folders, like the nested `.gitignore` files.
+#[derive(Clone, Default)]
+pub struct IgnoreRules {
+    /// The rules of the folders, from the root folder to the current one
+    matchers: Vec<Arc<Gitignore>>,
+}
+
+impl IgnoreRules {
+    pub const FILE_NAME: &'static str = ".loserignore";
+
+    /// The rules of the global ignore file of git(e.g. `core.excludesFile` or
+    /// "~/.config/git/ignore")
+    pub fn global() -> Self {
+        let (matcher, err) = Gitignore::global();
+        if let Some(err) = err {
+            eprintln!("ERROR: could not read the global ignore file: {err}");
+        }
+
+        IgnoreRules {
+            matchers: vec![Arc::new(matcher)],
+        }
+    }
+
+    /// Load the ignore files in the folder on top of the rules of `parent`. In the same folder, the
+    /// rules in `.loserignore` win over `.gitignore`, and `.gitignore` wins over `.git/info/exclude`.
+    pub fn load(dir_path: &Path, parent: &IgnoreRules) -> Self {
+        let ignore_files = [
+            dir_path.join(".git").join("info").join("exclude"),
+            dir_path.join(".gitignore"),
+            dir_path.join(Self::FILE_NAME),
+        ];
+        if !ignore_files.iter().any(|x| x.is_file()) {
+            return parent.clone();
+        }
+
+        let mut builder = GitignoreBuilder::new(dir_path);
+        for ignore_file in ignore_files.iter().filter(|x| x.is_file()) {
+            // NOTE: the invalid patterns are reported and skipped, the others still apply
+            if let Some(err) = builder.add(ignore_file) {
+                eprintln!(
+                    "ERROR: could not read the ignore file {ignore_file}: {err}",
+                    ignore_file = ignore_file.display()
+                );
+            }
+        }
+
+        let mut rules = parent.clone();
+        match builder.build() {
+            Ok(matcher) => rules.matchers.push(Arc::new(matcher)),
+            Err(err) => eprintln!(
+                "ERROR: could not load the ignore files in {dir_path}: {err}",
+                dir_path = dir_path.display()
+            ),
+        }
+
+        rules
+    }
+
+    /// Whether the file or the folder is ignored, the `.git` folders are always ignored
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if is_dir && path.file_name() == Some(".git".as_ref()) {
+            return true;
+        }
+
+        for matcher in self.matchers.iter().rev() {
+            match matcher.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IgnoreRules;
+    use std::{env, fs, process};
+
+    #[test]
+    fn is_ignored_ok() -> Result<(), ()> {
+        // arrange
+        let root = env::temp_dir().join(format!("loser-ignore-test-{pid}", pid = process::id()));
+        let docs = root.join("docs");
+        fs::create_dir_all(&docs).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let files = [
+            (root.join(".gitignore"), "target/\n*.log\n"),
+            (root.join(IgnoreRules::FILE_NAME), "drafts/\n"),
+            (docs.join(IgnoreRules::FILE_NAME), "!keep.log\n*.tmp\n"),
+        ];
+        for (path, content) in &files {
+            fs::write(path, content).map_err(|err| eprintln!("ERROR: {err}"))?;
+        }
+
+        let inputs = [
+            (root.join("target"), true, true),
+            (root.join("drafts"), true, true),
+            (root.join("src"), true, false),
+            (root.join(".git"), true, true),
+            (root.join("debug.log"), false, true),
+            (docs.join("debug.log"), false, true),
+            (docs.join("keep.log"), false, false),
+            (docs.join("a.tmp"), false, true),
+            (root.join("a.tmp"), false, false),
+        ];
+
+        // act
+        let root_rules = IgnoreRules::load(&root, &IgnoreRules::default());
+        let docs_rules = IgnoreRules::load(&docs, &root_rules);
+        let actual = inputs
+            .iter()
+            .map(|(path, is_dir, _)| {
+                let rules = if path.starts_with(&docs) {
+                    &docs_rules
+                } else {
+                    &root_rules
+                };
+                rules.is_ignored(path, *is_dir)
+            })
+            .collect::<Vec<bool>>();
+        fs::remove_dir_all(&root).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        let expected = inputs
+            .iter()
+            .map(|(_, _, ignored)| *ignored)
+            .collect::<Vec<bool>>();
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+}
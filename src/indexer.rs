@@ -0,0 +1,611 @@
+use super::config::{FolderConfig, IndexFilter};
+use super::ignore_rules::IgnoreRules;
+use super::model::analyzer::AnalyzedDocument;
+use super::model::in_memory_index_model::Model;
+use super::progress::Progress;
+use super::reader::{metadata::file_metadata, registry::ReaderRegistry};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex, RwLock},
+    thread,
+    time::SystemTime,
+};
+
+/// The most files added into the model under a single write lock
+const WRITE_BATCH_SIZE: usize = 64;
+
+/// The file to be read by a worker
+struct Job {
+    file_path: PathBuf,
+    metadata: fs::Metadata,
+    last_modified: SystemTime,
+    /// The readers configured for the folder holding the file
+    registry: Arc<ReaderRegistry>,
+}
+
+/// The jobs found by the walker, they're sent to the workers and counted into the progress once
+/// they're found
+struct JobQueue<'a> {
+    sender: mpsc::SyncSender<Job>,
+    progress: &'a Progress,
+    count: usize,
+}
+
+impl JobQueue<'_> {
+    /// Send the job to the workers, it blocks while the queue is full
+    fn push(&mut self, job: Job) -> Result<(), ()> {
+        // NOTE: the progress of the last indexing is kept until a file is found to be indexed
+        if self.count == 0 {
+            self.progress.start();
+        }
+        self.count += 1;
+        self.progress.found(job.metadata.len());
+        self.sender
+            .send(job)
+            .map_err(|_| eprintln!("ERROR: the indexing workers are stopped unexpectedly"))
+    }
+}
+
+/// The documents read and analyzed by a worker, or the failure of reading the file
+struct Extracted {
+    file_path: PathBuf,
+    last_modified: SystemTime,
+    bytes: u64,
+    documents: Result<Vec<AnalyzedDocument>, ()>,
+}
+
+/// The number of workers used when it's not provided, one for every CPU
+pub fn default_workers() -> usize {
+    thread::available_parallelism()
+        .map(|x| x.get())
+        .unwrap_or(1)
+}
+
+/// Index the files in the folder which are new or modified, and return the files which could not
+/// be read
+pub fn index_folder<M: Model + Send + Sync>(
+    dir_path: &Path,
+    model: Arc<RwLock<M>>,
+    filter: &IndexFilter,
+    workers: usize,
+    progress: &Progress,
+) -> Result<Vec<PathBuf>, ()> {
+    let walker_model = Arc::clone(&model);
+    index_jobs(model, workers, progress, move |jobs| {
+        walk_folder(
+            dir_path,
+            &walker_model,
+            &FolderConfig::user(),
+            &IgnoreRules::global(),
+            filter,
+            1,
+            jobs,
+        )
+    })
+}
+
+/// Index the changed files and folders in the folder(e.g. reported by the watcher), the files
+/// which don't exist anymore are removed. The files which could not be read are returned.
+pub fn index_paths<M: Model + Send + Sync>(
+    dir_path: &Path,
+    paths: &[PathBuf],
+    model: Arc<RwLock<M>>,
+    filter: &IndexFilter,
+    workers: usize,
+    progress: &Progress,
+) -> Result<Vec<PathBuf>, ()> {
+    // NOTE: the changes of the folder configs and the ignore files apply to the whole folders
+    // holding them, and the paths inside the changed folders are indexed with the folders
+    let mut paths = paths
+        .iter()
+        .map(|path| match path.parent() {
+            Some(parent)
+                if path.file_name() == Some(FolderConfig::FILE_NAME.as_ref())
+                    || path.file_name() == Some(IgnoreRules::FILE_NAME.as_ref())
+                    || path.file_name() == Some(".gitignore".as_ref()) =>
+            {
+                parent.to_path_buf()
+            }
+            _ => path.to_path_buf(),
+        })
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+    paths.dedup_by(|path, folder| path.starts_with(folder));
+
+    let walker_model = Arc::clone(&model);
+    index_jobs(model, workers, progress, move |jobs| {
+        for path in &paths {
+            walk_path(dir_path, path, &walker_model, filter, jobs)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Index the files found by `walk`. The walker sends the files to the `workers` reading and
+/// analyzing them in parallel as it finds them, and the results are added to the model by a single
+/// writer(the current thread).
+fn index_jobs<M, F>(
+    model: Arc<RwLock<M>>,
+    workers: usize,
+    progress: &Progress,
+    walk: F,
+) -> Result<Vec<PathBuf>, ()>
+where
+    M: Model + Send + Sync,
+    F: FnOnce(&mut JobQueue) -> Result<(), ()> + Send,
+{
+    // NOTE: the queue of the jobs is bounded, so the walker won't run too far ahead of the workers
+    let workers = workers.max(1);
+    let (job_sender, job_receiver) = mpsc::sync_channel::<Job>(workers * 4);
+    let job_receiver = Arc::new(Mutex::new(job_receiver));
+    let (extracted_sender, extracted_receiver) = mpsc::channel::<Extracted>();
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let job_receiver = Arc::clone(&job_receiver);
+            let extracted_sender = extracted_sender.clone();
+            scope.spawn(move || loop {
+                // NOTE: the lock is released once a job is taken, so the other workers could take
+                // the next one while this job is read
+                let job = job_receiver.lock().unwrap().recv();
+                match job {
+                    Ok(job) => {
+                        extracted_sender.send(read_job(job)).ok();
+                    }
+                    Err(_) => break,
+                }
+            });
+        }
+        drop(extracted_sender);
+
+        // NOTE: the totals of the progress grow while the folder is walked, the workers start
+        // reading the first files meanwhile
+        let walker = scope.spawn(move || {
+            let mut jobs = JobQueue {
+                sender: job_sender,
+                progress,
+                count: 0,
+            };
+            walk(&mut jobs)?;
+            if jobs.count > 0 {
+                progress.counted();
+            }
+
+            Ok(jobs.count)
+        });
+
+        // NOTE: the documents read while the last batch is written are taken at once as the next
+        // batch
+        let mut skipped_files = Vec::new();
+        let mut result = Ok(());
+        while let Ok(extracted) = extracted_receiver.recv() {
+            let mut batch = vec![extracted];
+            while batch.len() < WRITE_BATCH_SIZE {
+                match extracted_receiver.try_recv() {
+                    Ok(extracted) => batch.push(extracted),
+                    Err(_) => break,
+                }
+            }
+
+            for extracted in &batch {
+                progress.processed(extracted.bytes, extracted.documents.is_err());
+            }
+            if write_extracted(&model, batch, &mut skipped_files, progress.is_live()).is_err() {
+                result = Err(());
+            }
+        }
+
+        let total_files = walker
+            .join()
+            .map_err(|_| eprintln!("ERROR: the indexing is stopped unexpectedly"))??;
+        if total_files > 0 {
+            progress.finish();
+        }
+
+        result.map(|_| skipped_files)
+    })
+}
+
+/// Find the files to be reindexed at the changed path, the folder configs and the ignore rules
+/// of the folders above it are loaded from `dir_path`(the indexed folder)
+fn walk_path<M: Model>(
+    dir_path: &Path,
+    path: &Path,
+    model: &RwLock<M>,
+    filter: &IndexFilter,
+    jobs: &mut JobQueue,
+) -> Result<(), ()> {
+    let relative_path = match path.strip_prefix(dir_path) {
+        Ok(relative_path) => relative_path,
+        Err(_) => {
+            println!(
+                "INFO: {path} is not in the folder {dir_path}, skip it",
+                path = path.display(),
+                dir_path = dir_path.display()
+            );
+            return Ok(());
+        }
+    };
+
+    let metadata = match path.metadata() {
+        Ok(metadata) => metadata,
+        Err(_) => {
+            remove_indexed_files(&mut *model.write().unwrap(), path, "does not exist anymore");
+            return Ok(());
+        }
+    };
+
+    let mut config = FolderConfig::user();
+    let mut ignore_rules = IgnoreRules::global();
+    let mut folder = dir_path.to_path_buf();
+    let mut depth = 0;
+    for component in relative_path.components() {
+        config = FolderConfig::load(&folder, &config);
+        ignore_rules = IgnoreRules::load(&folder, &ignore_rules);
+        folder.push(component);
+        depth += 1;
+
+        let metadata = match folder.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => return Ok(()),
+        };
+        if ignore_rules.is_ignored(&folder, metadata.is_dir())
+            || filter.is_skipped(&folder, &metadata, depth)
+        {
+            remove_indexed_files(
+                &mut *model.write().unwrap(),
+                &folder,
+                "is not indexed anymore",
+            );
+            return Ok(());
+        }
+    }
+
+    if metadata.is_dir() {
+        remove_missing_files(&mut *model.write().unwrap(), path);
+        return walk_folder(path, model, &config, &ignore_rules, filter, depth + 1, jobs);
+    }
+
+    let last_modified = metadata.modified().map_err(|err| {
+        eprintln!(
+            "ERROR: could not get the last modified time of file {path}: {err}",
+            path = path.display()
+        )
+    })?;
+    if model
+        .read()
+        .unwrap()
+        .requires_reindexing(path, last_modified)
+    {
+        jobs.push(Job {
+            file_path: path.to_path_buf(),
+            metadata,
+            last_modified,
+            registry: Arc::new(ReaderRegistry::new(&config)),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Find the files to be reindexed in the folder and its sub-folders
+fn walk_folder<M: Model>(
+    dir_path: &Path,
+    model: &RwLock<M>,
+    config: &FolderConfig,
+    ignore_rules: &IgnoreRules,
+    filter: &IndexFilter,
+    depth: usize,
+    jobs: &mut JobQueue,
+) -> Result<(), ()> {
+    let dir = fs::read_dir(dir_path).map_err(|err| {
+        eprintln!(
+            "ERROR: could not open directory {dir_path} for indexing: {err}",
+            dir_path = dir_path.display()
+        )
+    })?;
+    let config = FolderConfig::load(dir_path, config);
+    let ignore_rules = IgnoreRules::load(dir_path, ignore_rules);
+    let registry = Arc::new(ReaderRegistry::new(&config));
+
+    let mut skipped_paths = Vec::new();
+    let mut folders = Vec::new();
+    let mut files = Vec::new();
+    for path in dir {
+        let file_path = path
+            .map_err(|err| {
+                eprintln!(
+                    "ERROR: could not read the file in directory: {dir_path} during indexing: {err}",
+                    dir_path = dir_path.display()
+                );
+            })?
+            .path();
+
+        let metadata = file_path.metadata().map_err(|err| {
+            eprintln!(
+                "ERROR: could not get the metadata of file {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+        if ignore_rules.is_ignored(&file_path, metadata.is_dir())
+            || filter.is_skipped(&file_path, &metadata, depth)
+        {
+            skipped_paths.push(file_path);
+        } else if metadata.is_dir() {
+            folders.push(file_path);
+        } else if file_path.file_name() == Some(FolderConfig::FILE_NAME.as_ref())
+            || file_path.file_name() == Some(IgnoreRules::FILE_NAME.as_ref())
+        {
+            continue;
+        } else {
+            let last_modified = metadata.modified().map_err(|err| {
+                eprintln!(
+                    "ERROR: could not get the last modified time of file {file_path}: {err}",
+                    file_path = file_path.display()
+                )
+            })?;
+            files.push((file_path, metadata, last_modified));
+        }
+    }
+
+    if !skipped_paths.is_empty() {
+        let mut model = model.write().unwrap();
+        for path in &skipped_paths {
+            remove_indexed_files(&mut *model, path, "is not indexed anymore");
+        }
+    }
+
+    // NOTE: the model is locked once for the whole folder rather than once for every file, and
+    // the searches read it meanwhile
+    let files = {
+        let model = model.read().unwrap();
+        files
+            .into_iter()
+            .filter(|(file_path, _, last_modified)| {
+                model.requires_reindexing(file_path, *last_modified)
+            })
+            .collect::<Vec<(PathBuf, fs::Metadata, SystemTime)>>()
+    };
+
+    for (file_path, metadata, last_modified) in files {
+        jobs.push(Job {
+            file_path,
+            metadata,
+            last_modified,
+            registry: Arc::clone(&registry),
+        })?;
+    }
+
+    for folder in folders {
+        walk_folder(
+            &folder,
+            model,
+            &config,
+            &ignore_rules,
+            filter,
+            depth + 1,
+            jobs,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Read the file and analyze its documents, it runs on the workers
+fn read_job(job: Job) -> Extracted {
+    let documents = job
+        .registry
+        .read_from_file(&job.file_path)
+        .map(|documents| {
+            documents
+                .into_iter()
+                .map(|mut document| {
+                    // NOTE: the documents in the file(e.g. the pages of a PDF, the rows of a CSV
+                    // or the files in an archive) don't share the size of the whole file
+                    if document.path == job.file_path {
+                        document.metadata.extend(file_metadata(&job.metadata));
+                    }
+                    AnalyzedDocument::new(document, job.last_modified)
+                })
+                .collect()
+        });
+
+    Extracted {
+        file_path: job.file_path,
+        last_modified: job.last_modified,
+        bytes: job.metadata.len(),
+        documents,
+    }
+}
+
+/// Add the documents read by the workers into the model. The whole batch is written under a single
+/// write lock, so the searches are blocked once for the batch rather than once for every file, and
+/// they never see a file partially added.
+fn write_extracted<M: Model>(
+    model: &RwLock<M>,
+    batch: Vec<Extracted>,
+    skipped_files: &mut Vec<PathBuf>,
+    quiet: bool,
+) -> Result<(), ()> {
+    let mut model = model.write().unwrap();
+    let mut result = Ok(());
+    for extracted in batch {
+        match extracted.documents {
+            Ok(documents) => {
+                // NOTE: the files are not listed while the live progress line is drawn
+                if !documents.is_empty() && !quiet {
+                    println!(
+                        "File path: {file_path}",
+                        file_path = extracted.file_path.display()
+                    );
+                }
+
+                if model
+                    .add_documents(extracted.file_path, documents, extracted.last_modified)
+                    .is_err()
+                {
+                    result = Err(());
+                }
+            }
+            Err(()) => {
+                // NOTE: the failure is recorded, so the file won't be read again until it's
+                // modified.
+                model.record_failure(extracted.file_path.clone(), extracted.last_modified);
+                skipped_files.push(extracted.file_path);
+            }
+        }
+    }
+
+    result
+}
+
+/// Remove the files indexed in the folder which don't exist anymore(e.g. the events of their
+/// removal are merged into the changes of the folder)
+fn remove_missing_files<M: Model>(model: &mut M, dir_path: &Path) {
+    let missing_files = model
+        .indexed_files_in(dir_path)
+        .into_iter()
+        .filter(|file| !file.exists())
+        .collect::<Vec<PathBuf>>();
+
+    for file in missing_files {
+        println!("{file} does not exist anymore", file = file.display());
+        model.remove_document(&file);
+    }
+}
+
+/// Remove the files indexed at the file or the folder at `path`(e.g. it's removed, or ignored or
+/// skipped by the rules)
+fn remove_indexed_files<M: Model>(model: &mut M, path: &Path, reason: &str) {
+    for file in model.indexed_files_in(path) {
+        println!("{file} {reason}", file = file.display());
+        model.remove_document(&file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{index_folder, index_paths};
+    use crate::config::IndexRules;
+    use crate::model::in_memory_index_model::{InMemoryIndexModel, Model};
+    use crate::progress::Progress;
+    use std::{
+        env, fs,
+        path::PathBuf,
+        process,
+        sync::{Arc, RwLock},
+    };
+
+    #[test]
+    fn index_folder_ok() -> Result<(), ()> {
+        // arrange
+        let root = env::temp_dir().join(format!("loser-indexer-test-{pid}", pid = process::id()));
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).map_err(|err| eprintln!("ERROR: {err}"))?;
+        for i in 0..20 {
+            fs::write(root.join(format!("andy{i}.txt")), format!("hello andy {i}"))
+                .map_err(|err| eprintln!("ERROR: {err}"))?;
+            fs::write(nested.join(format!("amy{i}.txt")), format!("hello amy {i}"))
+                .map_err(|err| eprintln!("ERROR: {err}"))?;
+        }
+        let filter = IndexRules::default().filter(&root)?;
+        let model = Arc::new(RwLock::new(InMemoryIndexModel::new()));
+
+        // act
+        let progress = Progress::new(false);
+        let skipped_files = index_folder(&root, Arc::clone(&model), &filter, 4, &progress)?;
+        let model = model.read().unwrap();
+        let actual = model
+            .search(&"amy".chars().collect::<Vec<char>>())?
+            .into_iter()
+            .filter(|(_, rank)| *rank > 0.0)
+            .collect::<Vec<_>>();
+        fs::remove_dir_all(&root).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert!(skipped_files.is_empty());
+        let report = progress.report();
+        assert!(!report.indexing);
+        assert_eq!((report.processed_files, report.total_files), (40, 40));
+        assert_eq!(model.files.len(), 40);
+        assert_eq!(actual.len(), 20);
+        assert!(actual.iter().all(|(path, _)| path.starts_with(&nested)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn index_paths_ok() -> Result<(), ()> {
+        // arrange
+        let root = env::temp_dir().join(format!("loser-paths-test-{pid}", pid = process::id()));
+        let docs = root.join("docs");
+        fs::create_dir_all(&docs).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let files = [
+            (root.join("removed.txt"), "hello andy"),
+            (docs.join("kept.txt"), "hello amy"),
+            (docs.join("debug.log"), "hello log"),
+            (docs.join("people.csv"), "name\nandy\n"),
+        ];
+        for (path, content) in &files {
+            fs::write(path, content).map_err(|err| eprintln!("ERROR: {err}"))?;
+        }
+        let filter = IndexRules::default().filter(&root)?;
+        let model = Arc::new(RwLock::new(InMemoryIndexModel::new()));
+        let progress = Progress::new(false);
+        index_folder(&root, Arc::clone(&model), &filter, 2, &progress)?;
+
+        fs::remove_file(root.join("removed.txt")).map_err(|err| eprintln!("ERROR: {err}"))?;
+        fs::write(docs.join("added.txt"), "hello zed").map_err(|err| eprintln!("ERROR: {err}"))?;
+        fs::write(docs.join(".loserignore"), "*.log\n").map_err(|err| eprintln!("ERROR: {err}"))?;
+        let changes = [
+            root.join("removed.txt"),
+            docs.join("added.txt"),
+            docs.join(".loserignore"),
+        ];
+
+        // act
+        let skipped_files =
+            index_paths(&root, &changes, Arc::clone(&model), &filter, 2, &progress)?;
+        let mut actual = model
+            .read()
+            .unwrap()
+            .files
+            .keys()
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        actual.sort();
+        let metadata_names = |path: PathBuf| {
+            model
+                .read()
+                .unwrap()
+                .document_metadata(&path)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<String>>()
+        };
+        let text_metadata = metadata_names(docs.join("kept.txt"));
+        let row_metadata = metadata_names(PathBuf::from(format!(
+            "{csv}#row=1",
+            csv = docs.join("people.csv").display()
+        )));
+        fs::remove_dir_all(&root).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert!(skipped_files.is_empty());
+        assert_eq!(
+            actual,
+            vec![
+                docs.join("added.txt"),
+                docs.join("kept.txt"),
+                docs.join("people.csv")
+            ]
+        );
+        assert!(text_metadata.contains(&"size".to_string()));
+        assert_eq!(row_metadata, vec!["name".to_string()]);
+
+        Ok(())
+    }
+}
@@ -1,3 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+/// The settings of the tokenization, they are stored in the index file since the index files made
+/// with the other settings hold the different terms
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AnalyzerConfig {
+    /// Increased whenever the tokens of the same text change
+    pub lexer_version: u32,
+    /// Whether the words are uppercased, so the search is case-insensitive
+    pub uppercase: bool,
+    /// Whether the fields and the metadata are indexed with the terms prefixed with their names
+    /// (e.g. "SUBJECT:HELLO")
+    pub field_terms: bool,
+}
+
+impl AnalyzerConfig {
+    /// The settings used by this version of LOSER
+    pub fn current() -> Self {
+        AnalyzerConfig {
+            lexer_version: 1,
+            uppercase: true,
+            field_terms: true,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Lexer<'a> {
     pub content: &'a [char],
@@ -35,6 +61,7 @@ impl<'a> Lexer<'a> {
     }
 
     /// Find the next token(individual word)
+    #[allow(clippy::needless_return)]
     pub fn next_token(&mut self) -> Option<String> {
         self.trim_left();
         if self.content.is_empty() {
@@ -115,6 +142,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_conversion)]
     fn next_token_ok() {
         // arrange
         let input = [
@@ -1,21 +1,38 @@
-use model::in_memory_index_model::{InMemoryIndexModel, Model};
-use reader::{pdf_reader::PDFReader, plain_text_reader::PlainTextReader, reader_trait::Reader};
-use serde::Deserialize;
-use serde_json;
+use config::{FolderConfig, IndexRules, SandboxConfig};
+use indexer::{default_workers, index_folder, index_paths};
+use model::{
+    binary_index::{self, SegmentedIndex},
+    in_memory_index_model::{InMemoryIndexModel, Model},
+    index_header::{Compatibility, IndexHeader},
+    persistent_model::{ModelStore, PersistentModel},
+    sqlite_model::{self, SqliteModel},
+};
+use progress::{format_bytes, Progress};
+use reader::registry::builtin_reader;
+use saver::{export_model_as_json, load_model, IndexSaver};
+use segments::SegmentStore;
 use std::{
-    env, fs,
-    io::BufWriter,
+    env,
+    io::{self, IsTerminal},
     path::{Path, PathBuf},
     process::{exit, ExitCode},
-    sync::{Arc, Mutex},
+    sync::{Arc, RwLock},
     thread,
     time::Duration,
 };
+use watcher::FolderWatcher;
 use web_server::WebServer;
 
+mod config;
+mod ignore_rules;
+mod indexer;
 mod lexer;
 mod model;
+mod progress;
 mod reader;
+mod saver;
+mod segments;
+mod watcher;
 mod web_server;
 
 fn main() -> ExitCode {
@@ -25,6 +42,7 @@ fn main() -> ExitCode {
     }
 }
 
+#[allow(clippy::needless_return)]
 fn entry() -> Result<(), ()> {
     let mut args = env::args();
     let program = args.next().expect("path to program doesn't be provided.");
@@ -51,22 +69,11 @@ fn entry() -> Result<(), ()> {
                 eprintln!("ERROR: no directory is provided for {subcommand} subcommand.");
             })?;
 
-            let folder_name = Path::new(&dir_path)
-                .file_name()
-                .ok_or_else(|| eprintln!("ERROR: could not extract the folder name: {dir_path}"))?;
-
-            let output_file_name = format!(
-                "{folder_name}.loser.json",
-                folder_name = folder_name.to_string_lossy().to_string()
-            );
-
-            println!("Indexing from scratch...");
-
-            let model = Arc::new(Mutex::new(InMemoryIndexModel::new()));
-            add_folder_to_model(&dir_path, Arc::clone(&model))?;
-
-            let model = &*model.lock().unwrap();
-            save_mode_as_json(model, &Path::new(&output_file_name))?;
+            let options = parse_index_options(&args.collect::<Vec<String>>())?;
+            match options.backend {
+                Backend::Memory => index::<InMemoryIndexModel>(&dir_path, options)?,
+                Backend::Sqlite => index::<SqliteModel>(&dir_path, options)?,
+            }
         }
         "search" => {
             let index_path = args.next().ok_or_else(|| {
@@ -83,105 +90,130 @@ fn entry() -> Result<(), ()> {
                 .chars()
                 .collect::<Vec<char>>();
 
-            let index_file = fs::File::open(&index_path).map_err(|err| {
-                eprintln!("ERROR: could not open the index file {index_path}: {err}");
-            })?;
+            if sqlite_model::is_sqlite_file(Path::new(&index_path)) {
+                let model = SqliteModel::open(Path::new(&index_path))?;
+                check_searchable(&model.header()?, &index_path)?;
+                print_results(
+                    model.search(&query)?,
+                    |path| model.best_passage(path, &query),
+                    |path| model.document_metadata(path),
+                );
+                return Ok(());
+            }
+
+            // NOTE: the segments are mapped into the memory, so only the parts needed by the query
+            // are read. The JSON index files and the corrupt ones(recovered from the backup) are
+            // loaded as a whole.
+            let segment_paths = if Path::new(&index_path).is_dir() {
+                SegmentStore::open(Path::new(&index_path), false)
+                    .map(|store| store.segment_paths())
+                    .ok()
+            } else if binary_index::is_binary_file(Path::new(&index_path)) {
+                Some(vec![PathBuf::from(&index_path)])
+            } else {
+                None
+            };
+            let index = segment_paths.and_then(|paths| SegmentedIndex::open(&paths).ok());
+            match index {
+                Some(index) => {
+                    if let Some(header) = index.header() {
+                        check_searchable(header, &index_path)?;
+                    }
+                    print_results(
+                        index.search(&query)?,
+                        |path| index.best_passage(path, &query),
+                        |path| index.document_metadata(path),
+                    );
+                }
+                None => {
+                    let loaded = load_model(Path::new(&index_path)).ok_or_else(|| {
+                        eprintln!("ERROR: could not load the index file {index_path}")
+                    })?;
+                    check_searchable(&loaded.header, &index_path)?;
+                    let model = loaded.model;
+                    print_results(
+                        model.search(&query)?,
+                        |path| model.best_passage(path, &query),
+                        |path| model.document_metadata(path),
+                    );
+                }
+            }
 
-            let mut data = serde_json::Deserializer::from_reader(index_file);
-            let model = InMemoryIndexModel::deserialize(&mut data).map_err(|err| {
-                eprintln!("ERROR: could not parse the index file {index_path}: {err}");
+            return Ok(());
+        }
+        "export" => {
+            let index_path = args.next().ok_or_else(|| {
+                prompt_usage(&program);
+                eprintln!("ERROR: no path to index is provided for {subcommand} subcommand.");
+            })?;
+            let json_path = args.next().ok_or_else(|| {
+                prompt_usage(&program);
+                eprintln!("ERROR: no path to JSON file is provided for {subcommand} subcommand.");
             })?;
 
-            for (path, rank) in model.search(&query)?.iter().take(10) {
-                println!("File Path: {path} | Rank: {rank}", path = path.display());
+            let model = load_model(Path::new(&index_path))
+                .ok_or_else(|| eprintln!("ERROR: could not load the index file {index_path}"))?
+                .model;
+            export_model_as_json(&model, Path::new(&json_path))?;
+            println!("INFO: exported the index file {index_path} to {json_path}");
+        }
+        "stats" => {
+            let index_path = args.next().ok_or_else(|| {
+                prompt_usage(&program);
+                eprintln!("ERROR: no path to index is provided for {subcommand} subcommand.");
+            })?;
+            if sqlite_model::is_sqlite_file(Path::new(&index_path)) {
+                eprintln!("ERROR: the stats are only reported for the index kept in the memory, {index_path} is a SQLite database");
+                return Err(());
             }
 
-            return Ok(());
+            let model = load_model(Path::new(&index_path))
+                .ok_or_else(|| eprintln!("ERROR: could not load the index file {index_path}"))?
+                .model;
+            print_stats(&model);
+        }
+        "extract" => {
+            // NOTE: an internal subcommand used by SandboxedReader to run a reader in a subprocess
+            let reader_name = args.next().ok_or_else(|| {
+                eprintln!("ERROR: no reader is provided for {subcommand} subcommand.")
+            })?;
+            let file_path = args.next().ok_or_else(|| {
+                eprintln!("ERROR: no file is provided for {subcommand} subcommand.")
+            })?;
+            let mut config: FolderConfig =
+                serde_json::from_str(&args.next().unwrap_or_default())
+                    .map_err(|err| eprintln!("ERROR: could not parse the folder config: {err}"))?;
+            // NOTE: the readers are already in the sandbox, don't spawn the subprocess again
+            config.sandbox = Some(SandboxConfig {
+                readers: Vec::new(),
+                ..config.sandbox()
+            });
+
+            let reader = builtin_reader(&reader_name, &config)
+                .ok_or_else(|| eprintln!("ERROR: unknown reader {reader_name}."))?;
+            let documents = reader.read_documents(Path::new(&file_path))?;
+            let documents = serde_json::to_string(&documents)
+                .map_err(|err| eprintln!("ERROR: could not serialize the documents: {err}"))?;
+            println!();
+            println!("{documents}");
         }
         "server" => {
             let dir_path = args.next().ok_or_else(|| {
                 prompt_usage(&program);
                 eprintln!("ERROR: no folder is provided for {subcommand} subcommand.")
             })?;
-            let index_path = Path::new(&format!("{dir_path}.loser.json")).to_path_buf();
-
-            let port = args.next().unwrap_or("8080".to_string());
-            let addr = format!("127.0.0.1:{port}");
-
-            let is_existed = index_path.try_exists().map_err(|err| {
-                eprintln!(
-                    "ERROR: could not check the existence of file {index_path}: {err}",
-                    index_path = index_path.display()
-                )
-            })?;
-
-            // TODO: how to make it more generic?
-            let model: Arc<Mutex<InMemoryIndexModel>>;
-
-            if is_existed {
-                let index_file = fs::File::open(&index_path).map_err(|err| {
-                    eprintln!(
-                        "ERROR: could not open the index file {index_path}: {err}",
-                        index_path = index_path.display()
-                    )
-                })?;
-
-                model = Arc::new(Mutex::<InMemoryIndexModel>::new(
-                    serde_json::from_reader(&index_file).map_err(|err| {
-                        eprintln!(
-                            "ERROR: could not parse the index file {index_path}: {err}",
-                            index_path = index_path.display()
-                        )
-                    })?,
-                ));
+            let mut options = args.collect::<Vec<String>>();
+            let port = if options.first().is_some_and(|x| !x.starts_with("--")) {
+                options.remove(0)
             } else {
-                model = Arc::new(Mutex::<InMemoryIndexModel>::new(Default::default()));
-            }
-
-            {
-                // TODO: what to do if this thread broken?
-                let model = Arc::clone(&model);
-
-                thread::spawn(move || -> Result<(), ()> {
-                    loop {
-                        // TODO: checking if the files existed need to be refactored
-                        let mut removed_files: Vec<PathBuf> = Vec::new();
-                        for path in model.lock().unwrap().docs.keys() {
-                            if path.try_exists().map_err(|err| {
-                                eprintln!(
-                                    "ERROR: could not check if the file {path} is existed: {err}",
-                                    path = path.display()
-                                )
-                            })? {
-                                continue;
-                            }
-
-                            println!("{file} does not exist anymore", file = path.display());
-                            removed_files.push(path.clone());
-                        }
-
-                        for file in removed_files {
-                            model.lock().unwrap().remove_document(&file)
-                        }
-
-                        add_folder_to_model(
-                            &Path::new(&dir_path).to_string_lossy().to_string(),
-                            Arc::clone(&model),
-                        )?;
-                        let model = model.lock().unwrap();
-                        save_mode_as_json(&model, &index_path)?;
-
-                        drop(model);
-                        println!("Finished indexing...");
-
-                        thread::sleep(Duration::from_secs(1));
-                    }
-                });
+                "8080".to_string()
+            };
+            let addr = format!("127.0.0.1:{port}");
+            let options = parse_index_options(&options)?;
+            match options.backend {
+                Backend::Memory => serve::<InMemoryIndexModel>(dir_path, &addr, options)?,
+                Backend::Sqlite => serve::<SqliteModel>(dir_path, &addr, options)?,
             }
-
-            let server = WebServer::new(addr.as_str(), model);
-
-            server.start()?;
         }
         _ => {
             prompt_usage(&program);
@@ -192,94 +224,431 @@ fn entry() -> Result<(), ()> {
     return Ok(());
 }
 
-fn read_from_file(file_path: &Path) -> Result<String, ()> {
-    let extension = file_path
-        .extension()
-        .ok_or_else(|| {
-            eprintln!("ERROR: could not detect file type of {file_path:?} without extension.")
-        })?
-        .to_string_lossy();
-
-    match extension.as_ref() {
-        "txt" => PlainTextReader::read_text(&file_path),
-        "pdf" => PDFReader::read_text(&file_path),
-        // "xml" => todo!(),
-        _ => {
-            eprintln!("ERROR: The file type: {extension} has not been supported yet.");
+/// The index file made by the different analyzer could not be searched, since the terms of the
+/// query don't match its terms
+fn check_searchable(header: &IndexHeader, index_path: &str) -> Result<(), ()> {
+    match header.compatibility(&[]) {
+        Compatibility::Incompatible(reason) => {
+            eprintln!("ERROR: {reason}, index the folder again to search {index_path}");
             Err(())
         }
+        _ => Ok(()),
     }
 }
 
-fn add_folder_to_model(dir_path: &str, model: Arc<Mutex<InMemoryIndexModel>>) -> Result<(), ()> {
-    let dir = fs::read_dir(dir_path).map_err(|err| {
-        eprintln!("ERROR: could not open directory {dir_path} for indexing: {err}")
-    })?;
+/// Print the top results with the best passages and the metadata
+fn print_results<P, M>(ranks: Vec<(PathBuf, f32)>, best_passage: P, document_metadata: M)
+where
+    P: Fn(&Path) -> Option<String>,
+    M: Fn(&Path) -> Vec<(String, String)>,
+{
+    for (path, rank) in ranks.iter().take(10) {
+        match best_passage(path) {
+            Some(anchor) => println!(
+                "File Path: {path} | Rank: {rank} | At: {anchor}",
+                path = path.display()
+            ),
+            None => println!("File Path: {path} | Rank: {rank}", path = path.display()),
+        }
+        for (name, value) in document_metadata(path) {
+            println!("    {name}: {value}");
+        }
+    }
+}
 
-    for path in dir {
-        let file_path = path.map_err(|err| {
-            eprintln!("ERROR: could not read the file in directory: {dir_path} during indexing: {err}");
-        })?.path();
+/// Print the counts of the model and the memory it takes once it's loaded(e.g. by the server)
+fn print_stats(model: &InMemoryIndexModel) {
+    let usage = model.memory_usage();
+    println!("Documents: {count}", count = model.docs.len());
+    println!(
+        "Files: {count}({failed} failed)",
+        count = model.files.len() + model.failed_files.len(),
+        failed = model.failed_files.len()
+    );
+    println!("Terms: {count}", count = model.terms.len());
+    println!("Postings: {count}", count = model.posting_count());
+    println!(
+        "Memory usage: {total}",
+        total = format_bytes(usage.total() as f64)
+    );
+    for (name, bytes) in [
+        ("terms", usage.terms),
+        ("postings", usage.postings),
+        ("documents", usage.docs),
+        ("files", usage.files),
+    ] {
+        println!("    {name}: {bytes}", bytes = format_bytes(bytes as f64));
+    }
+}
 
-        let last_modified = file_path
-            .metadata()
-            .map_err(|err| {
-                eprintln!(
-                    "ERROR: could not get the metadata of file {file_path}: {err}",
-                    file_path = file_path.display()
-                )
-            })?
-            .modified()
-            .map_err(|err| {
-                eprintln!(
-                    "ERROR: could not get the last modified time of file {file_path}: {err}",
-                    file_path = file_path.display()
-                )
-            })?;
+/// Index the folder from scratch and save the index to '<folder>.<extension>'
+fn index<M: PersistentModel>(dir_path: &str, options: IndexOptions) -> Result<(), ()> {
+    let folder_name = Path::new(dir_path)
+        .file_name()
+        .ok_or_else(|| eprintln!("ERROR: could not extract the folder name: {dir_path}"))?;
+    let index_path = PathBuf::from(format!(
+        "{folder_name}.{extension}",
+        folder_name = folder_name.to_string_lossy(),
+        extension = M::EXTENSION
+    ));
+
+    let rules = options.rules.unwrap_or_default();
+    // NOTE: the index file of the last run is never indexed, when it's saved inside the folder
+    let filter = rules.filter(Path::new(dir_path))?.skip_index(&index_path);
+
+    println!("Indexing from scratch...");
+
+    let mut model = M::create_index(&index_path)?;
+    model.set_rules(rules);
+    model.set_roots(vec![PathBuf::from(dir_path)]);
+    let model = Arc::new(RwLock::new(model));
+    // NOTE: the live progress line is drawn only on the terminal, the files are listed
+    // otherwise
+    let progress = Progress::new(io::stderr().is_terminal());
+    let skipped_files = index_folder(
+        Path::new(dir_path),
+        Arc::clone(&model),
+        &filter,
+        options.workers,
+        &progress,
+    )?;
+    report_skipped_files(&skipped_files);
+
+    let model = Arc::into_inner(model)
+        .map(|model| model.into_inner().unwrap())
+        .ok_or_else(|| eprintln!("ERROR: the model is still used after the indexing"))?;
+    model.save_index(&index_path, options.backup)
+}
+
+/// Serve the search of the folder on `addr`, the folder is indexed into '<folder>.<extension>' and
+/// watched for the changes
+fn serve<M: PersistentModel>(
+    dir_path: String,
+    addr: &str,
+    options: IndexOptions,
+) -> Result<(), ()> {
+    let IndexOptions {
+        rules,
+        workers,
+        poll,
+        save_interval,
+        backup,
+        backend: _,
+    } = options;
+    if let Some(rules) = &rules {
+        rules.filter(Path::new(&dir_path))?;
+    }
+
+    let index_path = PathBuf::from(format!("{dir_path}.{extension}", extension = M::EXTENSION));
+    let roots = vec![PathBuf::from(&dir_path)];
+    let (model, store) = M::open_index(&index_path, &roots, backup)?;
+    let store = Arc::new(store);
+    let model = Arc::new(RwLock::new(model));
+
+    // NOTE: the changes are saved at most once every interval, and the last changes are saved
+    // when the server is stopped(e.g. Ctrl-C)
+    let saver = Arc::new(IndexSaver::<M>::new(
+        Arc::clone(&model),
+        Arc::clone(&store),
+        save_interval,
+    ));
+    {
+        let saver = Arc::clone(&saver);
+        thread::spawn(move || saver.run());
+    }
+    thread::spawn(move || store.maintain());
+    {
+        let saver = Arc::clone(&saver);
+        ctrlc::set_handler(move || match saver.flush() {
+            Ok(()) => exit(0),
+            Err(()) => exit(1),
+        })
+        .map_err(|err| eprintln!("ERROR: could not handle the stop of the server: {err}"))?;
+    }
 
-        if file_path.is_dir() {
-            add_folder_to_model(&file_path.to_string_lossy().to_string(), Arc::clone(&model))?
-        } else if model
-            .lock()
-            .unwrap()
-            .requires_reindexing(&file_path, last_modified)
-        {
-            match read_from_file(&file_path) {
-                Ok(content) => {
-                    println!("File path: {file_path}", file_path = file_path.display());
-
-                    model.lock().unwrap().add_document(
-                        file_path,
-                        &content.chars().collect::<Vec<char>>(),
-                        last_modified,
-                    )?;
+    // NOTE: the rules given in the options replace the ones stored in the index file
+    if let Some(rules) = rules {
+        model.write().unwrap().set_rules(rules);
+    }
+    model.write().unwrap().set_roots(roots);
+
+    let progress = Arc::new(Progress::new(false));
+    // NOTE: the folder is watched before it's indexed, so the changes during the indexing are not
+    // missed
+    let watcher = FolderWatcher::new(Path::new(&dir_path), &index_path, poll)?;
+    {
+        let model = Arc::clone(&model);
+        let progress = Arc::clone(&progress);
+
+        // NOTE: the errors are reported and the whole folder is reindexed on the next changes, so
+        // the thread won't stop on a broken file.
+        thread::spawn(move || {
+            let mut failed = reindex_folder(
+                &dir_path,
+                &index_path,
+                Arc::clone(&model),
+                workers,
+                &progress,
+            )
+            .is_err();
+
+            loop {
+                let paths = match watcher.changed_paths() {
+                    Ok(paths) => paths,
+                    Err(()) => return,
+                };
+
+                let result = if failed {
+                    reindex_folder(
+                        &dir_path,
+                        &index_path,
+                        Arc::clone(&model),
+                        workers,
+                        &progress,
+                    )
+                } else {
+                    reindex_paths(
+                        &dir_path,
+                        &index_path,
+                        &paths,
+                        Arc::clone(&model),
+                        workers,
+                        &progress,
+                    )
+                };
+                failed = result.is_err();
+                if failed {
+                    eprintln!("ERROR: could not reindex the folder {dir_path}, retry on the next changes...");
                 }
-                Err(()) => continue,
             }
+        });
+    }
+
+    let server = WebServer::new(addr, model, progress);
+
+    server.start()
+}
+
+fn reindex_folder<M: Model + Send + Sync>(
+    dir_path: &str,
+    index_path: &Path,
+    model: Arc<RwLock<M>>,
+    workers: usize,
+    progress: &Progress,
+) -> Result<(), ()> {
+    // TODO: checking if the files existed need to be refactored
+    let mut removed_files: Vec<PathBuf> = Vec::new();
+    {
+        for path in model.read().unwrap().indexed_files() {
+            if path.try_exists().map_err(|err| {
+                eprintln!(
+                    "ERROR: could not check if the file {path} is existed: {err}",
+                    path = path.display()
+                )
+            })? {
+                continue;
+            }
+
+            println!("{file} does not exist anymore", file = path.display());
+            removed_files.push(path);
         }
     }
 
+    for file in removed_files {
+        model.write().unwrap().remove_document(&file)
+    }
+
+    // NOTE: the rules stored in the index file are used, so the folder is reindexed by the same
+    // rules every time. The index file is skipped when it's saved inside the folder(e.g.
+    // `server .`).
+    let filter = model
+        .read()
+        .unwrap()
+        .rules()
+        .filter(Path::new(dir_path))?
+        .skip_index(index_path);
+    let skipped_files = index_folder(
+        Path::new(dir_path),
+        Arc::clone(&model),
+        &filter,
+        workers,
+        progress,
+    )?;
+    report_skipped_files(&skipped_files);
+    println!("Finished indexing...");
+
     Ok(())
 }
 
-fn save_mode_as_json(model: &InMemoryIndexModel, file_path: &Path) -> Result<(), ()> {
-    let file = fs::File::create(file_path).map_err(|err| {
-        eprintln!(
-            "ERROR: could not create the index file: {file_path}: {err}",
-            file_path = file_path.display()
-        )
-    })?;
-
-    serde_json::to_writer(BufWriter::new(file), model)
-        .map_err(|err| eprintln!("ERROR: could not serialize index into the index file: {err}"))?;
+/// Reindex the changed files and folders reported by the watcher
+fn reindex_paths<M: Model + Send + Sync>(
+    dir_path: &str,
+    index_path: &Path,
+    paths: &[PathBuf],
+    model: Arc<RwLock<M>>,
+    workers: usize,
+    progress: &Progress,
+) -> Result<(), ()> {
+    let filter = model
+        .read()
+        .unwrap()
+        .rules()
+        .filter(Path::new(dir_path))?
+        .skip_index(index_path);
+    let skipped_files = index_paths(
+        Path::new(dir_path),
+        paths,
+        Arc::clone(&model),
+        &filter,
+        workers,
+        progress,
+    )?;
+    report_skipped_files(&skipped_files);
+    println!("Finished indexing the changes...");
 
     Ok(())
 }
 
+fn report_skipped_files(skipped_files: &[PathBuf]) {
+    if skipped_files.is_empty() {
+        return;
+    }
+
+    println!(
+        "INFO: {count} file(s) could not be read and were skipped:",
+        count = skipped_files.len()
+    );
+    for file_path in skipped_files {
+        println!("    {file_path}", file_path = file_path.display());
+    }
+}
+
+/// Where the model is kept
+enum Backend {
+    /// In the memory, saved as the segments of the index folder
+    Memory,
+    /// In a SQLite database('<folder>.loser.db'), so the other tools could query it
+    Sqlite,
+}
+
+/// The options of the index and the server subcommands
+struct IndexOptions {
+    /// The rules, nothing when no rule is given
+    rules: Option<IndexRules>,
+    /// The number of threads reading the files, it's not stored in the index file
+    workers: usize,
+    /// Whether the server polls the changes rather than watching the events of the file system
+    poll: bool,
+    /// The shortest interval between the saves of the index file by the server
+    save_interval: Duration,
+    /// Whether the previous index file is kept as the backup when it's saved
+    backup: bool,
+    backend: Backend,
+}
+
+/// Parse the options(e.g. "--include **/*.md --max-depth 3 --workers 4")
+fn parse_index_options(options: &[String]) -> Result<IndexOptions, ()> {
+    let mut rules: Option<IndexRules> = None;
+    let mut workers = default_workers();
+    let mut poll = false;
+    let mut backup = false;
+    let mut save_interval = Duration::from_secs(5);
+    let mut backend = Backend::Memory;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        if option == "--no-hidden" {
+            rules.get_or_insert_with(Default::default).hidden = false;
+            continue;
+        }
+        if option == "--poll" {
+            poll = true;
+            continue;
+        }
+        if option == "--backup" {
+            backup = true;
+            continue;
+        }
+
+        let value = options
+            .next()
+            .ok_or_else(|| eprintln!("ERROR: no value is provided for the option {option}."))?;
+        let invalid_value = |err: std::num::ParseIntError| {
+            eprintln!("ERROR: invalid value {value} for the option {option}: {err}")
+        };
+        match option.as_str() {
+            "--workers" => {
+                workers = value.parse().map_err(invalid_value)?;
+                if workers == 0 {
+                    eprintln!("ERROR: the option {option} should be at least 1.");
+                    return Err(());
+                }
+            }
+            "--backend" => {
+                backend = match value.as_str() {
+                    "memory" => Backend::Memory,
+                    "sqlite" => Backend::Sqlite,
+                    _ => {
+                        eprintln!("ERROR: unknown backend {value}, it should be memory or sqlite.");
+                        return Err(());
+                    }
+                }
+            }
+            "--save-interval" => {
+                save_interval = Duration::from_secs(value.parse().map_err(invalid_value)?)
+            }
+            "--include" => rules
+                .get_or_insert_with(Default::default)
+                .include
+                .push(value.to_string()),
+            "--exclude" => rules
+                .get_or_insert_with(Default::default)
+                .exclude
+                .push(value.to_string()),
+            "--max-file-size" => {
+                rules.get_or_insert_with(Default::default).max_file_size =
+                    Some(value.parse().map_err(invalid_value)?)
+            }
+            "--max-depth" => {
+                rules.get_or_insert_with(Default::default).max_depth =
+                    Some(value.parse().map_err(invalid_value)?)
+            }
+            _ => {
+                eprintln!("ERROR: unknown option {option}.");
+                return Err(());
+            }
+        }
+    }
+
+    Ok(IndexOptions {
+        rules,
+        workers,
+        poll,
+        save_interval,
+        backup,
+        backend,
+    })
+}
+
 fn prompt_usage(program: &str) {
     eprintln!("Usage: {program} [SUBCOMMAND] [OPTIONS]");
     eprintln!("Subcommands and options:");
-    eprintln!("     index <folder>                    index the <folder> from scratch and save the index to '<folder>.loser.json' file");
+    eprintln!("     index <folder> [options]          index the <folder> from scratch and save the index to '<folder>.loser' folder");
     eprintln!("     search <index-file> <query>       search <query> within the <index-file>");
-    eprintln!("     server <folder> [port]            search on local HTTP server within files in <folder>");
+    eprintln!(
+        "     export <index-file> <json-file>   export the <index-file> as JSON to <json-file>"
+    );
+    eprintln!("     stats <index-file>                report the counts of the documents and the terms in the <index-file>, and the memory they take");
+    eprintln!("     server <folder> [port] [options]  search on local HTTP server within files in <folder>");
+    eprintln!("Rules(stored in the index file and used whenever the folder is reindexed):");
+    eprintln!("     --include <glob>                  only index the files matching the <glob>(e.g. '**/*.md'), could be repeated");
+    eprintln!("     --exclude <glob>                  never index the files and folders matching the <glob>(e.g. '**/secrets/**'), could be repeated");
+    eprintln!("     --max-file-size <bytes>           skip the files larger than <bytes>");
+    eprintln!("     --max-depth <depth>               skip the files deeper than <depth>, the files in <folder> are at depth 1");
+    eprintln!("     --no-hidden                       skip the hidden files and folders");
+    eprintln!("Other options of index and server:");
+    eprintln!("     --workers <count>                 read the files with <count> threads, the number of CPUs by default");
+    eprintln!("     --backend <memory|sqlite>         keep the index in the memory and save it into '<folder>.loser' folder, or write it into '<folder>.loser.db' SQLite database, memory by default");
+    eprintln!("     --backup                          keep the previous manifest of the index folder as 'manifest.json.bak' when it's saved, it's loaded if the manifest is corrupt");
+    eprintln!("     --poll                            (server only) poll the changes every 2 seconds instead of watching the events(e.g. for the network shares)");
+    eprintln!("     --save-interval <seconds>         (server only) save the changes of the index file at most once every <seconds>, 5 by default");
 }
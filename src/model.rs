@@ -1 +1,7 @@
+pub mod analyzer;
+pub mod binary_index;
 pub mod in_memory_index_model;
+pub mod index_header;
+pub mod persistent_model;
+pub mod sqlite_model;
+pub mod term_dictionary;
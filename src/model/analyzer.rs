@@ -0,0 +1,116 @@
+use super::super::lexer::Lexer;
+use super::super::reader::reader_trait::Document;
+use std::{collections::HashMap, path::PathBuf, time::SystemTime};
+
+/// The terms with their frequencies
+pub type TermFreq = HashMap<String, usize>;
+
+/// The document with its terms counted. The documents are analyzed by the indexing workers in
+/// parallel, and then added to the model by a single writer, which interns the terms.
+#[derive(Debug, PartialEq)]
+pub struct AnalyzedDocument {
+    pub(super) path: PathBuf,
+    pub(super) tf: TermFreq,
+    pub(super) total_tokens: usize,
+    pub(super) last_modified: SystemTime,
+    pub(super) fields: HashMap<String, String>,
+    pub(super) metadata: HashMap<String, String>,
+    /// The anchors and the term frequencies of the passages
+    pub(super) passages: Vec<(String, TermFreq)>,
+}
+
+impl AnalyzedDocument {
+    pub fn new(document: Document, last_modified: SystemTime) -> Self {
+        let mut tf = TermFreq::new();
+        let mut count = 0;
+
+        for token in Lexer::new(&document.content.chars().collect::<Vec<char>>()) {
+            tf.entry(token).and_modify(|v| *v += 1).or_insert(1);
+            count += 1;
+        }
+
+        // NOTE: the value of a field is searchable by its plain terms, and by the terms prefixed with
+        // the field name(e.g. "SUBJECT:HELLO") for the query like "subject:hello". The metadata is
+        // only searchable by the prefixed terms, and it's not counted in the length of the document.
+        let mut fields = HashMap::new();
+        for (name, value) in document.fields {
+            let name = name.to_lowercase();
+            for token in Lexer::new(&value.chars().collect::<Vec<char>>()) {
+                tf.entry(format!("{}:{token}", name.to_uppercase()))
+                    .and_modify(|v| *v += 1)
+                    .or_insert(1);
+                tf.entry(token).and_modify(|v| *v += 1).or_insert(1);
+                count += 1;
+            }
+            insert_value(&mut fields, name, value);
+        }
+
+        let mut metadata = HashMap::new();
+        for (name, value) in document.metadata {
+            let name = name.to_lowercase();
+            for token in Lexer::new(&value.chars().collect::<Vec<char>>()) {
+                tf.entry(format!("{}:{token}", name.to_uppercase()))
+                    .and_modify(|v| *v += 1)
+                    .or_insert(1);
+            }
+            insert_value(&mut metadata, name, value);
+        }
+
+        let passages = document
+            .passages
+            .into_iter()
+            .map(|passage| {
+                let mut tf = TermFreq::new();
+                for token in Lexer::new(&passage.text.chars().collect::<Vec<char>>()) {
+                    tf.entry(token).and_modify(|v| *v += 1).or_insert(1);
+                }
+                (passage.anchor, tf)
+            })
+            .collect();
+
+        AnalyzedDocument {
+            path: document.path,
+            tf,
+            total_tokens: count,
+            last_modified,
+            fields,
+            metadata,
+            passages,
+        }
+    }
+}
+
+/// Insert the value of the field, the values of the same field(e.g. the sections of a document)
+/// are joined
+fn insert_value(values: &mut HashMap<String, String>, name: String, value: String) {
+    match values.get_mut(&name) {
+        Some(joined) => {
+            joined.push_str("; ");
+            joined.push_str(&value);
+        }
+        None => {
+            values.insert(name, value);
+        }
+    }
+}
+
+/// Lex the query into terms, and merge the field queries like "subject:hello" into a single term
+pub fn query_terms(query: &[char]) -> Vec<String> {
+    let tokens = Lexer::new(query).collect::<Vec<String>>();
+    let mut terms = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_field = i + 2 < tokens.len()
+            && tokens[i + 1] == ":"
+            && tokens[i].chars().all(|x| x.is_alphabetic());
+        if is_field {
+            terms.push(format!("{}:{}", tokens[i], tokens[i + 2]));
+            i += 3;
+        } else {
+            terms.push(tokens[i].clone());
+            i += 1;
+        }
+    }
+
+    terms
+}
@@ -0,0 +1,978 @@
+use super::{
+    analyzer::{query_terms, TermFreq},
+    in_memory_index_model::{Doc, InMemoryIndexModel, SourceFile, Tombstones},
+    index_header::{IndexHeader, FORMAT_VERSION},
+};
+use memmap2::Mmap;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The anchors and the terms of the passages of a document, as they're saved in the index file
+type Passages = Vec<(String, TermFreq)>;
+
+// NOTE: the binary index file is made of:
+//
+// - the header: the magic bytes, the format version(u32), the index header(JSON prefixed with its
+//   length as u32), and the counts and the offsets of the tables(u64 each)
+// - the documents: the path, the total tokens, the last modified time, the fields, the metadata and
+//   the passages of every document, sorted by the paths
+// - the document table: the offsets(u64) of the documents, the position of a document in it is its
+//   id
+// - the terms: every term with the number of the documents holding it, and its postings(the delta
+//   of the document id and the term frequency), sorted by the terms
+// - the term table: the offsets(u64) of the terms
+// - the source files, the failed files and the rules(JSON)
+// - the tombstones: the paths of the documents and the source files removed from the older
+//   segments
+//
+// The integers except the ones in the header and the tables are varints(LEB128), and the strings
+// are prefixed by their lengths. The tables have the fixed size entries, so a document or a term is
+// found by the binary search without reading the whole file.
+//
+// The files of the format version 1 have no index header, the tables follow the format version. The
+// files before the format version 3 have no tombstones.
+pub const MAGIC: &[u8; 8] = b"LOSERIDX";
+
+/// Whether the bytes are the binary index file rather than the JSON one
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Whether the file is the binary index file, by its magic bytes
+pub fn is_binary_file(file_path: &Path) -> bool {
+    format_version(file_path).is_some()
+}
+
+/// The format version of the binary index file, nothing when it's not a binary index file
+pub fn format_version(file_path: &Path) -> Option<u32> {
+    let mut bytes = [0; MAGIC.len() + 4];
+    fs::File::open(file_path)
+        .and_then(|mut file| file.read_exact(&mut bytes))
+        .ok()?;
+    if !is_binary(&bytes) {
+        return None;
+    }
+
+    Cursor::at(&bytes, MAGIC.len()).u32().ok()
+}
+
+/// Encode a segment of the index file, the tombstones hide the documents and the source files in
+/// the older segments
+pub fn encode_segment(model: &InMemoryIndexModel, tombstones: &Tombstones) -> Vec<u8> {
+    let mut paths = model.docs.keys().collect::<Vec<&PathBuf>>();
+    paths.sort();
+    let ids = paths
+        .iter()
+        .enumerate()
+        .map(|(id, path)| (*path, id))
+        .collect::<HashMap<&PathBuf, usize>>();
+
+    // NOTE: the postings are collected in the order of the document ids, so the deltas are never
+    // negative
+    let mut postings: BTreeMap<&str, Vec<(usize, usize)>> = BTreeMap::new();
+    for (id, path) in paths.iter().enumerate() {
+        for (term, count) in model.doc_terms(&model.docs[*path]) {
+            postings.entry(term).or_default().push((id, count));
+        }
+    }
+
+    let header = serde_json::to_vec(&IndexHeader::current(model.roots.clone()))
+        .expect("the index header should be serializable");
+    let mut bytes = Vec::new();
+    bytes.extend(MAGIC);
+    bytes.extend(FORMAT_VERSION.to_le_bytes());
+    bytes.extend((header.len() as u32).to_le_bytes());
+    bytes.extend(header);
+    let tables_offset = bytes.len();
+    bytes.resize(tables_offset + 8 * 5, 0);
+
+    let mut doc_offsets = Vec::with_capacity(paths.len());
+    for path in &paths {
+        doc_offsets.push(bytes.len() as u64);
+        write_doc(&mut bytes, path, &model.docs[*path], model);
+    }
+    let doc_table = bytes.len();
+    for offset in &doc_offsets {
+        bytes.extend(offset.to_le_bytes());
+    }
+
+    let mut term_offsets = Vec::with_capacity(postings.len());
+    for (term, postings) in &postings {
+        term_offsets.push(bytes.len() as u64);
+        write_str(&mut bytes, term);
+        write_varint(&mut bytes, postings.len() as u64);
+        let mut previous = 0;
+        for (id, count) in postings {
+            write_varint(&mut bytes, (id - previous) as u64);
+            write_varint(&mut bytes, *count as u64);
+            previous = *id;
+        }
+    }
+    let term_table = bytes.len();
+    for offset in &term_offsets {
+        bytes.extend(offset.to_le_bytes());
+    }
+
+    let files_offset = bytes.len();
+    let mut files = model.files.iter().collect::<Vec<(&PathBuf, &SourceFile)>>();
+    files.sort_by_key(|(path, _)| *path);
+    write_varint(&mut bytes, files.len() as u64);
+    for (path, file) in files {
+        write_str(&mut bytes, &path.to_string_lossy());
+        write_time(&mut bytes, file.last_modified);
+        let doc_ids = file
+            .docs
+            .iter()
+            .filter_map(|path| ids.get(path))
+            .collect::<Vec<&usize>>();
+        write_varint(&mut bytes, doc_ids.len() as u64);
+        for id in doc_ids {
+            write_varint(&mut bytes, *id as u64);
+        }
+    }
+
+    let mut failed_files = model
+        .failed_files
+        .iter()
+        .collect::<Vec<(&PathBuf, &SystemTime)>>();
+    failed_files.sort();
+    write_varint(&mut bytes, failed_files.len() as u64);
+    for (path, last_modified) in failed_files {
+        write_str(&mut bytes, &path.to_string_lossy());
+        write_time(&mut bytes, *last_modified);
+    }
+    write_str(
+        &mut bytes,
+        &serde_json::to_string(&model.rules).expect("the rules should be serializable"),
+    );
+    for paths in [&tombstones.docs, &tombstones.files] {
+        write_varint(&mut bytes, paths.len() as u64);
+        for path in paths {
+            write_str(&mut bytes, &path.to_string_lossy());
+        }
+    }
+
+    let table = [
+        paths.len(),
+        doc_table,
+        term_offsets.len(),
+        term_table,
+        files_offset,
+    ];
+    for (i, value) in table.iter().enumerate() {
+        let offset = tables_offset + i * 8;
+        bytes[offset..offset + 8].copy_from_slice(&(*value as u64).to_le_bytes());
+    }
+
+    bytes
+}
+
+/// Decode the whole model from the binary index file, e.g. for the server which keeps indexing
+pub fn decode(bytes: &[u8]) -> Result<(IndexHeader, InMemoryIndexModel), ()> {
+    decode_segment(bytes).map(|(header, model, _)| (header, model))
+}
+
+/// Decode a segment of the index file with its tombstones
+pub fn decode_segment(bytes: &[u8]) -> Result<(IndexHeader, InMemoryIndexModel, Tombstones), ()> {
+    let (header, tables) = read_header(bytes)?;
+    let reader = IndexReader { bytes, tables };
+    let (mut model, tombstones) =
+        decode_model(&reader).map_err(|()| eprintln!("ERROR: the index file is corrupt"))?;
+    model.roots = header.roots.clone();
+
+    Ok((header, model, tombstones))
+}
+
+fn decode_model(reader: &IndexReader) -> Result<(InMemoryIndexModel, Tombstones), ()> {
+    let (mut docs, passages): (Vec<(PathBuf, Doc)>, Vec<Passages>) = (0..reader.tables.doc_count)
+        .map(|id| {
+            reader
+                .doc(id)
+                .map(|(path, doc, passages)| ((path, doc), passages))
+        })
+        .collect::<Result<Vec<((PathBuf, Doc), Passages)>, ()>>()?
+        .into_iter()
+        .unzip();
+
+    let mut model = InMemoryIndexModel::new();
+    for term_id in 0..reader.tables.term_count {
+        let mut cursor = reader.term(term_id)?;
+        let term = cursor.string()?;
+        let postings = cursor.postings(reader.tables.doc_count)?;
+        // NOTE: the terms are added to the empty dictionary in order, so their ids increase and the
+        // terms of every document are sorted by the ids
+        let term_id = model.terms.add(&term, postings.len());
+        for (id, count) in postings {
+            docs.get_mut(id)
+                .ok_or(())?
+                .1
+                .tf
+                .push((term_id, count as u32));
+        }
+    }
+
+    // NOTE: the passages are interned after the terms of the documents, so the ids of the terms of
+    // the documents are still in order
+    for ((_, doc), passages) in docs.iter_mut().zip(passages) {
+        doc.passages = passages
+            .into_iter()
+            .map(|(anchor, tf)| {
+                (
+                    anchor,
+                    model.intern_passage(tf.iter().map(|(term, count)| (term.as_str(), *count))),
+                )
+            })
+            .collect();
+    }
+
+    let mut cursor = Cursor::at(reader.bytes, reader.tables.files_offset);
+    for _ in 0..cursor.varint()? {
+        let path = PathBuf::from(cursor.string()?);
+        let last_modified = cursor.time()?;
+        let doc_count = cursor.varint()?;
+        let mut doc_paths = Vec::new();
+        for _ in 0..doc_count {
+            doc_paths.push(docs.get(cursor.varint()? as usize).ok_or(())?.0.clone());
+        }
+        model.files.insert(
+            path,
+            SourceFile {
+                docs: doc_paths,
+                last_modified,
+            },
+        );
+    }
+    for _ in 0..cursor.varint()? {
+        let path = PathBuf::from(cursor.string()?);
+        model.failed_files.insert(path, cursor.time()?);
+    }
+    model.rules = serde_json::from_str(&cursor.string()?).map_err(|_| ())?;
+    model.docs = docs.into_iter().collect();
+
+    let tombstones = reader.tombstones()?;
+
+    Ok((model, tombstones))
+}
+
+/// A binary index file mapped into the memory, so the search reads only the postings of the
+/// query terms and the documents in the results rather than the whole file
+struct BinaryIndex {
+    mmap: Mmap,
+    header: IndexHeader,
+    tables: Tables,
+    tombstones: Tombstones,
+}
+
+impl BinaryIndex {
+    fn open(index_path: &Path) -> Result<Self, ()> {
+        let file = fs::File::open(index_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not open the index file {index_path}: {err}",
+                index_path = index_path.display()
+            )
+        })?;
+
+        // SAFETY: the index files are never modified in place, they are replaced by renaming the
+        // new files, so the mapped bytes won't change while they are read
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|err| {
+            eprintln!(
+                "ERROR: could not map the index file {index_path}: {err}",
+                index_path = index_path.display()
+            )
+        })?;
+        let (header, tables) = read_header(&mmap)?;
+        let tombstones = IndexReader {
+            bytes: &mmap,
+            tables,
+        }
+        .tombstones()
+        .map_err(|()| {
+            eprintln!(
+                "ERROR: the index file {index_path} is corrupt",
+                index_path = index_path.display()
+            )
+        })?;
+
+        Ok(BinaryIndex {
+            mmap,
+            header,
+            tables,
+            tombstones,
+        })
+    }
+
+    fn reader(&self) -> IndexReader<'_> {
+        IndexReader {
+            bytes: &self.mmap,
+            tables: self.tables,
+        }
+    }
+}
+
+/// The segments of the index file searched together. The documents are added to the newer
+/// segments, and the documents removed are hidden by the tombstones in the newer segments, so the
+/// ranks are the same as the ones of the model holding every segment.
+pub struct SegmentedIndex {
+    /// From the oldest segment to the newest one
+    segments: Vec<BinaryIndex>,
+    /// The ids of the documents hidden by the newer segments, for every segment
+    hidden: Vec<HashSet<usize>>,
+}
+
+impl SegmentedIndex {
+    pub fn open(segment_paths: &[PathBuf]) -> Result<Self, ()> {
+        let segments = segment_paths
+            .iter()
+            .map(|path| BinaryIndex::open(path))
+            .collect::<Result<Vec<BinaryIndex>, ()>>()?;
+
+        let mut hidden = vec![HashSet::new(); segments.len()];
+        for (i, newer) in segments.iter().enumerate() {
+            for path in &newer.tombstones.docs {
+                for (older, hidden) in segments[..i].iter().zip(hidden.iter_mut()) {
+                    if let Some(id) = older.reader().find_doc(path)? {
+                        hidden.insert(id);
+                    }
+                }
+            }
+        }
+
+        Ok(SegmentedIndex { segments, hidden })
+    }
+
+    /// The header of the oldest segment, it's the least compatible one
+    pub fn header(&self) -> Option<&IndexHeader> {
+        self.segments.first().map(|segment| &segment.header)
+    }
+
+    pub fn search(&self, query: &[char]) -> Result<Vec<(PathBuf, f32)>, ()> {
+        self.rank(query)
+            .map_err(|()| eprintln!("ERROR: the index file is corrupt"))
+    }
+
+    fn rank(&self, query: &[char]) -> Result<Vec<(PathBuf, f32)>, ()> {
+        let n = self.doc_count() as f32;
+        let mut ranks = self
+            .segments
+            .iter()
+            .map(|segment| vec![0_f32; segment.tables.doc_count])
+            .collect::<Vec<Vec<f32>>>();
+        for term in query_terms(query) {
+            let postings = self.postings(&term)?;
+            if postings.is_empty() {
+                continue;
+            }
+
+            let idf = (n / postings.len() as f32).log10();
+            for (segment, id, count) in postings {
+                let mut cursor = self.segments[segment].reader().doc_cursor(id)?;
+                cursor.string()?;
+                let total_tokens = cursor.varint()?.max(1) as f32;
+                *ranks[segment].get_mut(id).ok_or(())? += count as f32 / total_tokens * idf;
+            }
+        }
+
+        let mut result = Vec::with_capacity(self.doc_count());
+        for (i, ranks) in ranks.into_iter().enumerate() {
+            let reader = self.segments[i].reader();
+            for (id, rank) in ranks.into_iter().enumerate() {
+                if !self.hidden[i].contains(&id) {
+                    result.push((PathBuf::from(reader.doc_cursor(id)?.string()?), rank));
+                }
+            }
+        }
+        result.sort_by(|(_, rank1), (_, rank2)| rank2.partial_cmp(rank1).unwrap());
+
+        Ok(result)
+    }
+
+    /// The fields and the metadata of the document, sorted by their names
+    pub fn document_metadata(&self, path: &Path) -> Vec<(String, String)> {
+        let doc = match self.find_doc(path) {
+            Some((doc, _)) => doc,
+            None => return Vec::new(),
+        };
+
+        let mut metadata = doc
+            .fields
+            .into_iter()
+            .chain(doc.metadata)
+            .collect::<Vec<(String, String)>>();
+        metadata.sort();
+
+        metadata
+    }
+
+    /// The anchor of the passage in the document which matches the query best
+    pub fn best_passage(&self, path: &Path, query: &[char]) -> Option<String> {
+        let (_, passages) = self.find_doc(path)?;
+        let terms = query_terms(query);
+        let n = self.doc_count() as f32;
+        let mut weights = Vec::new();
+        for term in &terms {
+            let df = self.postings(term).ok()?.len().max(1);
+            weights.push(1_f32 + (n / df as f32).log10());
+        }
+
+        let mut best: Option<(String, f32)> = None;
+        for (anchor, tf) in passages {
+            let mut rank = 0_f32;
+            for (term, weight) in terms.iter().zip(&weights) {
+                rank += tf.get(term).cloned().unwrap_or(0) as f32 * weight;
+            }
+
+            if rank > 0_f32 && best.as_ref().is_none_or(|(_, best_rank)| rank > *best_rank) {
+                best = Some((anchor, rank));
+            }
+        }
+
+        best.map(|(anchor, _)| anchor)
+    }
+
+    /// The number of the documents which are not hidden
+    fn doc_count(&self) -> usize {
+        self.segments
+            .iter()
+            .zip(&self.hidden)
+            .map(|(segment, hidden)| segment.tables.doc_count - hidden.len())
+            .sum()
+    }
+
+    /// The postings of the term in every segment(the segment, the document id and the term
+    /// frequency), without the hidden documents
+    fn postings(&self, term: &str) -> Result<Vec<(usize, usize, usize)>, ()> {
+        let mut postings = Vec::new();
+        for (i, segment) in self.segments.iter().enumerate() {
+            for (id, count) in segment.reader().find_term(term)?.unwrap_or_default() {
+                if !self.hidden[i].contains(&id) {
+                    postings.push((i, id, count));
+                }
+            }
+        }
+
+        Ok(postings)
+    }
+
+    /// The document in the newest segment holding it, with its passages
+    fn find_doc(&self, path: &Path) -> Option<(Doc, Passages)> {
+        for (i, segment) in self.segments.iter().enumerate().rev() {
+            let reader = segment.reader();
+            match reader.find_doc(path).ok()? {
+                Some(id) if !self.hidden[i].contains(&id) => {
+                    return reader
+                        .doc(id)
+                        .ok()
+                        .map(|(_, doc, passages)| (doc, passages))
+                }
+                _ => continue,
+            }
+        }
+
+        None
+    }
+}
+
+/// The counts and the offsets of the tables in the binary index file
+#[derive(Clone, Copy)]
+struct Tables {
+    format_version: u32,
+    doc_count: usize,
+    doc_table: usize,
+    term_count: usize,
+    term_table: usize,
+    files_offset: usize,
+}
+
+fn read_header(bytes: &[u8]) -> Result<(IndexHeader, Tables), ()> {
+    if !is_binary(bytes) {
+        eprintln!("ERROR: the file is not a binary index file");
+        return Err(());
+    }
+
+    let mut cursor = Cursor::at(bytes, MAGIC.len());
+    let format_version = cursor
+        .u32()
+        .map_err(|()| eprintln!("ERROR: the index file is corrupt"))?;
+    let header = match format_version {
+        1 => IndexHeader::legacy(1, Vec::new()),
+        2..=FORMAT_VERSION => cursor
+            .u32()
+            .and_then(|len| cursor.take(len as usize))
+            .and_then(|header| serde_json::from_slice(header).map_err(|_| ()))
+            .map_err(|()| eprintln!("ERROR: the index file is corrupt"))?,
+        _ => {
+            eprintln!("ERROR: the format version {format_version} of the index file is not supported, the supported version is {FORMAT_VERSION}, it might be made by a newer version of LOSER");
+            return Err(());
+        }
+    };
+
+    let offsets = (0..5)
+        .map(|_| cursor.fixed())
+        .collect::<Result<Vec<usize>, ()>>()
+        .map_err(|()| eprintln!("ERROR: the index file is corrupt"))?;
+    let tables = Tables {
+        format_version,
+        doc_count: offsets[0],
+        doc_table: offsets[1],
+        term_count: offsets[2],
+        term_table: offsets[3],
+        files_offset: offsets[4],
+    };
+    let tables_fit = tables
+        .doc_count
+        .checked_mul(8)
+        .and_then(|x| x.checked_add(tables.doc_table))
+        .is_some_and(|x| x <= bytes.len())
+        && tables
+            .term_count
+            .checked_mul(8)
+            .and_then(|x| x.checked_add(tables.term_table))
+            .is_some_and(|x| x <= bytes.len());
+    if !tables_fit {
+        eprintln!("ERROR: the index file is corrupt");
+        return Err(());
+    }
+
+    Ok((header, tables))
+}
+
+/// Reader of the tables in the binary index file
+struct IndexReader<'a> {
+    bytes: &'a [u8],
+    tables: Tables,
+}
+
+impl<'a> IndexReader<'a> {
+    fn doc_cursor(&self, id: usize) -> Result<Cursor<'a>, ()> {
+        if id >= self.tables.doc_count {
+            return Err(());
+        }
+
+        let offset = Cursor::at(self.bytes, self.tables.doc_table + id * 8).fixed()?;
+        Ok(Cursor::at(self.bytes, offset))
+    }
+
+    /// The document with its passages, the terms of the passages are kept as they are since the
+    /// reader has no term dictionary
+    fn doc(&self, id: usize) -> Result<(PathBuf, Doc, Passages), ()> {
+        let mut cursor = self.doc_cursor(id)?;
+        let path = PathBuf::from(cursor.string()?);
+        let total_tokens = cursor.varint()? as usize;
+        let last_modified = cursor.time()?;
+        let fields = cursor.pairs()?.into_iter().collect();
+        let metadata = cursor.pairs()?.into_iter().collect();
+
+        let mut passages = Vec::new();
+        for _ in 0..cursor.varint()? {
+            let anchor = cursor.string()?;
+            let mut tf = TermFreq::new();
+            for _ in 0..cursor.varint()? {
+                let term = cursor.string()?;
+                tf.insert(term, cursor.varint()? as usize);
+            }
+            passages.push((anchor, tf));
+        }
+
+        let doc = Doc {
+            tf: Vec::new(),
+            total_tokens,
+            last_modified,
+            fields,
+            metadata,
+            passages: Vec::new(),
+        };
+
+        Ok((path, doc, passages))
+    }
+
+    /// The id of the document at the path, by the binary search over the sorted paths
+    fn find_doc(&self, path: &Path) -> Result<Option<usize>, ()> {
+        let (mut low, mut high) = (0, self.tables.doc_count);
+        while low < high {
+            let middle = (low + high) / 2;
+            let middle_path = PathBuf::from(self.doc_cursor(middle)?.string()?);
+            match middle_path.as_path().cmp(path) {
+                std::cmp::Ordering::Equal => return Ok(Some(middle)),
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn term(&self, term_id: usize) -> Result<Cursor<'a>, ()> {
+        let offset = Cursor::at(self.bytes, self.tables.term_table + term_id * 8).fixed()?;
+        Ok(Cursor::at(self.bytes, offset))
+    }
+
+    /// The postings of the term, by the binary search over the sorted terms
+    fn find_term(&self, term: &str) -> Result<Option<Vec<(usize, usize)>>, ()> {
+        let (mut low, mut high) = (0, self.tables.term_count);
+        while low < high {
+            let middle = (low + high) / 2;
+            let mut cursor = self.term(middle)?;
+            let middle_term = cursor.string()?;
+            match middle_term.as_str().cmp(term) {
+                std::cmp::Ordering::Equal => {
+                    return cursor.postings(self.tables.doc_count).map(Some)
+                }
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The tombstones after the rules, the files before the format version 3 have none
+    fn tombstones(&self) -> Result<Tombstones, ()> {
+        let mut tombstones = Tombstones::default();
+        if self.tables.format_version < 3 {
+            return Ok(tombstones);
+        }
+
+        let mut cursor = Cursor::at(self.bytes, self.tables.files_offset);
+        for _ in 0..cursor.varint()? {
+            cursor.string()?;
+            cursor.time()?;
+            for _ in 0..cursor.varint()? {
+                cursor.varint()?;
+            }
+        }
+        for _ in 0..cursor.varint()? {
+            cursor.string()?;
+            cursor.time()?;
+        }
+        cursor.string()?;
+
+        for paths in [&mut tombstones.docs, &mut tombstones.files] {
+            for _ in 0..cursor.varint()? {
+                paths.insert(PathBuf::from(cursor.string()?));
+            }
+        }
+
+        Ok(tombstones)
+    }
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn at(bytes: &'a [u8], position: usize) -> Self {
+        Cursor { bytes, position }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ()> {
+        let end = self.position.checked_add(len).ok_or(())?;
+        let bytes = self.bytes.get(self.position..end).ok_or(())?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, ()> {
+        let bytes = self.take(4)?.try_into().map_err(|_| ())?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn fixed(&mut self) -> Result<usize, ()> {
+        let bytes = self.take(8)?.try_into().map_err(|_| ())?;
+        usize::try_from(u64::from_le_bytes(bytes)).map_err(|_| ())
+    }
+
+    fn varint(&mut self) -> Result<u64, ()> {
+        let mut value = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(())
+    }
+
+    fn string(&mut self) -> Result<String, ()> {
+        let len = self.varint()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| ())
+    }
+
+    fn time(&mut self) -> Result<SystemTime, ()> {
+        let secs = self.varint()?;
+        // NOTE: the nanoseconds out of range would carry into the seconds, and the corrupt times
+        // could overflow, both of them panic rather than fail
+        let nanos = u32::try_from(self.varint()?)
+            .ok()
+            .filter(|nanos| *nanos < 1_000_000_000)
+            .ok_or(())?;
+        UNIX_EPOCH.checked_add(Duration::new(secs, nanos)).ok_or(())
+    }
+
+    fn pairs(&mut self) -> Result<Vec<(String, String)>, ()> {
+        let mut pairs = Vec::new();
+        for _ in 0..self.varint()? {
+            pairs.push((self.string()?, self.string()?));
+        }
+
+        Ok(pairs)
+    }
+
+    /// The postings of the term, the ids are checked against the number of the documents so the
+    /// corrupt deltas are rejected
+    fn postings(&mut self, doc_count: usize) -> Result<Vec<(usize, usize)>, ()> {
+        let mut postings = Vec::new();
+        let mut id: usize = 0;
+        for _ in 0..self.varint()? {
+            id = usize::try_from(self.varint()?)
+                .ok()
+                .and_then(|delta| id.checked_add(delta))
+                .filter(|id| *id < doc_count)
+                .ok_or(())?;
+            postings.push((id, self.varint()? as usize));
+        }
+
+        Ok(postings)
+    }
+}
+
+fn write_doc(bytes: &mut Vec<u8>, path: &Path, doc: &Doc, model: &InMemoryIndexModel) {
+    write_str(bytes, &path.to_string_lossy());
+    write_varint(bytes, doc.total_tokens as u64);
+    write_time(bytes, doc.last_modified);
+    for values in [&doc.fields, &doc.metadata] {
+        let mut values = values.iter().collect::<Vec<(&String, &String)>>();
+        values.sort();
+        write_varint(bytes, values.len() as u64);
+        for (name, value) in values {
+            write_str(bytes, name);
+            write_str(bytes, value);
+        }
+    }
+
+    write_varint(bytes, doc.passages.len() as u64);
+    for (anchor, tf) in &doc.passages {
+        write_str(bytes, anchor);
+        write_varint(bytes, tf.len() as u64);
+        for (term, count) in model.tf_terms(tf) {
+            write_str(bytes, term);
+            write_varint(bytes, count as u64);
+        }
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn write_str(bytes: &mut Vec<u8>, value: &str) {
+    write_varint(bytes, value.len() as u64);
+    bytes.extend(value.as_bytes());
+}
+
+fn write_time(bytes: &mut Vec<u8>, time: SystemTime) {
+    let duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    write_varint(bytes, duration.as_secs());
+    write_varint(bytes, u64::from(duration.subsec_nanos()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode_segment, write_varint, Cursor, SegmentedIndex, MAGIC};
+    use crate::model::analyzer::AnalyzedDocument;
+    use crate::model::in_memory_index_model::{InMemoryIndexModel, Model, Tombstones};
+    use crate::model::index_header::{IndexHeader, FORMAT_VERSION};
+    use crate::reader::reader_trait::{Document, Passage};
+    use std::{
+        env, fs,
+        path::PathBuf,
+        process,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    fn sample_model() -> Result<InMemoryIndexModel, ()> {
+        // NOTE: the largest nanoseconds, so the corrupt time is made by replacing them
+        let time = UNIX_EPOCH + Duration::new(1_700_000_000, 999_999_999);
+        let mut model = InMemoryIndexModel::new();
+        let inputs = [
+            ("test/andy.txt", "Andy is Andy, hello Andy."),
+            ("test/amy.txt", "Amy is Amy, hello Amy."),
+            ("test/both.txt", "Andy and Amy."),
+        ];
+        for (path, content) in inputs {
+            let mut document = Document::new(PathBuf::from(path), content.to_string());
+            document.fields = vec![("subject".to_string(), "greeting".to_string())];
+            document.metadata = vec![("author".to_string(), "Andy Lu".to_string())];
+            document.passages = vec![
+                Passage {
+                    anchor: "00:00:01.000".to_string(),
+                    text: "Andy is here".to_string(),
+                },
+                Passage {
+                    anchor: "00:00:02.000".to_string(),
+                    text: content.to_string(),
+                },
+            ];
+            model.add_documents(
+                PathBuf::from(path),
+                vec![AnalyzedDocument::new(document, time)],
+                time,
+            )?;
+        }
+        model.record_failure(PathBuf::from("test/broken.pdf"), time);
+        model.rules.max_depth = Some(3);
+        model.set_roots(vec![PathBuf::from("test")]);
+
+        Ok(model)
+    }
+
+    #[test]
+    fn encode_decode_ok() -> Result<(), ()> {
+        // arrange
+        let model = sample_model()?;
+
+        // act
+        let mut bytes = encode_segment(&model, &Tombstones::default());
+        let (header, actual) = decode(&bytes)?;
+        // NOTE: the nanoseconds are replaced by the ones out of range, encoded in as many bytes
+        let (mut nanos, mut corrupt_nanos) = (Vec::new(), Vec::new());
+        write_varint(&mut nanos, 999_999_999);
+        write_varint(&mut corrupt_nanos, 2_000_000_000);
+        let mut corrupt_time = bytes.clone();
+        let position = corrupt_time
+            .windows(nanos.len())
+            .position(|window| window == nanos)
+            .ok_or(())?;
+        corrupt_time[position..position + nanos.len()].copy_from_slice(&corrupt_nanos);
+        let corrupt_time = decode(&corrupt_time);
+        let overflow_time = Cursor::at(
+            &[
+                0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 0x00,
+            ],
+            0,
+        )
+        .time();
+        bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let newer = decode(&bytes);
+
+        // assert
+        assert_eq!(actual, model);
+        assert_eq!(header, IndexHeader::current(vec![PathBuf::from("test")]));
+        assert!(corrupt_time.is_err());
+        assert!(overflow_time.is_err());
+        assert!(newer.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn postings_ok() {
+        // arrange
+        let encode = |values: &[u64]| {
+            let mut bytes = Vec::new();
+            for value in values {
+                write_varint(&mut bytes, *value);
+            }
+            bytes
+        };
+        let valid = encode(&[2, 0, 3, 2, 1]);
+        let out_of_range = encode(&[2, 0, 3, 3, 1]);
+        let overflow = encode(&[2, 1, 3, u64::MAX, 1]);
+
+        // act
+        let valid = Cursor::at(&valid, 0).postings(3);
+        let out_of_range = Cursor::at(&out_of_range, 0).postings(3);
+        let overflow = Cursor::at(&overflow, 0).postings(3);
+
+        // assert
+        assert_eq!(valid, Ok(vec![(0, 3), (2, 1)]));
+        assert!(out_of_range.is_err());
+        assert!(overflow.is_err());
+    }
+
+    #[test]
+    fn segmented_index_search_ok() -> Result<(), ()> {
+        // arrange
+        let mut model = sample_model()?;
+        model.clear_pending();
+        let segment_paths = [1, 2].map(|i| {
+            env::temp_dir().join(format!(
+                "loser-segment-test-{pid}-{i}.loser",
+                pid = process::id()
+            ))
+        });
+        fs::write(
+            &segment_paths[0],
+            encode_segment(&model, &Tombstones::default()),
+        )
+        .map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // NOTE: the changes are saved as the newer segment, which hides the older copies
+        let time = SystemTime::now();
+        for (path, content) in [
+            ("test/amy.txt", "Amy is here, hello Andy."),
+            ("test/new.txt", "Amy, Amy and Amy."),
+        ] {
+            model.add_documents(
+                PathBuf::from(path),
+                vec![AnalyzedDocument::new(
+                    Document::new(PathBuf::from(path), content.to_string()),
+                    time,
+                )],
+                time,
+            )?;
+        }
+        model.remove_document(&PathBuf::from("test/both.txt"));
+        let (segment, tombstones) = model.pending_segment();
+        fs::write(&segment_paths[1], encode_segment(&segment, &tombstones))
+            .map_err(|err| eprintln!("ERROR: {err}"))?;
+        let queries = ["andy", "amy hello", "author:andy", "missing"];
+
+        // act
+        let index = SegmentedIndex::open(&segment_paths)?;
+        let mut actual = Vec::new();
+        let mut expected = Vec::new();
+        for query in queries {
+            let query = query.chars().collect::<Vec<char>>();
+            let mut ranks = index.search(&query)?;
+            ranks.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            actual.push(ranks);
+            let mut ranks = model.search(&query)?;
+            ranks.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            expected.push(ranks);
+        }
+        let query = "hello".chars().collect::<Vec<char>>();
+        let andy_path = PathBuf::from("test/andy.txt");
+        let metadata = index.document_metadata(&andy_path);
+        let passage = index.best_passage(&andy_path, &query);
+        let amy_metadata = index.document_metadata(&PathBuf::from("test/amy.txt"));
+        drop(index);
+        for path in &segment_paths {
+            fs::remove_file(path).map_err(|err| eprintln!("ERROR: {err}"))?;
+        }
+
+        // assert
+        assert_eq!(actual, expected);
+        assert_eq!(actual[0].len(), 3);
+        assert_eq!(metadata, model.document_metadata(&andy_path));
+        assert_eq!(passage, model.best_passage(&andy_path, &query));
+        assert_eq!(passage, Some("00:00:02.000".to_string()));
+        assert!(amy_metadata
+            .iter()
+            .all(|(name, _)| name != "author" && name != "subject"));
+
+        Ok(())
+    }
+}
@@ -1,69 +1,557 @@
-use super::super::lexer::Lexer;
-use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf, time::SystemTime};
+use super::super::config::IndexRules;
+use super::analyzer::{query_terms, AnalyzedDocument};
+use super::term_dictionary::{TermDictionary, TermId};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    mem,
+    ops::Bound,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 pub trait Model {
-    fn add_document(
+    /// Replace everything indexed from `file_path` with `documents`. A single file could hold
+    /// several documents(e.g. messages in a mbox archive), each of them is searchable on its own.
+    fn add_documents(
         &mut self,
         file_path: PathBuf,
-        content: &[char],
+        documents: Vec<AnalyzedDocument>,
         last_modified: SystemTime,
     ) -> Result<(), ()>;
 
-    fn remove_document(&mut self, file_path: &PathBuf);
+    /// Remove every document which was indexed from `file_path`
+    fn remove_document(&mut self, file_path: &Path);
+
+    /// Record that the file could not be read, so it won't be reindexed until it's modified
+    fn record_failure(&mut self, file_path: PathBuf, last_modified: SystemTime);
 
     fn search(&self, query: &[char]) -> Result<Vec<(PathBuf, f32)>, ()>;
 
-    fn requires_reindexing(&self, file_path: &PathBuf, last_modified: SystemTime) -> bool;
+    fn requires_reindexing(&self, file_path: &Path, last_modified: SystemTime) -> bool;
+
+    /// The fields and the metadata of the document, sorted by their names
+    fn document_metadata(&self, path: &Path) -> Vec<(String, String)>;
+
+    /// The anchor of the passage in the document(e.g. the timestamp of a subtitle cue) which matches
+    /// the query best, nothing for the documents without passages or without any matched passage
+    fn best_passage(&self, path: &Path, query: &[char]) -> Option<String>;
+
+    /// Whether the file on disk is indexed, the virtual paths of the documents(e.g.
+    /// "file.pdf#page=3") are not files.
+    fn contains_file(&self, file_path: &Path) -> bool;
+
+    /// The source files indexed into the model, including the ones failed to be read
+    fn indexed_files(&self) -> Vec<PathBuf>;
+
+    /// The source files indexed at the file or in the folder at `path`, including the ones failed
+    /// to be read
+    fn indexed_files_in(&self, path: &Path) -> Vec<PathBuf>;
+
+    /// The rules used to index the folder
+    fn rules(&self) -> IndexRules;
+
+    /// Replace the rules used to index the folder
+    fn set_rules(&mut self, rules: IndexRules);
+
+    /// Replace the folders indexed into the model, the paths of the documents start with them
+    fn set_roots(&mut self, roots: Vec<PathBuf>);
+
+    /// The count of the changes made to the model, it's increased by every change, so the model is
+    /// saved only when it's changed
+    fn generation(&self) -> u64;
 }
 
-type TermFreq = HashMap<String, usize>;
 type FileTF = HashMap<PathBuf, Doc>;
-// NOTE: DocFreq: Appearing times of different tokens in different docs
-type DocFreq = HashMap<String, usize>;
+// NOTE: the files are ordered by their paths, so the files in a folder are found by a range
+type SourceFiles = BTreeMap<PathBuf, SourceFile>;
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Doc {
-    tf: TermFreq,
-    total_tokens: usize,
-    last_modified: SystemTime,
+    /// The ids of the terms in the term dictionary of the model and their frequencies, sorted by
+    /// the ids
+    pub(super) tf: Vec<(TermId, u32)>,
+    pub(super) total_tokens: usize,
+    pub(super) last_modified: SystemTime,
+    pub(super) fields: HashMap<String, String>,
+    pub(super) metadata: HashMap<String, String>,
+    /// The anchors of the passages with the ids of their terms and their frequencies, sorted by the
+    /// ids
+    pub(super) passages: Vec<(String, Vec<(TermId, u32)>)>,
+}
+
+/// The file on disk which the documents are read from
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SourceFile {
+    pub docs: Vec<PathBuf>,
+    pub last_modified: SystemTime,
+}
+
+/// The documents and the source files removed from the model. They are saved with the changes, so
+/// they hide the older copies in the previous segments of the index file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Tombstones {
+    pub docs: BTreeSet<PathBuf>,
+    pub files: BTreeSet<PathBuf>,
 }
 
-#[derive(Default, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Default, Debug, Eq)]
 pub struct InMemoryIndexModel {
     pub docs: FileTF,
-    pub df: DocFreq,
+    /// The terms of the documents with their document frequencies
+    pub terms: TermDictionary,
+    pub files: SourceFiles,
+    /// The files which could not be read, and when they were modified
+    pub failed_files: BTreeMap<PathBuf, SystemTime>,
+    /// The rules used to index the folder
+    pub rules: IndexRules,
+    /// The folders indexed into the model, the paths of the documents start with them
+    pub roots: Vec<PathBuf>,
+    generation: u64,
+    /// The source files changed(added, failed or removed) and the documents removed since the
+    /// changes are saved
+    pending: Tombstones,
+}
+
+/// The bytes taken by the model in the memory, estimated from the sizes of its tables and their
+/// contents
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// The term dictionary
+    pub terms: usize,
+    /// The term ids and the term frequencies of the documents
+    pub postings: usize,
+    /// The paths, the fields, the metadata and the passages of the documents
+    pub docs: usize,
+    /// The source files and the failed files
+    pub files: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.terms + self.postings + self.docs + self.files
+    }
+}
+
+// NOTE: the generation only counts the changes, and the term ids depend on the order the documents
+// are added, the models with the same documents are equal
+impl PartialEq for InMemoryIndexModel {
+    fn eq(&self, other: &Self) -> bool {
+        self.docs.len() == other.docs.len()
+            && self.docs.iter().all(|(path, doc)| {
+                other
+                    .docs
+                    .get(path)
+                    .is_some_and(|other_doc| self.same_doc(doc, other, other_doc))
+            })
+            && self.terms == other.terms
+            && self.files == other.files
+            && self.failed_files == other.failed_files
+            && self.rules == other.rules
+            && self.roots == other.roots
+    }
 }
 
 impl InMemoryIndexModel {
     pub fn new() -> Self {
         InMemoryIndexModel {
             docs: HashMap::new(),
-            df: HashMap::new(),
+            terms: TermDictionary::new(),
+            files: BTreeMap::new(),
+            failed_files: BTreeMap::new(),
+            rules: IndexRules::default(),
+            roots: Vec::new(),
+            generation: 0,
+            pending: Tombstones::default(),
+        }
+    }
+
+    /// The changes since they are saved, as a segment holding the changed source files and their
+    /// documents, and the tombstones of the removed ones
+    pub fn pending_segment(&self) -> (InMemoryIndexModel, Tombstones) {
+        let mut segment = InMemoryIndexModel::new();
+        segment.rules = self.rules.clone();
+        segment.roots = self.roots.clone();
+        for path in &self.pending.files {
+            if let Some(file) = self.files.get(path) {
+                for doc_path in &file.docs {
+                    if let Some(doc) = self.docs.get(doc_path) {
+                        let mut copy = doc.clone();
+                        copy.passages = doc
+                            .passages
+                            .iter()
+                            .map(|(anchor, tf)| {
+                                (anchor.clone(), segment.intern_passage(self.tf_terms(tf)))
+                            })
+                            .collect();
+                        segment.insert_doc(doc_path.clone(), copy, self.doc_terms(doc));
+                    }
+                }
+                segment.files.insert(path.clone(), file.clone());
+            }
+            if let Some(failed_at) = self.failed_files.get(path) {
+                segment.failed_files.insert(path.clone(), *failed_at);
+            }
+        }
+
+        (segment, self.pending.clone())
+    }
+
+    /// Forget the changes once they are saved
+    pub fn clear_pending(&mut self) {
+        self.pending = Tombstones::default();
+    }
+
+    /// Apply a segment of the index file on the model, the tombstones remove the older copies
+    /// first
+    pub fn apply_segment(&mut self, mut segment: InMemoryIndexModel, tombstones: &Tombstones) {
+        for path in &tombstones.files {
+            self.remove_document(path);
+        }
+        for path in &tombstones.docs {
+            self.remove_doc(path);
+        }
+
+        for (path, mut doc) in mem::take(&mut segment.docs) {
+            let ids = mem::take(&mut doc.tf);
+            doc.passages = mem::take(&mut doc.passages)
+                .into_iter()
+                .map(|(anchor, tf)| (anchor, self.intern_passage(segment.tf_terms(&tf))))
+                .collect();
+            self.insert_doc(path, doc, segment.tf_terms(&ids));
+        }
+        self.files.extend(segment.files);
+        self.failed_files.extend(segment.failed_files);
+        self.rules = segment.rules;
+        self.roots = segment.roots;
+        self.generation += 1;
+    }
+
+    fn remove_doc(&mut self, path: &Path) {
+        if let Some(doc) = self.docs.remove(path) {
+            self.generation += 1;
+            self.pending.docs.insert(path.to_path_buf());
+            // NOTE: the terms left in no document are dropped, so the segments and the model
+            // loaded from them hold the same terms
+            for (id, _) in &doc.tf {
+                self.terms.release(*id);
+            }
+            for (id, _) in doc.passages.iter().flat_map(|(_, tf)| tf) {
+                self.terms.release_passage_term(*id);
+            }
+        }
+    }
+
+    fn insert_document(&mut self, document: AnalyzedDocument) {
+        let AnalyzedDocument {
+            path,
+            tf,
+            total_tokens,
+            last_modified,
+            fields,
+            metadata,
+            passages,
+        } = document;
+        let passages = passages
+            .into_iter()
+            .map(|(anchor, tf)| {
+                (
+                    anchor,
+                    self.intern_passage(tf.iter().map(|(term, count)| (term.as_str(), *count))),
+                )
+            })
+            .collect();
+        let doc = Doc {
+            tf: Vec::new(),
+            total_tokens,
+            last_modified,
+            fields,
+            metadata,
+            passages,
+        };
+        self.insert_doc(
+            path,
+            doc,
+            tf.iter().map(|(term, count)| (term.as_str(), *count)),
+        );
+    }
+
+    /// Insert the document with its terms interned into the term dictionary
+    fn insert_doc<'a, I>(&mut self, path: PathBuf, mut doc: Doc, tf: I)
+    where
+        I: Iterator<Item = (&'a str, usize)>,
+    {
+        let mut ids = tf
+            .map(|(term, count)| (self.terms.add(term, 1), count as u32))
+            .collect::<Vec<(TermId, u32)>>();
+        ids.sort_unstable();
+        doc.tf = ids;
+
+        self.docs.insert(path, doc);
+    }
+
+    /// Intern the terms of a passage into the term dictionary, they're not counted in the document
+    /// frequencies
+    pub(super) fn intern_passage<'a, I>(&mut self, tf: I) -> Vec<(TermId, u32)>
+    where
+        I: Iterator<Item = (&'a str, usize)>,
+    {
+        let mut ids = tf
+            .map(|(term, count)| (self.terms.add_passage_term(term), count as u32))
+            .collect::<Vec<(TermId, u32)>>();
+        ids.sort_unstable();
+
+        ids
+    }
+
+    /// Whether the documents are the same, their terms are compared by the terms rather than by
+    /// the ids
+    fn same_doc(&self, doc: &Doc, other: &InMemoryIndexModel, other_doc: &Doc) -> bool {
+        doc.total_tokens == other_doc.total_tokens
+            && doc.last_modified == other_doc.last_modified
+            && doc.fields == other_doc.fields
+            && doc.metadata == other_doc.metadata
+            && doc.passages.len() == other_doc.passages.len()
+            && doc.passages.iter().zip(&other_doc.passages).all(
+                |((anchor, tf), (other_anchor, other_tf))| {
+                    anchor == other_anchor
+                        && self.tf_terms(tf).collect::<HashMap<&str, usize>>()
+                            == other.tf_terms(other_tf).collect::<HashMap<&str, usize>>()
+                },
+            )
+            && self.doc_terms(doc).collect::<HashMap<&str, usize>>()
+                == other.doc_terms(other_doc).collect::<HashMap<&str, usize>>()
+    }
+
+    /// The terms of the document with their frequencies
+    pub(super) fn doc_terms<'a>(&'a self, doc: &'a Doc) -> impl Iterator<Item = (&'a str, usize)> {
+        self.tf_terms(&doc.tf)
+    }
+
+    /// The terms of the ids with their frequencies(e.g. of a passage)
+    pub(super) fn tf_terms<'a>(
+        &'a self,
+        tf: &'a [(TermId, u32)],
+    ) -> impl Iterator<Item = (&'a str, usize)> {
+        tf.iter()
+            .map(|(id, count)| (self.terms.term(*id), *count as usize))
+    }
+
+    /// The count of the term frequencies of every document, i.e. the postings of the terms
+    pub fn posting_count(&self) -> usize {
+        self.docs.values().map(|doc| doc.tf.len()).sum()
+    }
+
+    /// The bytes taken by the model, the allocator's overhead is not counted
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage {
+            terms: self.terms.memory_usage(),
+            ..MemoryUsage::default()
+        };
+
+        usage.docs += map_bytes(&self.docs);
+        for (path, doc) in &self.docs {
+            usage.postings += doc.tf.capacity() * mem::size_of::<(TermId, u32)>();
+            usage.docs += path.capacity()
+                + map_bytes(&doc.fields)
+                + map_bytes(&doc.metadata)
+                + doc.passages.capacity() * mem::size_of::<(String, Vec<(TermId, u32)>)>();
+            for (name, value) in doc.fields.iter().chain(&doc.metadata) {
+                usage.docs += name.capacity() + value.capacity();
+            }
+            for (anchor, tf) in &doc.passages {
+                usage.docs += anchor.capacity() + tf.capacity() * mem::size_of::<(TermId, u32)>();
+            }
+        }
+
+        usage.files += tree_bytes(&self.files) + tree_bytes(&self.failed_files);
+        for (path, file) in &self.files {
+            usage.files += path.capacity()
+                + file.docs.capacity() * mem::size_of::<PathBuf>()
+                + file.docs.iter().map(|path| path.capacity()).sum::<usize>();
+        }
+        for path in self.failed_files.keys() {
+            usage.files += path.capacity();
+        }
+
+        usage
+    }
+}
+
+/// The bytes taken by the table of the hash map, a control byte for every bucket
+fn map_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+    map.capacity() * (mem::size_of::<(K, V)>() + 1)
+}
+
+/// The bytes taken by the entries of the B-tree map, the unused slots of its nodes are not counted
+fn tree_bytes<K, V>(map: &BTreeMap<K, V>) -> usize {
+    map.len() * mem::size_of::<(K, V)>()
+}
+
+/// The model as it's saved into the JSON index file, the documents hold their terms rather than
+/// the ids of them. The document frequencies are saved for the older versions of LOSER, and they
+/// are counted again when it's loaded.
+#[derive(Deserialize, Serialize)]
+struct JsonModel<'a> {
+    docs: HashMap<Cow<'a, Path>, JsonDoc<'a>>,
+    #[serde(default)]
+    df: HashMap<Cow<'a, str>, usize>,
+    #[serde(default)]
+    files: Cow<'a, SourceFiles>,
+    #[serde(default)]
+    failed_files: Cow<'a, BTreeMap<PathBuf, SystemTime>>,
+    #[serde(default)]
+    rules: Cow<'a, IndexRules>,
+    #[serde(default)]
+    roots: Cow<'a, [PathBuf]>,
+}
+
+/// The terms with their frequencies, borrowed from the term dictionary when it's saved
+type JsonTermFreq<'a> = HashMap<Cow<'a, str>, usize>;
+
+#[derive(Deserialize, Serialize)]
+struct JsonDoc<'a> {
+    tf: JsonTermFreq<'a>,
+    total_tokens: usize,
+    last_modified: SystemTime,
+    #[serde(default)]
+    fields: Cow<'a, HashMap<String, String>>,
+    #[serde(default)]
+    metadata: Cow<'a, HashMap<String, String>>,
+    #[serde(default)]
+    passages: Vec<(Cow<'a, str>, JsonTermFreq<'a>)>,
+}
+
+impl Serialize for InMemoryIndexModel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let docs = self
+            .docs
+            .iter()
+            .map(|(path, doc)| {
+                let json_doc = JsonDoc {
+                    tf: self
+                        .doc_terms(doc)
+                        .map(|(term, count)| (Cow::Borrowed(term), count))
+                        .collect(),
+                    total_tokens: doc.total_tokens,
+                    last_modified: doc.last_modified,
+                    fields: Cow::Borrowed(&doc.fields),
+                    metadata: Cow::Borrowed(&doc.metadata),
+                    passages: doc
+                        .passages
+                        .iter()
+                        .map(|(anchor, tf)| {
+                            let tf = self
+                                .tf_terms(tf)
+                                .map(|(term, count)| (Cow::Borrowed(term), count))
+                                .collect();
+                            (Cow::Borrowed(anchor.as_str()), tf)
+                        })
+                        .collect(),
+                };
+                (Cow::Borrowed(path.as_path()), json_doc)
+            })
+            .collect();
+        let df = self
+            .terms
+            .iter()
+            .map(|(term, df)| (Cow::Borrowed(term), df))
+            .collect();
+
+        JsonModel {
+            docs,
+            df,
+            files: Cow::Borrowed(&self.files),
+            failed_files: Cow::Borrowed(&self.failed_files),
+            rules: Cow::Borrowed(&self.rules),
+            roots: Cow::Borrowed(&self.roots),
         }
+        .serialize(serializer)
     }
 }
 
-fn compute_tf(token: &str, doc: &Doc) -> f32 {
-    let m = doc.total_tokens as f32;
-    let n = doc.tf.get(token).cloned().unwrap_or(0) as f32;
+impl<'de> Deserialize<'de> for InMemoryIndexModel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = JsonModel::deserialize(deserializer)?;
+        let mut model = InMemoryIndexModel::new();
+        for (path, json_doc) in json.docs {
+            let passages = json_doc
+                .passages
+                .into_iter()
+                .map(|(anchor, tf)| {
+                    let tf = tf.iter().map(|(term, count)| (&**term, *count));
+                    (anchor.into_owned(), model.intern_passage(tf))
+                })
+                .collect();
+            let doc = Doc {
+                tf: Vec::new(),
+                total_tokens: json_doc.total_tokens,
+                last_modified: json_doc.last_modified,
+                fields: json_doc.fields.into_owned(),
+                metadata: json_doc.metadata.into_owned(),
+                passages,
+            };
+            let tf = json_doc.tf.iter().map(|(term, count)| (&**term, *count));
+            model.insert_doc(path.into_owned(), doc, tf);
+        }
+        model.files = json.files.into_owned();
+        // NOTE: the index files made before the files were recorded hold a document for every
+        // file, so the file is known to be removed or modified when the folder is reindexed
+        let listed = model
+            .files
+            .values()
+            .flat_map(|file| file.docs.iter())
+            .cloned()
+            .collect::<BTreeSet<PathBuf>>();
+        for (path, doc) in &model.docs {
+            if !listed.contains(path) {
+                model.files.insert(
+                    path.clone(),
+                    SourceFile {
+                        docs: vec![path.clone()],
+                        last_modified: doc.last_modified,
+                    },
+                );
+            }
+        }
+        model.failed_files = json.failed_files.into_owned();
+        model.rules = json.rules.into_owned();
+        model.roots = json.roots.into_owned();
+
+        Ok(model)
+    }
+}
+
+fn compute_tf(id: Option<TermId>, doc: &Doc) -> f32 {
+    // NOTE: a document with only the metadata(e.g. an empty file) has no tokens
+    let m = doc.total_tokens.max(1) as f32;
+    let n = id
+        .and_then(|id| doc.tf.binary_search_by_key(&id, |(id, _)| *id).ok())
+        .map(|i| doc.tf[i].1)
+        .unwrap_or(0) as f32;
     n / m
 }
 
 fn compute_idf(token: &str, model: &InMemoryIndexModel) -> f32 {
     let n = model.docs.len() as f32;
-    let m = model.df.get(token).cloned().unwrap_or(1) as f32;
+    let m = model.terms.df(token).unwrap_or(1) as f32;
     (n / m).log10()
 }
 
 impl Model for InMemoryIndexModel {
     fn search(&self, query: &[char]) -> Result<Vec<(PathBuf, f32)>, ()> {
         let mut result: Vec<(PathBuf, f32)> = Vec::new();
-        let tokens = Lexer::new(query).collect::<Vec<String>>();
+        // NOTE: the terms of the query are looked up in the term dictionary once, rather than for
+        // every document
+        let terms = query_terms(query)
+            .iter()
+            .map(|token| (self.terms.id(token), compute_idf(token, self)))
+            .collect::<Vec<(Option<TermId>, f32)>>();
         for (path, doc) in &self.docs {
             let mut rank = 0_f32;
-            for token in &tokens {
-                rank += compute_tf(token.as_str(), doc) * compute_idf(token.as_str(), &self);
+            for (id, idf) in &terms {
+                rank += compute_tf(*id, doc) * idf;
             }
             result.push((path.to_path_buf(), rank));
         }
@@ -71,66 +559,179 @@ impl Model for InMemoryIndexModel {
         Ok(result)
     }
 
-    fn add_document(
+    fn add_documents(
         &mut self,
         file_path: PathBuf,
-        content: &[char],
+        documents: Vec<AnalyzedDocument>,
         last_modified: SystemTime,
     ) -> Result<(), ()> {
         self.remove_document(&file_path);
 
-        let mut tf = TermFreq::new();
-        let mut count = 0;
+        let mut paths = Vec::new();
+        for document in documents {
+            paths.push(document.path.clone());
+            // NOTE: the document replaced is removed first, so it's hidden in the older segments
+            self.remove_doc(&document.path);
+            self.insert_document(document);
+        }
+        self.pending.files.insert(file_path.clone());
+        self.files.insert(
+            file_path,
+            SourceFile {
+                docs: paths,
+                last_modified,
+            },
+        );
+        self.generation += 1;
+
+        Ok(())
+    }
+
+    fn record_failure(&mut self, file_path: PathBuf, last_modified: SystemTime) {
+        self.remove_document(&file_path);
+        self.pending.files.insert(file_path.clone());
+        self.failed_files.insert(file_path, last_modified);
+        self.generation += 1;
+    }
 
-        for token in Lexer::new(content) {
-            tf.entry(token).and_modify(|v| *v += 1).or_insert(1);
-            count += 1;
+    #[allow(clippy::needless_return)]
+    fn requires_reindexing(&self, file_path: &Path, last_modified: SystemTime) -> bool {
+        if let Some(failed_at) = self.failed_files.get(file_path) {
+            return *failed_at < last_modified;
         }
 
-        for term in tf.keys() {
-            self.df
-                .entry(term.to_string())
-                .and_modify(|v| *v += 1)
-                .or_insert(1);
+        if let Some(file) = self.files.get(file_path) {
+            return file.last_modified < last_modified;
         }
 
-        let doc = Doc {
-            tf,
-            total_tokens: count,
-            last_modified,
+        if let Some(doc) = self.docs.get(file_path) {
+            return doc.last_modified < last_modified;
+        }
+
+        return true;
+    }
+
+    fn document_metadata(&self, path: &Path) -> Vec<(String, String)> {
+        let mut metadata = match self.docs.get(path) {
+            Some(doc) => doc
+                .fields
+                .iter()
+                .chain(doc.metadata.iter())
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect::<Vec<(String, String)>>(),
+            None => Vec::new(),
         };
-        self.docs.insert(file_path, doc);
+        metadata.sort();
 
-        Ok(())
+        metadata
     }
 
-    fn requires_reindexing(&self, file_path: &PathBuf, last_modified: SystemTime) -> bool {
-        if let Some(doc) = self.docs.get(file_path) {
-            return doc.last_modified < last_modified;
+    fn best_passage(&self, path: &Path, query: &[char]) -> Option<String> {
+        let doc = self.docs.get(path)?;
+        // NOTE: 1 + idf is the weight of a term, so the terms in every document still count
+        let terms = query_terms(query)
+            .iter()
+            .map(|term| (self.terms.id(term), 1_f32 + compute_idf(term, self)))
+            .collect::<Vec<(Option<TermId>, f32)>>();
+
+        let mut best: Option<(&String, f32)> = None;
+        for (anchor, tf) in &doc.passages {
+            let mut rank = 0_f32;
+            for (id, weight) in &terms {
+                let n = id
+                    .and_then(|id| tf.binary_search_by_key(&id, |(id, _)| *id).ok())
+                    .map(|i| tf[i].1)
+                    .unwrap_or(0) as f32;
+                rank += n * weight;
+            }
+
+            if rank > 0_f32 && best.is_none_or(|(_, best_rank)| rank > best_rank) {
+                best = Some((anchor, rank));
+            }
         }
 
-        return true;
+        best.map(|(anchor, _)| anchor.clone())
     }
 
-    fn remove_document(&mut self, file_path: &PathBuf) {
-        if let Some(doc) = self.docs.remove(file_path) {
-            for t in doc.tf.keys() {
-                if let Some(f) = self.df.get_mut(t) {
-                    *f -= 1;
-                }
+    fn contains_file(&self, file_path: &Path) -> bool {
+        self.files.contains_key(file_path)
+    }
+
+    fn indexed_files(&self) -> Vec<PathBuf> {
+        self.files
+            .keys()
+            .chain(self.failed_files.keys())
+            .cloned()
+            .collect()
+    }
+
+    fn indexed_files_in(&self, path: &Path) -> Vec<PathBuf> {
+        // NOTE: the paths are ordered by their components, so the files in the folder follow it
+        let in_path = |file: &&PathBuf| file.starts_with(path);
+        self.files
+            .range::<Path, _>((Bound::Included(path), Bound::Unbounded))
+            .map(|(file, _)| file)
+            .take_while(in_path)
+            .chain(
+                self.failed_files
+                    .range::<Path, _>((Bound::Included(path), Bound::Unbounded))
+                    .map(|(file, _)| file)
+                    .take_while(in_path),
+            )
+            .cloned()
+            .collect()
+    }
+
+    fn rules(&self) -> IndexRules {
+        self.rules.clone()
+    }
+
+    fn set_rules(&mut self, rules: IndexRules) {
+        self.rules = rules;
+        self.generation += 1;
+    }
+
+    fn set_roots(&mut self, roots: Vec<PathBuf>) {
+        if self.roots != roots {
+            self.roots = roots;
+            self.generation += 1;
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn remove_document(&mut self, file_path: &Path) {
+        if self.failed_files.remove(file_path).is_some() {
+            self.generation += 1;
+            self.pending.files.insert(file_path.to_path_buf());
+        }
+        let paths = match self.files.remove(file_path) {
+            Some(file) => {
+                self.generation += 1;
+                self.pending.files.insert(file_path.to_path_buf());
+                file.docs
             }
+            None => vec![file_path.to_path_buf()],
+        };
+
+        for path in paths {
+            self.remove_doc(&path);
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::in_memory_index_model::Doc;
+    use super::super::in_memory_index_model::{Doc, SourceFile};
     use super::{InMemoryIndexModel, Model};
+    use crate::model::analyzer::AnalyzedDocument;
+    use crate::reader::reader_trait::{Document, Passage};
     use std::{
         collections::HashMap,
         ops::Add,
-        path::PathBuf,
+        path::{Path, PathBuf},
         str::FromStr,
         time::{Duration, SystemTime},
     };
@@ -146,26 +747,41 @@ mod tests {
 
         let mut expected = InMemoryIndexModel::new();
         let expected_doc = Doc {
-            tf: HashMap::from([
-                ("ANDY".to_string(), 2),
-                ("IS".to_string(), 1),
-                (".".to_string(), 1),
-            ]),
+            tf: Vec::new(),
             total_tokens: 4,
             last_modified: time,
+            fields: HashMap::new(),
+            metadata: HashMap::new(),
+            passages: Vec::new(),
         };
-        expected.docs.insert(path.clone(), expected_doc);
-        expected.df = HashMap::from([
-            ("ANDY".to_string(), 1),
-            ("IS".to_string(), 1),
-            (".".to_string(), 1),
-        ]);
+        expected.insert_doc(
+            path.clone(),
+            expected_doc,
+            [("ANDY", 2), ("IS", 1), (".", 1)].into_iter(),
+        );
+        expected.files.insert(
+            path.clone(),
+            SourceFile {
+                docs: vec![path.clone()],
+                last_modified: time,
+            },
+        );
 
         // act
-        model.add_document(path.clone(), &content.chars().collect::<Vec<char>>(), time)?;
+        model.add_documents(
+            path.clone(),
+            vec![AnalyzedDocument::new(
+                Document::new(path.clone(), content),
+                time,
+            )],
+            time,
+        )?;
 
         // assert
         assert_eq!(model, expected);
+        assert_eq!(model.terms.df("ANDY"), Some(1));
+        assert_eq!(model.terms.df("IS"), Some(1));
+        assert_eq!(model.terms.df("."), Some(1));
 
         Ok(())
     }
@@ -177,17 +793,23 @@ mod tests {
         let path1: PathBuf = PathBuf::from_str("test/test.txt")
             .map_err(|err| eprintln!("ERROR: the path is not valid in test: {err}"))?;
         let content1 = String::from("Andy is Andy.");
-        model.add_document(
+        model.add_documents(
             path1.clone(),
-            &content1.chars().collect::<Vec<char>>(),
+            vec![AnalyzedDocument::new(
+                Document::new(path1.clone(), content1),
+                SystemTime::now(),
+            )],
             SystemTime::now(),
         )?;
         let path2: PathBuf = PathBuf::from_str("test/test2.txt")
             .map_err(|err| eprintln!("ERROR: the path is not valid in test: {err}"))?;
         let content2 = String::from("Amy is Amy.");
-        model.add_document(
+        model.add_documents(
             path2.clone(),
-            &content2.chars().collect::<Vec<char>>(),
+            vec![AnalyzedDocument::new(
+                Document::new(path2.clone(), content2),
+                SystemTime::now(),
+            )],
             SystemTime::now(),
         )?;
 
@@ -204,6 +826,89 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn search_metadata_ok() -> Result<(), ()> {
+        // arrange
+        let mut model = InMemoryIndexModel::new();
+        let path1 = PathBuf::from("test/notes.pdf");
+        let mut document1 = Document::new(path1.clone(), "Notes about Rust.".to_string());
+        document1.metadata = vec![("Author".to_string(), "Andy".to_string())];
+        model.add_documents(
+            path1.clone(),
+            vec![AnalyzedDocument::new(document1, SystemTime::now())],
+            SystemTime::now(),
+        )?;
+        let path2 = PathBuf::from("test/andy.txt");
+        model.add_documents(
+            path2.clone(),
+            vec![AnalyzedDocument::new(
+                Document::new(path2.clone(), "Andy is Andy.".to_string()),
+                SystemTime::now(),
+            )],
+            SystemTime::now(),
+        )?;
+
+        // act
+        let by_author = model.search(&"author:andy".chars().collect::<Vec<char>>())?;
+        let by_term = model.search(&"andy".chars().collect::<Vec<char>>())?;
+        let metadata = model.document_metadata(&path1);
+
+        // assert
+        // NOTE: the author is not counted in the length of the document("Notes", "about", "Rust"
+        // and ".")
+        assert_eq!(model.docs[&path1].total_tokens, 4);
+        assert_eq!(by_author[0].0, path1);
+        assert!(by_author[0].1 > 0_f32);
+        assert_eq!(by_term[0].0, path2);
+        assert_eq!(by_term[1].1, 0_f32);
+        assert_eq!(metadata, vec![("author".to_string(), "Andy".to_string())]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn best_passage_ok() -> Result<(), ()> {
+        // arrange
+        let mut model = InMemoryIndexModel::new();
+        let path = PathBuf::from("test/meeting.srt");
+        let cues = [
+            ("00:00:01.000", "Hello, Andy."),
+            ("00:00:04.000", "Amy is here, and Andy is here."),
+            ("00:00:09.000", "Bye."),
+        ];
+        let mut document = Document::new(path.clone(), String::new());
+        for (anchor, text) in cues {
+            document.content.push_str(text);
+            document.passages.push(Passage {
+                anchor: anchor.to_string(),
+                text: text.to_string(),
+            });
+        }
+        model.add_documents(
+            path.clone(),
+            vec![AnalyzedDocument::new(document, SystemTime::now())],
+            SystemTime::now(),
+        )?;
+
+        // act
+        let hello = model.best_passage(&path, &"hello".chars().collect::<Vec<char>>());
+        let amy_or_andy = model.best_passage(&path, &"amy andy".chars().collect::<Vec<char>>());
+        let nobody = model.best_passage(&path, &"nobody".chars().collect::<Vec<char>>());
+        let andy_df = model.terms.df("ANDY");
+        model.remove_document(&path);
+
+        // assert
+        assert_eq!(hello.as_deref(), Some("00:00:01.000"));
+        assert_eq!(amy_or_andy.as_deref(), Some("00:00:04.000"));
+        assert_eq!(nobody, None);
+        // NOTE: the terms of the passages are interned without counting them in the document
+        // frequencies, and they're released with the document
+        assert_eq!(andy_df, Some(1));
+        assert_eq!(model.terms.len(), 0);
+
+        Ok(())
+    }
+
     #[test]
     fn requires_reindexing_ok() -> Result<(), ()> {
         // arrange
@@ -213,9 +918,12 @@ mod tests {
         let content = String::from("Andy is Andy.");
         let time = SystemTime::now();
 
-        model.add_document(
+        model.add_documents(
             file_path.clone(),
-            &content.chars().collect::<Vec<char>>(),
+            vec![AnalyzedDocument::new(
+                Document::new(file_path.clone(), content),
+                time,
+            )],
             time,
         )?;
 
@@ -229,6 +937,85 @@ mod tests {
     }
 
     #[test]
+    fn record_failure_ok() -> Result<(), ()> {
+        // arrange
+        let mut model = InMemoryIndexModel::new();
+        let file_path = PathBuf::from("test/broken.pdf");
+        let time = SystemTime::now();
+        model.add_documents(
+            file_path.clone(),
+            vec![AnalyzedDocument::new(
+                Document::new(file_path.clone(), "Andy is Andy.".to_string()),
+                time,
+            )],
+            time,
+        )?;
+        let modified = time.add(Duration::from_secs(10));
+
+        // act
+        model.record_failure(file_path.clone(), modified);
+        let unchanged = model.requires_reindexing(&file_path, modified);
+        let changed = model.requires_reindexing(&file_path, modified.add(Duration::from_secs(10)));
+        let indexed_files = model.indexed_files();
+        model.remove_document(&file_path);
+
+        // assert
+        assert!(!unchanged);
+        assert!(changed);
+        assert_eq!(indexed_files, vec![file_path.clone()]);
+        assert!(model.docs.is_empty());
+        assert!(model.failed_files.is_empty());
+        assert!(model.requires_reindexing(&file_path, modified));
+
+        Ok(())
+    }
+
+    #[test]
+    fn indexed_files_in_ok() -> Result<(), ()> {
+        // arrange
+        let mut model = InMemoryIndexModel::new();
+        let time = SystemTime::now();
+        for path in [
+            "a/b.txt",
+            "a/b/c.txt",
+            "a/b/d/e.txt",
+            "a/b0/f.txt",
+            "a/bc.txt",
+        ] {
+            let path = PathBuf::from(path);
+            model.add_documents(
+                path.clone(),
+                vec![AnalyzedDocument::new(
+                    Document::new(path, "Andy".to_string()),
+                    time,
+                )],
+                time,
+            )?;
+        }
+        model.record_failure(PathBuf::from("a/b/broken.pdf"), time);
+
+        // act
+        let in_folder = model.indexed_files_in(Path::new("a/b"));
+        let file = model.indexed_files_in(Path::new("a/b.txt"));
+        let nothing = model.indexed_files_in(Path::new("a/b/c"));
+
+        // assert
+        assert_eq!(
+            in_folder,
+            vec![
+                PathBuf::from("a/b/c.txt"),
+                PathBuf::from("a/b/d/e.txt"),
+                PathBuf::from("a/b/broken.pdf"),
+            ]
+        );
+        assert_eq!(file, vec![PathBuf::from("a/b.txt")]);
+        assert!(nothing.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::bind_instead_of_map)]
     fn remove_document_ok() -> Result<(), ()> {
         // arrange
         let mut model = InMemoryIndexModel::new();
@@ -240,26 +1027,32 @@ mod tests {
         let content2 = String::from("Amy is Amy.");
         let time = SystemTime::now();
 
-        model.add_document(
+        model.add_documents(
             file_path1.clone(),
-            &content1.chars().collect::<Vec<char>>(),
+            vec![AnalyzedDocument::new(
+                Document::new(file_path1.clone(), content1),
+                time,
+            )],
             time,
         )?;
 
-        model.add_document(
+        model.add_documents(
             file_path2.clone(),
-            &content2.chars().collect::<Vec<char>>(),
+            vec![AnalyzedDocument::new(
+                Document::new(file_path2.clone(), content2),
+                time,
+            )],
             time,
         )?;
 
         // act && assert
         assert_eq!(model.docs.keys().count(), 2);
         assert_eq!(
-            model.df.get("ANDY").and_then(|count| Some(*count as i32)),
+            model.terms.df("ANDY").and_then(|count| Some(count as i32)),
             Some(1)
         );
         assert_eq!(
-            model.df.get("IS").and_then(|count| Some(*count as i32)),
+            model.terms.df("IS").and_then(|count| Some(count as i32)),
             Some(2)
         );
 
@@ -267,14 +1060,104 @@ mod tests {
 
         assert_eq!(model.docs.keys().count(), 1);
         assert_eq!(
-            model.df.get("ANDY").and_then(|count| Some(*count as i32)),
-            Some(0)
+            model.terms.df("ANDY").and_then(|count| Some(count as i32)),
+            None
         );
         assert_eq!(
-            model.df.get("IS").and_then(|count| Some(*count as i32)),
+            model.terms.df("IS").and_then(|count| Some(count as i32)),
             Some(1)
         );
 
         Ok(())
     }
+
+    #[test]
+    fn load_legacy_json_ok() -> Result<(), ()> {
+        // arrange
+        let json = r#"{
+            "docs": {
+                "test/test.txt": {
+                    "tf": {"ANDY": 2, "IS": 1},
+                    "total_tokens": 3,
+                    "last_modified": {"secs_since_epoch": 1, "nanos_since_epoch": 0}
+                }
+            },
+            "df": {"ANDY": 1, "IS": 1}
+        }"#;
+
+        // act
+        let model: InMemoryIndexModel =
+            serde_json::from_str(json).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert!(model.contains_file(&PathBuf::from("test/test.txt")));
+        assert_eq!(model.indexed_files(), vec![PathBuf::from("test/test.txt")]);
+        assert_eq!(model.terms.df("ANDY"), Some(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_usage_ok() -> Result<(), ()> {
+        // arrange
+        let mut model = InMemoryIndexModel::new();
+        let time = SystemTime::now();
+        let empty = model.memory_usage();
+        for i in 0..10 {
+            let file_path = PathBuf::from(format!("test/test{i}.txt"));
+            model.add_documents(
+                file_path.clone(),
+                vec![AnalyzedDocument::new(
+                    Document::new(file_path, "Andy is Andy.".to_string()),
+                    time,
+                )],
+                time,
+            )?;
+        }
+
+        // act
+        let usage = model.memory_usage();
+
+        // assert
+        assert_eq!(empty.total(), 0);
+        assert_eq!(model.terms.len(), 3);
+        assert_eq!(model.posting_count(), 30);
+        assert!(usage.terms > 0);
+        assert!(usage.postings >= 30 * 8);
+        assert!(usage.docs > 0);
+        assert!(usage.files > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn generation_ok() -> Result<(), ()> {
+        // arrange
+        let mut model = InMemoryIndexModel::new();
+        let file_path = PathBuf::from("test/test1.txt");
+        let time = SystemTime::now();
+        let mut actual = vec![model.generation()];
+
+        // act
+        model.add_documents(
+            file_path.clone(),
+            vec![AnalyzedDocument::new(
+                Document::new(file_path.clone(), "Andy is Andy.".to_string()),
+                time,
+            )],
+            time,
+        )?;
+        actual.push(model.generation());
+        model.remove_document(&PathBuf::from("test/missing.txt"));
+        actual.push(model.generation());
+        model.remove_document(&file_path);
+        actual.push(model.generation());
+
+        // assert
+        assert!(actual[0] < actual[1]);
+        assert_eq!(actual[1], actual[2]);
+        assert!(actual[2] < actual[3]);
+
+        Ok(())
+    }
 }
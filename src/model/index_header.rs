@@ -0,0 +1,124 @@
+use super::super::lexer::AnalyzerConfig;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The version of the binary index file, increased whenever its layout changes
+pub const FORMAT_VERSION: u32 = 3;
+
+/// The format version of the JSON index files, they were saved before the binary format
+pub const JSON_FORMAT_VERSION: u32 = 0;
+
+/// What the index file is made by, stored at the start of the index file
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct IndexHeader {
+    pub format_version: u32,
+    /// The version of LOSER saving the index file, nothing when it's unknown(e.g. the JSON index
+    /// files)
+    pub loser_version: Option<String>,
+    pub analyzer: AnalyzerConfig,
+    /// The indexed folders, empty when they are unknown
+    pub roots: Vec<PathBuf>,
+}
+
+/// Whether the index file could be used by this version of LOSER
+#[derive(Debug, PartialEq)]
+pub enum Compatibility {
+    Current,
+    /// Made by the older version, it's usable and should be saved again in the current format
+    Outdated,
+    /// Its terms or its documents don't match, so the folder should be indexed from scratch
+    Incompatible(String),
+}
+
+impl IndexHeader {
+    /// The header of the index file saved by this version of LOSER
+    pub fn current(roots: Vec<PathBuf>) -> Self {
+        IndexHeader {
+            format_version: FORMAT_VERSION,
+            loser_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            analyzer: AnalyzerConfig::current(),
+            roots,
+        }
+    }
+
+    /// The header of the index files saved before the header is added(e.g. the JSON index files),
+    /// they are made by the first lexer without the field terms
+    pub fn legacy(format_version: u32, roots: Vec<PathBuf>) -> Self {
+        IndexHeader {
+            format_version,
+            loser_version: None,
+            analyzer: AnalyzerConfig {
+                lexer_version: 0,
+                uppercase: true,
+                field_terms: false,
+            },
+            roots,
+        }
+    }
+
+    /// Check the index file against this version of LOSER and the folders to be indexed, the
+    /// folders aren't checked when `roots` is empty(e.g. the search)
+    pub fn compatibility(&self, roots: &[PathBuf]) -> Compatibility {
+        if self.analyzer != AnalyzerConfig::current() {
+            return Compatibility::Incompatible(format!(
+                "the index file is made by the different analyzer {analyzer:?}",
+                analyzer = self.analyzer
+            ));
+        }
+
+        // NOTE: the paths of the documents start with the indexed folders, they don't match
+        // the files in the other folders
+        if !roots.is_empty() && !self.roots.is_empty() && self.roots != roots {
+            return Compatibility::Incompatible(format!(
+                "the index file is made for the folders {roots:?}",
+                roots = self.roots
+            ));
+        }
+
+        let current = IndexHeader::current(self.roots.clone());
+        if self.format_version < FORMAT_VERSION || self.loser_version != current.loser_version {
+            return Compatibility::Outdated;
+        }
+
+        Compatibility::Current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compatibility, IndexHeader, JSON_FORMAT_VERSION};
+    use crate::lexer::AnalyzerConfig;
+    use std::path::PathBuf;
+
+    #[test]
+    fn compatibility_ok() {
+        // arrange
+        let roots = vec![PathBuf::from("./data")];
+        let other_roots = vec![PathBuf::from("./other")];
+        let current = IndexHeader::current(roots.clone());
+        let legacy = IndexHeader::legacy(JSON_FORMAT_VERSION, Vec::new());
+        let other_analyzer = IndexHeader {
+            analyzer: AnalyzerConfig {
+                lexer_version: 0,
+                ..AnalyzerConfig::current()
+            },
+            ..IndexHeader::current(roots.clone())
+        };
+
+        // act
+        let actual = [
+            current.compatibility(&roots),
+            current.compatibility(&[]),
+            current.compatibility(&other_roots),
+            legacy.compatibility(&roots),
+            other_analyzer.compatibility(&[]),
+        ];
+
+        // assert
+        assert_eq!(actual[0], Compatibility::Current);
+        assert_eq!(actual[1], Compatibility::Current);
+        assert!(matches!(actual[2], Compatibility::Incompatible(_)));
+        assert!(matches!(actual[3], Compatibility::Incompatible(_)));
+        assert!(matches!(actual[4], Compatibility::Incompatible(_)));
+    }
+}
@@ -0,0 +1,44 @@
+use super::in_memory_index_model::Model;
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+/// The model saved into an index, so it's kept across the runs. Every backend implements it, and
+/// the subcommands index and serve the folders through it without knowing the backend.
+pub trait PersistentModel: Model + Send + Sync + Sized + 'static {
+    /// Where the changes of the model are saved by the server
+    type Store: ModelStore<Self>;
+
+    /// The extension of the index, appended to the folder(e.g. "data.loser")
+    const EXTENSION: &'static str;
+
+    /// An empty model to index the folder from scratch, it's saved to `index_path` by `save_index`
+    /// once the folder is indexed
+    fn create_index(index_path: &Path) -> Result<Self, ()>;
+
+    /// Save the whole model to `index_path`, the previous index is replaced, or kept as the backup
+    /// when `backup` is set
+    fn save_index(self, index_path: &Path, backup: bool) -> Result<(), ()>;
+
+    /// Load the model from `index_path` with the store saving its changes. The index made by an
+    /// older version of LOSER is migrated, and the model is empty when the index is missing,
+    /// corrupt or incompatible with `roots`(e.g. made by a different analyzer), so the folder is
+    /// indexed from scratch. The index made by a newer version of LOSER is never overwritten.
+    fn open_index(
+        index_path: &Path,
+        roots: &[PathBuf],
+        backup: bool,
+    ) -> Result<(Self, Self::Store), ()>;
+}
+
+/// Where the changes of the model are saved
+pub trait ModelStore<M>: Send + Sync + 'static {
+    /// Save the changes made since the last save. The model is locked only while the changes are
+    /// taken from it, so the searches and the indexing go on while they are written.
+    fn save(&self, model: &RwLock<M>) -> Result<(), ()>;
+
+    /// Keep tidying up the index in the background(e.g. merging the segments), it's run on its
+    /// own thread and never returns when there's anything to do
+    fn maintain(&self) {}
+}
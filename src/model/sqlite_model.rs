@@ -0,0 +1,1054 @@
+use super::super::config::IndexRules;
+use super::super::saver::{sibling_path, sync_parent};
+use super::{
+    analyzer::{query_terms, AnalyzedDocument, TermFreq},
+    in_memory_index_model::Model,
+    index_header::{Compatibility, IndexHeader},
+    persistent_model::{ModelStore, PersistentModel},
+};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Transaction};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::Read,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+    sync::{Mutex, MutexGuard, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// NOTE: the SQLite index file is made of the tables:
+//
+// - settings: the index header and the rules(JSON), by their names
+// - files: the source files with their last modified times, and whether they failed to be read
+// - docs: the path, the source file, the total tokens and the last modified time of every
+//   document, with its fields, its metadata and its passages(JSON)
+// - terms: every term with the number of the documents holding it
+// - postings: the term frequencies in the documents, by the terms and by the documents
+//
+// The times are the nanoseconds since the UNIX epoch. The version of the tables is the user version
+// of the database, so the other tools could check it by "PRAGMA user_version".
+pub const SCHEMA_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+const SCHEMA: &str = "
+CREATE TABLE settings(name TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE files(path TEXT PRIMARY KEY, last_modified INTEGER NOT NULL, failed INTEGER NOT NULL);
+CREATE TABLE docs(
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    file_path TEXT NOT NULL,
+    total_tokens INTEGER NOT NULL,
+    last_modified INTEGER NOT NULL,
+    fields TEXT NOT NULL,
+    metadata TEXT NOT NULL,
+    passages TEXT NOT NULL
+);
+CREATE INDEX docs_file_path ON docs(file_path);
+CREATE TABLE terms(id INTEGER PRIMARY KEY, term TEXT NOT NULL UNIQUE, df INTEGER NOT NULL);
+CREATE TABLE postings(
+    term_id INTEGER NOT NULL,
+    doc_id INTEGER NOT NULL,
+    freq INTEGER NOT NULL,
+    PRIMARY KEY(term_id, doc_id)
+) WITHOUT ROWID;
+CREATE INDEX postings_doc_id ON postings(doc_id);
+";
+
+/// Whether the file is a SQLite database, by its magic bytes
+pub fn is_sqlite_file(file_path: &Path) -> bool {
+    let mut bytes = [0; MAGIC.len()];
+    fs::File::open(file_path)
+        .and_then(|mut file| file.read_exact(&mut bytes))
+        .is_ok_and(|()| &bytes == MAGIC)
+}
+
+/// The model kept in a SQLite database rather than in the memory. Every change is written into the
+/// database right away, so the documents are never held in the memory as a whole.
+pub struct SqliteModel {
+    // NOTE: the searches share the model under the read lock, so they take turns on the connection
+    connection: Mutex<Connection>,
+    db_path: PathBuf,
+    generation: u64,
+}
+
+impl SqliteModel {
+    /// Create an empty database at `db_path`, the existing one is replaced
+    pub fn create(db_path: &Path) -> Result<Self, ()> {
+        if db_path.exists() {
+            fs::remove_file(db_path).map_err(|err| {
+                eprintln!(
+                    "ERROR: could not remove the file {db_path}: {err}",
+                    db_path = db_path.display()
+                )
+            })?;
+        }
+        remove_journals(db_path)?;
+
+        let connection = Connection::open(db_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not create the SQLite index {db_path}: {err}",
+                db_path = db_path.display()
+            )
+        })?;
+        let model = SqliteModel {
+            connection: Mutex::new(connection),
+            db_path: db_path.to_path_buf(),
+            generation: 0,
+        };
+        model
+            .configure()
+            .and_then(|()| model.connection().execute_batch(SCHEMA))
+            .and_then(|()| {
+                model
+                    .connection()
+                    .pragma_update(None, "user_version", SCHEMA_VERSION)
+            })
+            .map_err(|err| {
+                eprintln!(
+                    "ERROR: could not create the tables of the SQLite index {db_path}: {err}",
+                    db_path = db_path.display()
+                )
+            })?;
+        model.set_setting("header", &IndexHeader::current(Vec::new()))?;
+        model.set_setting("rules", &IndexRules::default())?;
+
+        Ok(model)
+    }
+
+    /// Open the database made by `create`
+    pub fn open(db_path: &Path) -> Result<Self, ()> {
+        if !is_sqlite_file(db_path) {
+            eprintln!(
+                "ERROR: {db_path} is not a SQLite index",
+                db_path = db_path.display()
+            );
+            return Err(());
+        }
+
+        let connection = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+            .map_err(|err| {
+                eprintln!(
+                    "ERROR: could not open the SQLite index {db_path}: {err}",
+                    db_path = db_path.display()
+                )
+            })?;
+        let version: u32 = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(|err| {
+                eprintln!(
+                    "ERROR: could not read the SQLite index {db_path}: {err}",
+                    db_path = db_path.display()
+                )
+            })?;
+        if version != SCHEMA_VERSION {
+            eprintln!(
+                "ERROR: the SQLite index {db_path} has the tables of the version {version}, the supported version is {SCHEMA_VERSION}",
+                db_path = db_path.display()
+            );
+            return Err(());
+        }
+
+        let model = SqliteModel {
+            connection: Mutex::new(connection),
+            db_path: db_path.to_path_buf(),
+            generation: 0,
+        };
+        model.configure().map_err(|err| {
+            eprintln!(
+                "ERROR: could not open the SQLite index {db_path}: {err}",
+                db_path = db_path.display()
+            )
+        })?;
+
+        Ok(model)
+    }
+
+    /// Close the database and move it to `db_path`. The previous database is kept as the
+    /// backup(e.g. "data.loser.db.bak") when `backup` is set.
+    pub fn persist(self, db_path: &Path, backup: bool) -> Result<(), ()> {
+        let temp_path = self.db_path;
+        // NOTE: the write-ahead log is merged into the database when it's closed, so the database
+        // is moved as a single file
+        self.connection
+            .into_inner()
+            .unwrap()
+            .close()
+            .map_err(|(_, err)| {
+                eprintln!(
+                    "ERROR: could not close the SQLite index {temp_path}: {err}",
+                    temp_path = temp_path.display()
+                )
+            })?;
+
+        if backup && db_path.exists() {
+            // NOTE: the changes left in the write-ahead log of the previous database(e.g. by the
+            // server) are merged into it, so the backup is complete
+            if let Ok(previous) =
+                Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+            {
+                previous
+                    .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+                    .ok();
+            }
+
+            let backup_path = sibling_path(db_path, "bak");
+            fs::rename(db_path, &backup_path).map_err(|err| {
+                eprintln!(
+                    "ERROR: could not back up the file {db_path}: {err}",
+                    db_path = db_path.display()
+                )
+            })?;
+        }
+
+        // NOTE: the write-ahead log left by the previous database would be applied to the new one
+        // when it's opened
+        remove_journals(db_path)?;
+        fs::rename(&temp_path, db_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not move the file {temp_path} to {db_path}: {err}",
+                temp_path = temp_path.display(),
+                db_path = db_path.display()
+            )
+        })?;
+        sync_parent(db_path);
+
+        Ok(())
+    }
+
+    /// What the database is made by
+    pub fn header(&self) -> Result<IndexHeader, ()> {
+        self.setting("header")
+    }
+
+    /// Move the changes in the write-ahead log into the database
+    pub fn checkpoint(&self) -> Result<(), ()> {
+        self.connection()
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(|err| {
+                eprintln!(
+                    "ERROR: could not save the changes into the SQLite index {db_path}: {err}",
+                    db_path = self.db_path.display()
+                )
+            })
+    }
+
+    // NOTE: the commits are not synced to the disk one by one, a crash loses the last changes
+    // rather than corrupting the database
+    fn configure(&self) -> rusqlite::Result<()> {
+        self.connection()
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        self.connection()
+            .pragma_update(None, "synchronous", "NORMAL")
+    }
+
+    fn setting<T: DeserializeOwned>(&self, name: &str) -> Result<T, ()> {
+        let value: String = self
+            .connection()
+            .query_row(
+                "SELECT value FROM settings WHERE name = ?1",
+                [name],
+                |row| row.get(0),
+            )
+            .map_err(|err| {
+                eprintln!("ERROR: could not read the {name} of the SQLite index: {err}")
+            })?;
+
+        serde_json::from_str(&value).map_err(|err| {
+            eprintln!("ERROR: could not parse the {name} of the SQLite index: {err}")
+        })
+    }
+
+    fn set_setting<T: Serialize>(&self, name: &str, value: &T) -> Result<(), ()> {
+        let value = serde_json::to_string(value)
+            .map_err(|err| eprintln!("ERROR: could not serialize the {name}: {err}"))?;
+        self.connection()
+            .execute(
+                "INSERT INTO settings(name, value) VALUES (?1, ?2)
+                 ON CONFLICT(name) DO UPDATE SET value = excluded.value",
+                params![name, value],
+            )
+            .map_err(|err| {
+                eprintln!("ERROR: could not save the {name} of the SQLite index: {err}")
+            })?;
+
+        Ok(())
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap()
+    }
+
+    fn rank(&self, query: &[char]) -> rusqlite::Result<Vec<(PathBuf, f32)>> {
+        let connection = self.connection();
+        let n = doc_count(&connection)? as f32;
+        let mut ranks: HashMap<i64, f32> = HashMap::new();
+        for term in query_terms(query) {
+            let (term_id, df) = match find_term(&connection, &term)? {
+                Some(term) => term,
+                None => continue,
+            };
+
+            let idf = (n / df as f32).log10();
+            let mut statement = connection.prepare_cached(
+                "SELECT postings.doc_id, postings.freq, docs.total_tokens FROM postings
+                 JOIN docs ON docs.id = postings.doc_id WHERE postings.term_id = ?1",
+            )?;
+            let postings = statement
+                .query_map([term_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            for posting in postings {
+                let (doc_id, count, total_tokens): (i64, usize, usize) = posting?;
+                *ranks.entry(doc_id).or_default() +=
+                    count as f32 / total_tokens.max(1) as f32 * idf;
+            }
+        }
+
+        let mut statement = connection.prepare_cached("SELECT id, path FROM docs")?;
+        let mut result = statement
+            .query_map([], |row| {
+                let id: i64 = row.get(0)?;
+                let path: String = row.get(1)?;
+                Ok((
+                    PathBuf::from(path),
+                    ranks.get(&id).cloned().unwrap_or(0_f32),
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<(PathBuf, f32)>>>()?;
+        result.sort_by(|(_, rank1), (_, rank2)| rank2.partial_cmp(rank1).unwrap());
+
+        Ok(result)
+    }
+
+    fn find_passages(&self, path: &Path) -> rusqlite::Result<Option<String>> {
+        self.connection()
+            .query_row(
+                "SELECT passages FROM docs WHERE path = ?1",
+                [path.to_string_lossy()],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    fn find_last_modified(&self, file_path: &Path) -> rusqlite::Result<Option<i64>> {
+        let file_path = file_path.to_string_lossy();
+        let last_modified = self
+            .connection()
+            .query_row(
+                "SELECT last_modified FROM files WHERE path = ?1",
+                [&file_path],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if last_modified.is_some() {
+            return Ok(last_modified);
+        }
+
+        self.connection()
+            .query_row(
+                "SELECT last_modified FROM docs WHERE path = ?1",
+                [&file_path],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+}
+
+impl Model for SqliteModel {
+    fn add_documents(
+        &mut self,
+        file_path: PathBuf,
+        documents: Vec<AnalyzedDocument>,
+        last_modified: SystemTime,
+    ) -> Result<(), ()> {
+        let transaction = self.connection.get_mut().unwrap().transaction();
+        transaction
+            .and_then(|transaction| {
+                remove_file(&transaction, &file_path)?;
+                for document in documents {
+                    // NOTE: the document replaced is removed first, its terms are counted again
+                    let doc_id = transaction
+                        .query_row(
+                            "SELECT id FROM docs WHERE path = ?1",
+                            [document.path.to_string_lossy()],
+                            |row| row.get(0),
+                        )
+                        .optional()?;
+                    if let Some(doc_id) = doc_id {
+                        remove_doc(&transaction, doc_id)?;
+                    }
+                    insert_doc(&transaction, &file_path, document)?;
+                }
+                transaction.execute(
+                    "INSERT INTO files(path, last_modified, failed) VALUES (?1, ?2, 0)",
+                    params![file_path.to_string_lossy(), to_nanos(last_modified)],
+                )?;
+                transaction.commit()
+            })
+            .map_err(|err| {
+                eprintln!(
+                    "ERROR: could not add the file {file_path} into the SQLite index: {err}",
+                    file_path = file_path.display()
+                )
+            })?;
+        self.generation += 1;
+
+        Ok(())
+    }
+
+    fn remove_document(&mut self, file_path: &Path) {
+        let removed = self
+            .connection
+            .get_mut()
+            .unwrap()
+            .transaction()
+            .and_then(|transaction| {
+                let removed = remove_file(&transaction, file_path)?;
+                transaction.commit()?;
+                Ok(removed)
+            });
+        match removed {
+            Ok(true) => self.generation += 1,
+            Ok(false) => {}
+            Err(err) => eprintln!(
+                "ERROR: could not remove the file {file_path} from the SQLite index: {err}",
+                file_path = file_path.display()
+            ),
+        }
+    }
+
+    fn record_failure(&mut self, file_path: PathBuf, last_modified: SystemTime) {
+        let recorded = self
+            .connection
+            .get_mut()
+            .unwrap()
+            .transaction()
+            .and_then(|transaction| {
+                remove_file(&transaction, &file_path)?;
+                transaction.execute(
+                    "INSERT INTO files(path, last_modified, failed) VALUES (?1, ?2, 1)",
+                    params![file_path.to_string_lossy(), to_nanos(last_modified)],
+                )?;
+                transaction.commit()
+            });
+        if let Err(err) = recorded {
+            eprintln!(
+                "ERROR: could not record the failure of the file {file_path} in the SQLite index: {err}",
+                file_path = file_path.display()
+            );
+        }
+        self.generation += 1;
+    }
+
+    fn search(&self, query: &[char]) -> Result<Vec<(PathBuf, f32)>, ()> {
+        self.rank(query)
+            .map_err(|err| eprintln!("ERROR: could not search the SQLite index: {err}"))
+    }
+
+    // NOTE: the file is read again when the database could not be read, so the index is repaired
+    // rather than missing the file
+    fn requires_reindexing(&self, file_path: &Path, last_modified: SystemTime) -> bool {
+        match self.find_last_modified(file_path) {
+            Ok(Some(indexed_at)) => indexed_at < to_nanos(last_modified),
+            Ok(None) => true,
+            Err(err) => {
+                eprintln!(
+                    "ERROR: could not read the file {file_path} from the SQLite index: {err}",
+                    file_path = file_path.display()
+                );
+                true
+            }
+        }
+    }
+
+    fn document_metadata(&self, path: &Path) -> Vec<(String, String)> {
+        let values = self
+            .connection()
+            .query_row(
+                "SELECT fields, metadata FROM docs WHERE path = ?1",
+                [path.to_string_lossy()],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional();
+        let (fields, metadata) = match values {
+            Ok(Some(values)) => values,
+            Ok(None) => return Vec::new(),
+            Err(err) => {
+                eprintln!(
+                    "ERROR: could not read the document {path} from the SQLite index: {err}",
+                    path = path.display()
+                );
+                return Vec::new();
+            }
+        };
+
+        let mut metadata = [fields, metadata]
+            .iter()
+            .flat_map(|values| {
+                serde_json::from_str::<HashMap<String, String>>(values).unwrap_or_default()
+            })
+            .collect::<Vec<(String, String)>>();
+        metadata.sort();
+
+        metadata
+    }
+
+    fn best_passage(&self, path: &Path, query: &[char]) -> Option<String> {
+        let passages = self.find_passages(path).ok()??;
+        let passages: Vec<(String, TermFreq)> = serde_json::from_str(&passages).ok()?;
+        let terms = query_terms(query);
+
+        // NOTE: 1 + idf is the weight of a term, so the terms in every document still count
+        let connection = self.connection();
+        let n = doc_count(&connection).ok()? as f32;
+        let mut weights = Vec::new();
+        for term in &terms {
+            let df = find_term(&connection, term)
+                .ok()?
+                .map(|(_, df)| df)
+                .unwrap_or(1);
+            weights.push(1_f32 + (n / df as f32).log10());
+        }
+
+        let mut best: Option<(String, f32)> = None;
+        for (anchor, tf) in passages {
+            let mut rank = 0_f32;
+            for (term, weight) in terms.iter().zip(&weights) {
+                rank += tf.get(term).cloned().unwrap_or(0) as f32 * weight;
+            }
+
+            if rank > 0_f32 && best.as_ref().is_none_or(|(_, best_rank)| rank > *best_rank) {
+                best = Some((anchor, rank));
+            }
+        }
+
+        best.map(|(anchor, _)| anchor)
+    }
+
+    fn contains_file(&self, file_path: &Path) -> bool {
+        self.connection()
+            .query_row(
+                "SELECT 1 FROM files WHERE path = ?1 AND failed = 0",
+                [file_path.to_string_lossy()],
+                |_| Ok(()),
+            )
+            .optional()
+            .is_ok_and(|found| found.is_some())
+    }
+
+    fn indexed_files(&self) -> Vec<PathBuf> {
+        let paths = self
+            .connection()
+            .prepare_cached("SELECT path FROM files")
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| row.get::<_, String>(0).map(PathBuf::from))?
+                    .collect::<rusqlite::Result<Vec<PathBuf>>>()
+            });
+
+        paths.unwrap_or_else(|err| {
+            eprintln!("ERROR: could not read the files from the SQLite index: {err}");
+            Vec::new()
+        })
+    }
+
+    fn indexed_files_in(&self, path: &Path) -> Vec<PathBuf> {
+        // NOTE: the paths in the folder sort between "folder/" and "folder0", since '0' follows the
+        // separator, so they're found by the primary key
+        let path = path.to_string_lossy();
+        let path = path.trim_end_matches(MAIN_SEPARATOR);
+        let next_separator = (MAIN_SEPARATOR as u8 + 1) as char;
+        let paths = self
+            .connection()
+            .prepare_cached("SELECT path FROM files WHERE path = ?1 OR (path >= ?2 AND path < ?3)")
+            .and_then(|mut statement| {
+                statement
+                    .query_map(
+                        [
+                            path.to_string(),
+                            format!("{path}{MAIN_SEPARATOR}"),
+                            format!("{path}{next_separator}"),
+                        ],
+                        |row| row.get::<_, String>(0).map(PathBuf::from),
+                    )?
+                    .collect::<rusqlite::Result<Vec<PathBuf>>>()
+            });
+
+        paths.unwrap_or_else(|err| {
+            eprintln!("ERROR: could not read the files from the SQLite index: {err}");
+            Vec::new()
+        })
+    }
+
+    // NOTE: the folder is indexed by the default rules when they could not be read, the error is
+    // reported
+    fn rules(&self) -> IndexRules {
+        self.setting("rules").unwrap_or_default()
+    }
+
+    fn set_rules(&mut self, rules: IndexRules) {
+        if self.set_setting("rules", &rules).is_ok() {
+            self.generation += 1;
+        }
+    }
+
+    fn set_roots(&mut self, roots: Vec<PathBuf>) {
+        if let Ok(mut header) = self.header() {
+            if header.roots != roots {
+                header.roots = roots;
+                if self.set_setting("header", &header).is_ok() {
+                    self.generation += 1;
+                }
+            }
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+/// The changes of the SQLite model are committed into the database right away, saving them only
+/// moves them from the write-ahead log into the database
+pub struct SqliteStore;
+
+impl ModelStore<SqliteModel> for SqliteStore {
+    fn save(&self, model: &RwLock<SqliteModel>) -> Result<(), ()> {
+        model.read().unwrap().checkpoint()
+    }
+}
+
+impl PersistentModel for SqliteModel {
+    type Store = SqliteStore;
+
+    const EXTENSION: &'static str = "loser.db";
+
+    // NOTE: the database is built aside and moved into place by `save_index`, so the previous one
+    // is still searchable during the indexing
+    fn create_index(index_path: &Path) -> Result<Self, ()> {
+        SqliteModel::create(&sibling_path(index_path, "tmp"))
+    }
+
+    fn save_index(self, index_path: &Path, backup: bool) -> Result<(), ()> {
+        self.persist(index_path, backup)
+    }
+
+    // NOTE: the server changes the database in place, so the backup is copied when it's opened,
+    // and it's copied back when the database is corrupt
+    fn open_index(
+        index_path: &Path,
+        roots: &[PathBuf],
+        backup: bool,
+    ) -> Result<(Self, SqliteStore), ()> {
+        if let Some(version) = schema_version(index_path) {
+            if version > SCHEMA_VERSION {
+                eprintln!(
+                    "ERROR: the SQLite index {index_path} is made by a newer version of LOSER(the version {version} of the tables), the supported version is {SCHEMA_VERSION}",
+                    index_path = index_path.display()
+                );
+                return Err(());
+            }
+        }
+
+        let backup_path = sibling_path(index_path, "bak");
+        let mut backed_up = false;
+        let mut loaded = None;
+        if index_path.exists() {
+            loaded = SqliteModel::open(index_path).ok();
+            if loaded.is_none() && backup_path.exists() {
+                loaded = restore(&backup_path, index_path);
+            }
+        }
+
+        let model = match loaded {
+            Some(model) => match model.header()?.compatibility(roots) {
+                Compatibility::Current => model,
+                Compatibility::Outdated => {
+                    println!(
+                        "INFO: migrate the index {index_path} to this version of LOSER",
+                        index_path = index_path.display()
+                    );
+                    let header = model.header()?;
+                    model.set_setting("header", &IndexHeader::current(header.roots))?;
+                    model
+                }
+                Compatibility::Incompatible(reason) => {
+                    println!("INFO: {reason}, index the folder from scratch...");
+                    let rules = model.rules();
+                    if backup {
+                        model.checkpoint()?;
+                        backed_up = true;
+                    }
+                    drop(model);
+                    // NOTE: the previous database is kept as the backup rather than the empty one
+                    if backed_up {
+                        fs::rename(index_path, &backup_path).map_err(|err| {
+                            eprintln!(
+                                "ERROR: could not back up the file {index_path}: {err}",
+                                index_path = index_path.display()
+                            )
+                        })?;
+                    }
+                    let mut model = SqliteModel::create(index_path)?;
+                    model.set_rules(rules);
+                    model
+                }
+            },
+            None => {
+                if index_path.exists() {
+                    println!("INFO: the index is corrupt, index the folder from scratch...");
+                }
+                SqliteModel::create(index_path)?
+            }
+        };
+
+        if backup && !backed_up {
+            model.checkpoint()?;
+            fs::copy(index_path, &backup_path).map_err(|err| {
+                eprintln!(
+                    "ERROR: could not back up the file {index_path}: {err}",
+                    index_path = index_path.display()
+                )
+            })?;
+        }
+
+        Ok((model, SqliteStore))
+    }
+}
+
+/// Copy the backup over the corrupt database, the backup is kept in case the database is
+/// corrupted again
+fn restore(backup_path: &Path, db_path: &Path) -> Option<SqliteModel> {
+    SqliteModel::open(backup_path).ok()?;
+    remove_journals(db_path).ok()?;
+    fs::copy(backup_path, db_path).ok()?;
+    let model = SqliteModel::open(db_path).ok()?;
+    println!(
+        "INFO: the index {db_path} is recovered from the backup {backup_path}",
+        db_path = db_path.display(),
+        backup_path = backup_path.display()
+    );
+
+    Some(model)
+}
+
+/// The version of the tables of the database, nothing when it's not a database
+fn schema_version(db_path: &Path) -> Option<u32> {
+    if !is_sqlite_file(db_path) {
+        return None;
+    }
+
+    let connection = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY).ok()?;
+    connection
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .ok()
+}
+
+fn doc_count(connection: &Connection) -> rusqlite::Result<usize> {
+    connection.query_row("SELECT COUNT(*) FROM docs", [], |row| row.get(0))
+}
+
+/// The id of the term and the number of the documents holding it
+fn find_term(connection: &Connection, term: &str) -> rusqlite::Result<Option<(i64, usize)>> {
+    connection
+        .prepare_cached("SELECT id, df FROM terms WHERE term = ?1")?
+        .query_row([term], |row| Ok((row.get(0)?, row.get(1)?)))
+        .optional()
+}
+
+/// Remove the source file and its documents, or the document at `file_path` when it's not a
+/// source file. It returns whether anything is removed.
+fn remove_file(transaction: &Transaction, file_path: &Path) -> rusqlite::Result<bool> {
+    let file_path = file_path.to_string_lossy();
+    let removed_file = transaction.execute("DELETE FROM files WHERE path = ?1", [&file_path])? > 0;
+    let query = if removed_file {
+        "SELECT id FROM docs WHERE file_path = ?1"
+    } else {
+        "SELECT id FROM docs WHERE path = ?1"
+    };
+    let doc_ids = transaction
+        .prepare_cached(query)?
+        .query_map([&file_path], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?;
+    for doc_id in &doc_ids {
+        remove_doc(transaction, *doc_id)?;
+    }
+
+    Ok(removed_file || !doc_ids.is_empty())
+}
+
+// NOTE: the terms left in no document are dropped, like the ones in the model in the memory
+fn remove_doc(transaction: &Transaction, doc_id: i64) -> rusqlite::Result<()> {
+    transaction.execute(
+        "UPDATE terms SET df = df - 1 WHERE id IN (SELECT term_id FROM postings WHERE doc_id = ?1)",
+        [doc_id],
+    )?;
+    transaction.execute(
+        "DELETE FROM terms WHERE df = 0 AND id IN (SELECT term_id FROM postings WHERE doc_id = ?1)",
+        [doc_id],
+    )?;
+    transaction.execute("DELETE FROM postings WHERE doc_id = ?1", [doc_id])?;
+    transaction.execute("DELETE FROM docs WHERE id = ?1", [doc_id])?;
+
+    Ok(())
+}
+
+fn insert_doc(
+    transaction: &Transaction,
+    file_path: &Path,
+    document: AnalyzedDocument,
+) -> rusqlite::Result<()> {
+    let AnalyzedDocument {
+        path,
+        tf,
+        total_tokens,
+        last_modified,
+        fields,
+        metadata,
+        passages,
+    } = document;
+    transaction.execute(
+        "INSERT INTO docs(path, file_path, total_tokens, last_modified, fields, metadata, passages)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            path.to_string_lossy(),
+            file_path.to_string_lossy(),
+            total_tokens,
+            to_nanos(last_modified),
+            to_json(&fields)?,
+            to_json(&metadata)?,
+            to_json(&passages)?,
+        ],
+    )?;
+    let doc_id = transaction.last_insert_rowid();
+
+    let mut insert_term = transaction.prepare_cached(
+        "INSERT INTO terms(term, df) VALUES (?1, 1)
+         ON CONFLICT(term) DO UPDATE SET df = df + 1 RETURNING id",
+    )?;
+    let mut insert_posting = transaction
+        .prepare_cached("INSERT INTO postings(term_id, doc_id, freq) VALUES (?1, ?2, ?3)")?;
+    for (term, count) in &tf {
+        let term_id: i64 = insert_term.query_row([term], |row| row.get(0))?;
+        insert_posting.execute(params![term_id, doc_id, count])?;
+    }
+
+    Ok(())
+}
+
+/// Remove the write-ahead log and the shared memory of the database
+fn remove_journals(db_path: &Path) -> Result<(), ()> {
+    for path in [journal_path(db_path, "wal"), journal_path(db_path, "shm")] {
+        if path.exists() {
+            fs::remove_file(&path).map_err(|err| {
+                eprintln!(
+                    "ERROR: could not remove the file {path}: {err}",
+                    path = path.display()
+                )
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The path of the journal files next to the database(e.g. "data.loser.db-wal")
+fn journal_path(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push("-");
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn to_nanos(time: SystemTime) -> i64 {
+    let duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX)
+}
+
+fn to_json<T: Serialize>(value: &T) -> rusqlite::Result<String> {
+    serde_json::to_string(value).map_err(|err| rusqlite::Error::ToSqlConversionFailure(err.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_sqlite_file, journal_path, SqliteModel};
+    use crate::config::IndexRules;
+    use crate::model::analyzer::AnalyzedDocument;
+    use crate::model::in_memory_index_model::{InMemoryIndexModel, Model};
+    use crate::model::index_header::IndexHeader;
+    use crate::model::persistent_model::PersistentModel;
+    use crate::reader::reader_trait::{Document, Passage};
+    use crate::saver::sibling_path;
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+        process,
+        time::{Duration, SystemTime},
+    };
+
+    fn add_samples(model: &mut dyn Model, time: SystemTime) -> Result<(), ()> {
+        let inputs = [
+            ("test/andy.txt", "Andy is Andy, hello Andy."),
+            ("test/amy.txt", "Amy is Amy, hello Amy."),
+            ("test/both.txt", "Andy and Amy."),
+        ];
+        for (path, content) in inputs {
+            let mut document = Document::new(PathBuf::from(path), content.to_string());
+            document.metadata = vec![("Author".to_string(), "Andy".to_string())];
+            document.passages = vec![Passage {
+                anchor: "00:00:01.000".to_string(),
+                text: content.to_string(),
+            }];
+            model.add_documents(
+                PathBuf::from(path),
+                vec![AnalyzedDocument::new(document, time)],
+                time,
+            )?;
+        }
+        model.record_failure(PathBuf::from("test/broken.pdf"), time);
+        model.remove_document(&PathBuf::from("test/amy.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_model_ok() -> Result<(), ()> {
+        // arrange
+        let db_path =
+            env::temp_dir().join(format!("loser-sqlite-test-{pid}.db", pid = process::id()));
+        let time = SystemTime::now();
+        let mut expected = InMemoryIndexModel::new();
+        add_samples(&mut expected, time)?;
+        let mut model = SqliteModel::create(&db_path)?;
+
+        // act
+        add_samples(&mut model, time)?;
+        let mut actual_files = model.indexed_files();
+        actual_files.sort();
+        let queries = ["andy", "amy", "author:andy", "hello andy", "nobody"];
+
+        // assert
+        let mut expected_files = expected.indexed_files();
+        expected_files.sort();
+        assert_eq!(actual_files, expected_files);
+        for path in ["test", "test/", "test/andy.txt", "tes", "test/andy"] {
+            let mut actual = model.indexed_files_in(Path::new(path));
+            let mut files = expected.indexed_files_in(Path::new(path));
+            actual.sort();
+            files.sort();
+            assert_eq!(actual, files);
+        }
+        for query in queries {
+            let query = query.chars().collect::<Vec<char>>();
+            let mut actual = model.search(&query)?;
+            let mut ranks = expected.search(&query)?;
+            actual.sort_by(|x, y| x.0.cmp(&y.0));
+            ranks.sort_by(|x, y| x.0.cmp(&y.0));
+            assert_eq!(actual, ranks);
+            for (path, _) in &ranks {
+                assert_eq!(
+                    model.best_passage(path, &query),
+                    expected.best_passage(path, &query)
+                );
+                assert_eq!(
+                    model.document_metadata(path),
+                    expected.document_metadata(path)
+                );
+            }
+        }
+        let later = time + Duration::from_secs(10);
+        for path in ["test/andy.txt", "test/broken.pdf", "test/amy.txt"] {
+            let path = PathBuf::from(path);
+            for time in [time, later] {
+                assert_eq!(
+                    model.requires_reindexing(&path, time),
+                    expected.requires_reindexing(&path, time)
+                );
+            }
+            assert_eq!(model.contains_file(&path), expected.contains_file(&path));
+        }
+        fs::remove_file(&db_path).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn persist_ok() -> Result<(), ()> {
+        // arrange
+        let temp_path =
+            env::temp_dir().join(format!("loser-persist-test-{pid}.tmp", pid = process::id()));
+        let db_path =
+            env::temp_dir().join(format!("loser-persist-test-{pid}.db", pid = process::id()));
+        let roots = vec![PathBuf::from("./data")];
+        let mut model = SqliteModel::create(&temp_path)?;
+        model.set_roots(roots.clone());
+        add_samples(&mut model, SystemTime::now())?;
+        // NOTE: the write-ahead log is left by the previous database
+        fs::write(journal_path(&db_path, "wal"), "stale")
+            .map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // act
+        model.persist(&db_path, false)?;
+        let stale = journal_path(&db_path, "wal").exists();
+        let model = SqliteModel::open(&db_path)?;
+        let actual = model.search(&"andy".chars().collect::<Vec<char>>())?;
+        let header = model.header()?;
+        drop(model);
+        let is_sqlite = [is_sqlite_file(&db_path), temp_path.exists()];
+        fs::remove_file(&db_path).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert_eq!(header, IndexHeader::current(roots));
+        assert_eq!(actual.len(), 2);
+        assert_eq!(actual[0].0, PathBuf::from("test/andy.txt"));
+        assert_eq!(is_sqlite, [true, false]);
+        assert!(!stale);
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_index_ok() -> Result<(), ()> {
+        // arrange
+        let index_path = env::temp_dir().join(format!(
+            "loser-open-test-{pid}.loser.db",
+            pid = process::id()
+        ));
+        let backup_path = sibling_path(&index_path, "bak");
+        let roots = vec![PathBuf::from("./data")];
+        let other_roots = vec![PathBuf::from("./other")];
+        let rules = IndexRules {
+            max_depth: Some(3),
+            ..IndexRules::default()
+        };
+        let mut model = SqliteModel::create_index(&index_path)?;
+        model.set_rules(rules.clone());
+        model.set_roots(roots.clone());
+        add_samples(&mut model, SystemTime::now())?;
+        model.save_index(&index_path, false)?;
+        let count = |model: &SqliteModel| model.indexed_files().len();
+
+        // act
+        let (opened, _) = SqliteModel::open_index(&index_path, &roots, true)?;
+        let opened = count(&opened);
+        // NOTE: the database is truncated like a crash during the write
+        fs::write(&index_path, "SQLite format 3\0").map_err(|err| eprintln!("ERROR: {err}"))?;
+        let (recovered, _) = SqliteModel::open_index(&index_path, &roots, false)?;
+        let recovered = count(&recovered);
+        let (incompatible, _) = SqliteModel::open_index(&index_path, &other_roots, true)?;
+        let actual_rules = incompatible.rules();
+        let incompatible = count(&incompatible);
+        let backed_up = count(&SqliteModel::open(&backup_path)?);
+        fs::remove_file(&index_path).map_err(|err| eprintln!("ERROR: {err}"))?;
+        fs::remove_file(&backup_path).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert_eq!(opened, 3);
+        assert_eq!(recovered, 3);
+        assert_eq!(incompatible, 0);
+        assert_eq!(backed_up, 3);
+        assert_eq!(actual_rules, rules);
+
+        Ok(())
+    }
+}
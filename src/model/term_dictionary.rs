@@ -0,0 +1,199 @@
+use std::{collections::HashMap, mem, sync::Arc};
+
+/// The id of a term in the term dictionary
+pub type TermId = u32;
+
+/// The terms of the model with the number of the documents holding them(the document frequency).
+/// Every term is stored once and the documents and their passages refer to it by its id. The ids of
+/// the terms left in no document and no passage are reused by the new terms, so the dictionary
+/// doesn't grow with the changes.
+#[derive(Debug, Default, Clone)]
+pub struct TermDictionary {
+    ids: HashMap<Arc<str>, TermId>,
+    entries: Vec<Option<Entry>>,
+    free_ids: Vec<TermId>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    term: Arc<str>,
+    df: usize,
+    /// The number of the passages holding the term, they keep the term without counting it in the
+    /// document frequency
+    passages: usize,
+}
+
+// NOTE: the ids depend on the order the terms are added, the dictionaries with the same terms and
+// the same document frequencies are equal
+impl PartialEq for TermDictionary {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(term, df)| other.df(term) == Some(df))
+    }
+}
+
+impl Eq for TermDictionary {}
+
+impl TermDictionary {
+    pub fn new() -> Self {
+        TermDictionary::default()
+    }
+
+    /// The count of the terms, including the ones only in the passages
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn id(&self, term: &str) -> Option<TermId> {
+        self.ids.get(term).copied()
+    }
+
+    /// The term of the id, the ids are taken from the dictionary so the term is always there
+    pub fn term(&self, id: TermId) -> &str {
+        &self.entry(id).term
+    }
+
+    /// The number of the documents holding the term, nothing when no document holds it
+    pub fn df(&self, term: &str) -> Option<usize> {
+        self.id(term)
+            .map(|id| self.entry(id).df)
+            .filter(|df| *df > 0)
+    }
+
+    /// The terms of the documents with their document frequencies
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.entries
+            .iter()
+            .flatten()
+            .filter(|entry| entry.df > 0)
+            .map(|entry| (&*entry.term, entry.df))
+    }
+
+    /// Count the term in `docs` more documents, the term is added when it's new
+    pub fn add(&mut self, term: &str, docs: usize) -> TermId {
+        let id = self.intern(term);
+        self.entry_mut(id).df += docs;
+        id
+    }
+
+    /// Count the term in one more passage, the term is added when it's new
+    pub fn add_passage_term(&mut self, term: &str) -> TermId {
+        let id = self.intern(term);
+        self.entry_mut(id).passages += 1;
+        id
+    }
+
+    /// Uncount the term in a document removed, the term is dropped when it's left in no document
+    /// and no passage
+    pub fn release(&mut self, id: TermId) {
+        self.entry_mut(id).df -= 1;
+        self.drop_unused(id);
+    }
+
+    /// Uncount the term in a passage removed
+    pub fn release_passage_term(&mut self, id: TermId) {
+        self.entry_mut(id).passages -= 1;
+        self.drop_unused(id);
+    }
+
+    /// The bytes taken by the dictionary, estimated from the capacities of its tables and the
+    /// lengths of the terms
+    pub fn memory_usage(&self) -> usize {
+        // NOTE: a hash table has a control byte for every bucket, and an Arc has the strong and the
+        // weak counts before the term
+        let terms = self
+            .ids
+            .keys()
+            .map(|term| term.len() + 2 * mem::size_of::<usize>())
+            .sum::<usize>();
+
+        self.ids.capacity() * (mem::size_of::<(Arc<str>, TermId)>() + 1)
+            + self.entries.capacity() * mem::size_of::<Option<Entry>>()
+            + self.free_ids.capacity() * mem::size_of::<TermId>()
+            + terms
+    }
+
+    /// The id of the term, the term is added with no document and no passage when it's new
+    fn intern(&mut self, term: &str) -> TermId {
+        if let Some(id) = self.id(term) {
+            return id;
+        }
+
+        let term: Arc<str> = Arc::from(term);
+        let entry = Some(Entry {
+            term: Arc::clone(&term),
+            df: 0,
+            passages: 0,
+        });
+        let id = match self.free_ids.pop() {
+            Some(id) => {
+                self.entries[id as usize] = entry;
+                id
+            }
+            None => {
+                self.entries.push(entry);
+                (self.entries.len() - 1) as TermId
+            }
+        };
+        self.ids.insert(term, id);
+
+        id
+    }
+
+    fn drop_unused(&mut self, id: TermId) {
+        let entry = self.entry(id);
+        if entry.df == 0 && entry.passages == 0 {
+            let term = Arc::clone(&entry.term);
+            self.ids.remove(&term);
+            self.entries[id as usize] = None;
+            self.free_ids.push(id);
+        }
+    }
+
+    fn entry(&self, id: TermId) -> &Entry {
+        self.entries[id as usize]
+            .as_ref()
+            .expect("the id should be in the dictionary")
+    }
+
+    fn entry_mut(&mut self, id: TermId) -> &mut Entry {
+        self.entries[id as usize]
+            .as_mut()
+            .expect("the id should be in the dictionary")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TermDictionary;
+
+    #[test]
+    fn term_dictionary_ok() -> Result<(), ()> {
+        // arrange
+        let mut terms = TermDictionary::new();
+        let andy = terms.add("ANDY", 1);
+        let is = terms.add("IS", 1);
+
+        // act
+        let andy_again = terms.add("ANDY", 2);
+        terms.release(is);
+        let amy = terms.add("AMY", 1);
+        let here = terms.add_passage_term("HERE");
+        let passage_df = terms.df("HERE");
+        terms.release_passage_term(here);
+        let mut expected = TermDictionary::new();
+        expected.add("AMY", 1);
+        expected.add("ANDY", 3);
+
+        // assert
+        assert_eq!(andy_again, andy);
+        assert_eq!(terms.df("IS"), None);
+        assert_eq!(amy, is);
+        assert_eq!(terms.term(amy), "AMY");
+        assert_eq!(terms.df("ANDY"), Some(3));
+        assert_eq!(passage_df, None);
+        assert_eq!(terms.id("HERE"), None);
+        assert_eq!(terms, expected);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How often the live progress line is redrawn
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The progress of the indexing, shared by the indexer and the server
+pub struct Progress {
+    /// Whether the progress is drawn as a live line on the terminal
+    live: bool,
+    state: Mutex<ProgressState>,
+}
+
+#[derive(Default)]
+struct ProgressState {
+    started: Option<Instant>,
+    finished: Option<Instant>,
+    redrawn: Option<Instant>,
+    /// Whether the walker is still finding the files, so the totals could grow
+    counting: bool,
+    total_files: usize,
+    total_bytes: u64,
+    processed_files: usize,
+    processed_bytes: u64,
+    errors: usize,
+}
+
+/// The snapshot of the progress, served as JSON by the server
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ProgressReport {
+    pub indexing: bool,
+    /// Whether the files to be indexed are still being found, the totals grow meanwhile
+    pub counting: bool,
+    pub total_files: usize,
+    pub processed_files: usize,
+    pub total_bytes: u64,
+    pub processed_bytes: u64,
+    pub errors: usize,
+    pub elapsed_secs: f64,
+    pub bytes_per_sec: f64,
+    /// The estimated seconds to finish, nothing when it could not be estimated yet
+    pub eta_secs: Option<f64>,
+}
+
+impl Progress {
+    pub fn new(live: bool) -> Self {
+        Progress {
+            live,
+            state: Mutex::new(ProgressState::default()),
+        }
+    }
+
+    /// Whether the progress is drawn as a live line, so the files don't need to be listed
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
+    /// Start counting the progress once the first file to be indexed is found, the previous
+    /// progress is reset
+    pub fn start(&self) {
+        *self.state.lock().unwrap() = ProgressState {
+            started: Some(Instant::now()),
+            counting: true,
+            ..Default::default()
+        };
+    }
+
+    /// Count the file of `bytes` found to be indexed into the totals
+    pub fn found(&self, bytes: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.total_files += 1;
+        state.total_bytes += bytes;
+    }
+
+    /// All the files to be indexed are found, so the totals are final
+    pub fn counted(&self) {
+        self.state.lock().unwrap().counting = false;
+    }
+
+    /// Count the file whose `bytes` are read, or could not be read
+    pub fn processed(&self, bytes: u64, failed: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.processed_files += 1;
+        state.processed_bytes += bytes;
+        if failed {
+            state.errors += 1;
+        }
+
+        let now = Instant::now();
+        if self.live && state.redrawn.is_none_or(|x| now - x >= REDRAW_INTERVAL) {
+            state.redrawn = Some(now);
+            let line = state.report(now).line();
+            drop(state);
+            eprint!("\r\x1b[2K{line}");
+            io::stderr().flush().ok();
+        }
+    }
+
+    pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.finished = Some(now);
+        if self.live && state.total_files > 0 {
+            eprintln!("\r\x1b[2K{line}", line = state.report(now).line());
+        }
+    }
+
+    pub fn report(&self) -> ProgressReport {
+        self.state.lock().unwrap().report(Instant::now())
+    }
+}
+
+impl ProgressState {
+    fn report(&self, now: Instant) -> ProgressReport {
+        let started = match self.started {
+            Some(started) => started,
+            None => return ProgressReport::default(),
+        };
+        let elapsed_secs = (self.finished.unwrap_or(now) - started).as_secs_f64();
+        let bytes_per_sec = if elapsed_secs > 0.0 {
+            self.processed_bytes as f64 / elapsed_secs
+        } else {
+            0.0
+        };
+
+        // NOTE: the ETA is estimated by the bytes since the larger files take longer to read, and
+        // by the files when the processed files are all empty. It's unknown until all the files
+        // are found.
+        let eta_secs = if self.finished.is_some() {
+            Some(0.0)
+        } else if self.counting {
+            None
+        } else if self.processed_bytes > 0 {
+            Some(self.total_bytes.saturating_sub(self.processed_bytes) as f64 / bytes_per_sec)
+        } else if self.processed_files > 0 {
+            let remaining = self.total_files.saturating_sub(self.processed_files) as f64;
+            Some(remaining * elapsed_secs / self.processed_files as f64)
+        } else {
+            None
+        };
+
+        ProgressReport {
+            indexing: self.finished.is_none(),
+            counting: self.counting,
+            total_files: self.total_files,
+            processed_files: self.processed_files,
+            total_bytes: self.total_bytes,
+            processed_bytes: self.processed_bytes,
+            errors: self.errors,
+            elapsed_secs,
+            bytes_per_sec,
+            eta_secs,
+        }
+    }
+}
+
+impl ProgressReport {
+    /// The progress line(e.g. "Indexing: 120/500 files, 1.5 MiB/6.0 MiB, 3.0 MiB/s, ETA 2s, 0
+    /// errors")
+    pub fn line(&self) -> String {
+        let eta = match self.eta_secs {
+            Some(eta) => format!("{eta:.0}s"),
+            None => "-".to_string(),
+        };
+
+        format!(
+            "Indexing: {processed_files}/{total_files} files, {processed_bytes}/{total_bytes}, {speed}/s, ETA {eta}, {errors} errors",
+            processed_files = self.processed_files,
+            total_files = self.total_files,
+            processed_bytes = format_bytes(self.processed_bytes as f64),
+            total_bytes = format_bytes(self.total_bytes as f64),
+            speed = format_bytes(self.bytes_per_sec),
+            errors = self.errors,
+        )
+    }
+}
+
+/// Format the bytes with the binary units(e.g. "1.5 MiB")
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024.0 {
+        return format!("{bytes:.0} B");
+    }
+
+    let mut value = bytes / 1024.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+
+    format!("{value:.1} {unit}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProgressReport, ProgressState};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn report_ok() {
+        // arrange
+        let started = Instant::now();
+        let state = ProgressState {
+            started: Some(started),
+            total_files: 4,
+            total_bytes: 4096,
+            processed_files: 1,
+            processed_bytes: 1024,
+            errors: 1,
+            ..Default::default()
+        };
+
+        // act
+        let actual = state.report(started + Duration::from_secs(2));
+        let counting = ProgressState {
+            counting: true,
+            ..state
+        }
+        .report(started + Duration::from_secs(2));
+
+        // assert
+        assert_eq!(
+            actual,
+            ProgressReport {
+                indexing: true,
+                counting: false,
+                total_files: 4,
+                processed_files: 1,
+                total_bytes: 4096,
+                processed_bytes: 1024,
+                errors: 1,
+                elapsed_secs: 2.0,
+                bytes_per_sec: 512.0,
+                eta_secs: Some(6.0),
+            }
+        );
+        assert_eq!(
+            actual.line(),
+            "Indexing: 1/4 files, 1.0 KiB/4.0 KiB, 512 B/s, ETA 6s, 1 errors"
+        );
+        assert_eq!(counting.eta_secs, None);
+        assert!(counting.counting);
+    }
+}
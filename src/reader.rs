@@ -1,3 +1,18 @@
+pub mod archive_reader;
+pub mod command_reader;
+pub mod csv_reader;
+pub mod email_reader;
+pub mod file_type;
+pub mod html_reader;
+pub mod json_reader;
+pub mod latex_reader;
+pub mod metadata;
+pub mod notebook_reader;
+pub mod office_reader;
 pub mod pdf_reader;
 pub mod plain_text_reader;
 pub mod reader_trait;
+pub mod registry;
+pub mod rst_reader;
+pub mod sandboxed_reader;
+pub mod subtitle_reader;
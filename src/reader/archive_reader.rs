@@ -0,0 +1,520 @@
+use super::super::config::{ArchiveConfig, FolderConfig};
+use super::{
+    file_type::{is_tar, FileType, SNIFF_LENGTH},
+    reader_trait::{Document, Reader},
+    registry::ReaderRegistry,
+};
+use flate2::read::GzDecoder;
+use std::{
+    env, fs,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Reader of zip, tar, tar.gz and gz archives. An archive is treated as a virtual folder, and every
+/// supported file in it is indexed under a path like "backup.zip!/notes/a.txt".
+pub struct ArchiveReader {
+    /// The config of the folder holding the archive, which applies to the files in the archive
+    pub config: FolderConfig,
+}
+
+/// The separator between the path to the archive and the path to the file inside of it
+pub const ARCHIVE_SEPARATOR: &str = "!/";
+
+// NOTE: used to name the temporary folders, so the entries extracted at the same time won't collide
+static EXTRACTED_ENTRIES: AtomicUsize = AtomicUsize::new(0);
+
+impl Reader for ArchiveReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        Ok(self
+            .read_documents(file_path)?
+            .into_iter()
+            .map(|document| document.content)
+            .collect::<Vec<String>>()
+            .join("\n"))
+    }
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let archive = self.config.archive();
+        // NOTE: the limits apply to every archive on its own, so the archives inside the archives
+        // are opened one level less deep, and they're skipped like the binary files once no level
+        // is left
+        let max_depth = archive.max_depth;
+        let nested_config = FolderConfig {
+            archive: Some(ArchiveConfig {
+                max_depth: max_depth.saturating_sub(1),
+                ..archive.clone()
+            }),
+            ..self.config.clone()
+        };
+        let mut registry = ReaderRegistry::new(&nested_config);
+        if max_depth == 0 {
+            registry.unregister_builtin("archive");
+        }
+        let mut limits = EntryLimits {
+            config: archive,
+            file_path,
+            entries: 0,
+            total_bytes: 0,
+        };
+        let mut documents = Vec::new();
+        // NOTE: the entries are read one by one, so only one of them is held in the memory
+        read_entries(file_path, &mut limits, &mut |name, data| {
+            let virtual_path = format!(
+                "{archive}{ARCHIVE_SEPARATOR}{name}",
+                archive = file_path.display()
+            );
+            if let Ok(entry_documents) = read_entry(&name, &data, &virtual_path, &registry) {
+                documents.extend(entry_documents);
+            }
+        })?;
+
+        Ok(documents)
+    }
+}
+
+/// Read the content of the file named `name` in the archive(e.g. "notes/a.txt" of
+/// "backup.zip!/notes/a.txt"), within the limits of the archive config
+pub fn read_archive_entry(
+    file_path: &Path,
+    name: &str,
+    config: &FolderConfig,
+) -> Result<Vec<u8>, ()> {
+    let mut limits = EntryLimits {
+        config: config.archive(),
+        file_path,
+        entries: 0,
+        total_bytes: 0,
+    };
+    let mut content = None;
+    read_entries(file_path, &mut limits, &mut |entry_name, data| {
+        if content.is_none() && entry_name == name {
+            content = Some(data);
+        }
+    })?;
+
+    content.ok_or_else(|| {
+        eprintln!(
+            "ERROR: could not find {name} in the archive {file_path}",
+            file_path = file_path.display()
+        )
+    })
+}
+
+/// The count of the files and the bytes read from the archive, the archive fails to be read once
+/// they exceed the limits
+struct EntryLimits<'a> {
+    config: ArchiveConfig,
+    file_path: &'a Path,
+    entries: usize,
+    total_bytes: u64,
+}
+
+impl EntryLimits<'_> {
+    /// Read the content of the file in the archive, no more bytes than the limits are read
+    fn read<R: Read>(&mut self, name: &str, reader: R) -> Result<Vec<u8>, ()> {
+        let file_path = self.file_path.display();
+        self.entries += 1;
+        if self.entries > self.config.max_entries {
+            eprintln!(
+                "ERROR: the archive {file_path} holds more than {max_entries} files",
+                max_entries = self.config.max_entries
+            );
+            return Err(());
+        }
+
+        let left_bytes = self.config.max_total_bytes.saturating_sub(self.total_bytes);
+        let limit = self.config.max_entry_bytes.min(left_bytes);
+        let mut data = Vec::new();
+        reader
+            .take(limit + 1)
+            .read_to_end(&mut data)
+            .map_err(|err| {
+                eprintln!("ERROR: could not read {name} in the archive {file_path}: {err}")
+            })?;
+        if data.len() as u64 > limit {
+            if limit < self.config.max_entry_bytes {
+                eprintln!(
+                    "ERROR: the files in the archive {file_path} are larger than {max_total_bytes} bytes",
+                    max_total_bytes = self.config.max_total_bytes
+                );
+            } else {
+                eprintln!(
+                    "ERROR: {name} in the archive {file_path} is larger than {max_entry_bytes} bytes",
+                    max_entry_bytes = self.config.max_entry_bytes
+                );
+            }
+            return Err(());
+        }
+        self.total_bytes += data.len() as u64;
+
+        Ok(data)
+    }
+}
+
+/// Read the names and the content of the files in the archive one by one
+fn read_entries<F>(file_path: &Path, limits: &mut EntryLimits, on_entry: &mut F) -> Result<(), ()>
+where
+    F: FnMut(String, Vec<u8>),
+{
+    let file = fs::File::open(file_path).map_err(|err| {
+        eprintln!(
+            "ERROR: could not open the archive {file_path}: {err}",
+            file_path = file_path.display()
+        )
+    })?;
+
+    match FileType::sniff_file(file_path)? {
+        FileType::Zip => read_zip_entries(file, file_path, limits, on_entry),
+        FileType::Tar => read_tar_entries(file, file_path, limits, on_entry),
+        FileType::Gzip => {
+            let mut decoder = GzDecoder::new(file);
+            let mut head = Vec::new();
+            (&mut decoder)
+                .take(SNIFF_LENGTH as u64)
+                .read_to_end(&mut head)
+                .map_err(|err| {
+                    eprintln!(
+                        "ERROR: could not decompress the file {file_path}: {err}",
+                        file_path = file_path.display()
+                    )
+                })?;
+
+            let is_tar_gz = is_tar(&head);
+            let reader = Cursor::new(head).chain(decoder);
+            if is_tar_gz {
+                return read_tar_entries(reader, file_path, limits, on_entry);
+            }
+
+            // NOTE: a plain gz file only holds a single file named without the ".gz" suffix
+            let name = file_path
+                .file_stem()
+                .map(|x| x.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let data = limits.read(&name, reader)?;
+            on_entry(name, data);
+
+            Ok(())
+        }
+        _ => {
+            eprintln!(
+                "ERROR: {file_path} is not a zip, tar or gz archive",
+                file_path = file_path.display()
+            );
+            Err(())
+        }
+    }
+}
+
+fn read_zip_entries<F>(
+    file: fs::File,
+    file_path: &Path,
+    limits: &mut EntryLimits,
+    on_entry: &mut F,
+) -> Result<(), ()>
+where
+    F: FnMut(String, Vec<u8>),
+{
+    let mut archive = zip::ZipArchive::new(file).map_err(|err| {
+        eprintln!(
+            "ERROR: could not read the zip archive {file_path}: {err}",
+            file_path = file_path.display()
+        )
+    })?;
+
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).map_err(|err| {
+            eprintln!(
+                "ERROR: could not read the entry in zip archive {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+        if entry.is_dir() {
+            continue;
+        }
+
+        // NOTE: the entries with unsafe names(e.g. "../a.txt") are skipped
+        let name = match entry.enclosed_name() {
+            Some(name) => name.to_string_lossy().replace('\\', "/"),
+            None => continue,
+        };
+
+        let data = limits.read(&name, entry)?;
+        on_entry(name, data);
+    }
+
+    Ok(())
+}
+
+fn read_tar_entries<R, F>(
+    reader: R,
+    file_path: &Path,
+    limits: &mut EntryLimits,
+    on_entry: &mut F,
+) -> Result<(), ()>
+where
+    R: Read,
+    F: FnMut(String, Vec<u8>),
+{
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries().map_err(|err| {
+        eprintln!(
+            "ERROR: could not read the tar archive {file_path}: {err}",
+            file_path = file_path.display()
+        )
+    })? {
+        let entry = entry.map_err(|err| {
+            eprintln!(
+                "ERROR: could not read the entry in tar archive {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = match entry.path() {
+            Ok(name) => name.to_string_lossy().trim_start_matches("./").to_string(),
+            Err(_) => continue,
+        };
+
+        let data = limits.read(&name, entry)?;
+        on_entry(name, data);
+    }
+
+    Ok(())
+}
+
+/// Extract the entry into a temporary file, so it could be read like other files on the disk, and
+/// then replace the temporary path in the documents with the virtual one.
+fn read_entry(
+    name: &str,
+    data: &[u8],
+    virtual_path: &str,
+    registry: &ReaderRegistry,
+) -> Result<Vec<Document>, ()> {
+    let file_name = Path::new(name).file_name().ok_or(())?;
+    let dir = env::temp_dir().join(format!(
+        "loser-{pid}-{n}",
+        pid = process::id(),
+        n = EXTRACTED_ENTRIES.fetch_add(1, Ordering::SeqCst)
+    ));
+    let temp_path = dir.join(file_name);
+
+    fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&temp_path, data))
+        .map_err(|err| {
+            eprintln!("ERROR: could not extract {virtual_path} into a temporary file: {err}")
+        })?;
+
+    let result = registry.read_from_file(&temp_path);
+    fs::remove_dir_all(&dir)
+        .map_err(|err| {
+            eprintln!(
+                "ERROR: could not remove the temporary folder {dir}: {err}",
+                dir = dir.display()
+            )
+        })
+        .ok();
+
+    let temp_path = temp_path.to_string_lossy().to_string();
+    Ok(result?
+        .into_iter()
+        .map(|mut document| {
+            document.path = PathBuf::from(document.path.to_string_lossy().replacen(
+                &temp_path,
+                virtual_path,
+                1,
+            ));
+            document
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArchiveReader;
+    use crate::{
+        config::{ArchiveConfig, FolderConfig},
+        reader::reader_trait::Reader,
+    };
+    use flate2::{write::GzEncoder, Compression};
+    use std::{env, fs, path::PathBuf, process};
+
+    #[test]
+    fn read_documents_tar_gz_ok() -> Result<(), ()> {
+        // arrange
+        let dir = env::temp_dir().join(format!("loser-archive-test-{pid}", pid = process::id()));
+        fs::create_dir_all(&dir).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let archive_path = dir.join("backup.tar.gz");
+
+        let encoder = GzEncoder::new(
+            fs::File::create(&archive_path).map_err(|err| eprintln!("ERROR: {err}"))?,
+            Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        let entries: [(&str, &[u8]); 2] = [
+            ("notes/a.txt", b"Andy is Andy."),
+            ("image.png", b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR"),
+        ];
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content)
+                .map_err(|err| eprintln!("ERROR: {err}"))?;
+        }
+        builder
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        let expected_path = PathBuf::from(format!(
+            "{archive}!/notes/a.txt",
+            archive = archive_path.display()
+        ));
+
+        // act
+        let reader = ArchiveReader {
+            config: FolderConfig::default(),
+        };
+        let actual = reader.read_documents(&archive_path);
+        fs::remove_dir_all(&dir).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let actual = actual?;
+
+        // assert
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].path, expected_path);
+        assert_eq!(actual[0].content, "Andy is Andy.");
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_documents_limits_ok() -> Result<(), ()> {
+        // arrange
+        let dir = env::temp_dir().join(format!(
+            "loser-archive-limits-test-{pid}",
+            pid = process::id()
+        ));
+        fs::create_dir_all(&dir).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let archive_path = dir.join("backup.tar");
+        let mut builder = tar::Builder::new(
+            fs::File::create(&archive_path).map_err(|err| eprintln!("ERROR: {err}"))?,
+        );
+        for (name, content) in [("a.txt", "Andy is Andy."), ("b.txt", "Amy is Amy.")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .map_err(|err| eprintln!("ERROR: {err}"))?;
+        }
+        builder.finish().map_err(|err| eprintln!("ERROR: {err}"))?;
+        let read = |archive: ArchiveConfig| {
+            let reader = ArchiveReader {
+                config: FolderConfig {
+                    archive: Some(archive),
+                    ..FolderConfig::default()
+                },
+            };
+            reader
+                .read_documents(&archive_path)
+                .map(|documents| documents.len())
+        };
+
+        // act
+        let within_limits = read(ArchiveConfig::default());
+        let too_many_files = read(ArchiveConfig {
+            max_entries: 1,
+            ..ArchiveConfig::default()
+        });
+        let too_large_file = read(ArchiveConfig {
+            max_entry_bytes: 12,
+            ..ArchiveConfig::default()
+        });
+        let too_large_archive = read(ArchiveConfig {
+            max_total_bytes: 20,
+            ..ArchiveConfig::default()
+        });
+        fs::remove_dir_all(&dir).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert_eq!(within_limits, Ok(2));
+        assert_eq!(too_many_files, Err(()));
+        assert_eq!(too_large_file, Err(()));
+        assert_eq!(too_large_archive, Err(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_documents_nested_ok() -> Result<(), ()> {
+        // arrange
+        let dir = env::temp_dir().join(format!(
+            "loser-archive-nested-test-{pid}",
+            pid = process::id()
+        ));
+        fs::create_dir_all(&dir).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let archive_path = dir.join("outer.tar");
+        let tar = |name: &str, content: &[u8]| -> Result<Vec<u8>, ()> {
+            let mut builder = tar::Builder::new(Vec::new());
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content)
+                .map_err(|err| eprintln!("ERROR: {err}"))?;
+            builder
+                .into_inner()
+                .map_err(|err| eprintln!("ERROR: {err}"))
+        };
+        // NOTE: a.txt is in inner.tar, which is in middle.tar, which is in outer.tar
+        let inner = tar("a.txt", b"Andy is Andy.")?;
+        let middle = tar("inner.tar", &inner)?;
+        fs::write(&archive_path, tar("middle.tar", &middle)?)
+            .map_err(|err| eprintln!("ERROR: {err}"))?;
+        let read = |max_depth: usize| {
+            let reader = ArchiveReader {
+                config: FolderConfig {
+                    archive: Some(ArchiveConfig {
+                        max_depth,
+                        ..ArchiveConfig::default()
+                    }),
+                    ..FolderConfig::default()
+                },
+            };
+            reader.read_documents(&archive_path).map(|documents| {
+                documents
+                    .into_iter()
+                    .map(|document| document.path)
+                    .collect::<Vec<PathBuf>>()
+            })
+        };
+        let expected_path = PathBuf::from(format!(
+            "{archive}!/middle.tar!/inner.tar!/a.txt",
+            archive = archive_path.display()
+        ));
+
+        // act
+        let within_depth = read(2);
+        let too_deep = read(1);
+        let not_nested = read(0);
+        fs::remove_dir_all(&dir).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert_eq!(within_depth, Ok(vec![expected_path]));
+        assert_eq!(too_deep, Ok(Vec::new()));
+        assert_eq!(not_nested, Ok(Vec::new()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use super::super::config::CommandConfig;
+use super::reader_trait::Reader;
+use std::{
+    io::Read,
+    path::Path,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Run an external program, and capture its standard output as the text of the file
+pub struct CommandReader {
+    pub program: String,
+    pub args: Vec<String>,
+    pub timeout: Duration,
+    pub max_output_bytes: usize,
+}
+
+const PLACEHOLDER: &str = "{}";
+
+impl CommandReader {
+    pub fn from_config(config: &CommandConfig) -> Result<Self, ()> {
+        let (program, args) = config
+            .command
+            .split_first()
+            .ok_or_else(|| eprintln!("ERROR: the command of the reader should not be empty."))?;
+
+        Ok(CommandReader {
+            program: program.clone(),
+            args: args.to_vec(),
+            timeout: Duration::from_secs(config.timeout_secs),
+            max_output_bytes: config.max_output_bytes,
+        })
+    }
+}
+
+impl Reader for CommandReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        let file_path_arg = file_path.to_string_lossy().to_string();
+        let mut args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| arg.replace(PLACEHOLDER, &file_path_arg))
+            .collect();
+        if !self.args.iter().any(|arg| arg.contains(PLACEHOLDER)) {
+            args.push(file_path_arg);
+        }
+
+        let mut command = Command::new(&self.program);
+        command.args(&args);
+        let output = run_command(&mut command, file_path, self.timeout, self.max_output_bytes)?;
+
+        Ok(String::from_utf8_lossy(&output).to_string())
+    }
+}
+
+fn kill(child: &mut Child, program: &str) {
+    child
+        .kill()
+        .and_then(|_| child.wait())
+        .map_err(|err| eprintln!("ERROR: could not kill {program}: {err}"))
+        .ok();
+}
+
+/// Run the command for the file and capture its standard output. The command is killed when it
+/// runs longer than `timeout` or writes more than `max_output_bytes`.
+pub fn run_command(
+    command: &mut Command,
+    file_path: &Path,
+    timeout: Duration,
+    max_output_bytes: usize,
+) -> Result<Vec<u8>, ()> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| {
+            eprintln!(
+                "ERROR: could not run {program} for {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+    // NOTE: one more byte than the limit is read, so the exceeded output could be detected
+    let stdout = child.stdout.take().ok_or(())?;
+    let limit = max_output_bytes as u64 + 1;
+    let output_thread = thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.take(limit).read_to_end(&mut output).map(|_| output)
+    });
+
+    let started = Instant::now();
+    while !output_thread.is_finished() {
+        if started.elapsed() > timeout {
+            kill(&mut child, &program);
+            eprintln!(
+                "ERROR: {program} timed out after {timeout:?} for {file_path}",
+                file_path = file_path.display()
+            );
+            return Err(());
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let output = output_thread
+        .join()
+        .map_err(|_| ())?
+        .map_err(|err| eprintln!("ERROR: could not read the output of {program}: {err}"))?;
+
+    if output.len() > max_output_bytes {
+        kill(&mut child, &program);
+        eprintln!(
+            "ERROR: the output of {program} for {file_path} exceeds {max_output_bytes} bytes",
+            file_path = file_path.display()
+        );
+        return Err(());
+    }
+
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(output),
+            Ok(Some(status)) => {
+                eprintln!(
+                    "ERROR: {program} failed for {file_path}: {status}",
+                    file_path = file_path.display()
+                );
+                return Err(());
+            }
+            Ok(None) if started.elapsed() > timeout => {
+                kill(&mut child, &program);
+                eprintln!(
+                    "ERROR: {program} timed out after {timeout:?} for {file_path}",
+                    file_path = file_path.display()
+                );
+                return Err(());
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(err) => {
+                eprintln!("ERROR: could not wait for {program}: {err}");
+                return Err(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommandReader;
+    use crate::{config::CommandConfig, reader::reader_trait::Reader};
+    use std::{path::Path, time::Duration};
+
+    #[test]
+    fn read_text_ok() -> Result<(), ()> {
+        // arrange
+        let reader = CommandReader {
+            program: "echo".to_string(),
+            args: vec!["Andy".to_string(), "is".to_string(), "{}".to_string()],
+            timeout: Duration::from_secs(5),
+            max_output_bytes: 1024,
+        };
+
+        // act
+        let actual = reader.read_text(Path::new("Andy."))?;
+
+        // assert
+        assert_eq!(actual, "Andy is Andy.\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_config_ok() -> Result<(), ()> {
+        // arrange
+        let config: CommandConfig = serde_json::from_str(
+            r#"{ "extensions": ["txt"], "command": ["printf", "%s|%s", "Andy  is", "{}"] }"#,
+        )
+        .map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // act
+        let reader = CommandReader::from_config(&config)?;
+        let actual = reader.read_text(Path::new("my notes.txt"))?;
+
+        // assert
+        assert_eq!(actual, "Andy  is|my notes.txt");
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_text_limits_ok() {
+        // arrange
+        let slow = CommandReader {
+            program: "sleep".to_string(),
+            args: vec!["5".to_string(), "{}".to_string()],
+            timeout: Duration::from_millis(100),
+            max_output_bytes: 1024,
+        };
+        let verbose = CommandReader {
+            program: "echo".to_string(),
+            args: vec!["Andy is Andy.".to_string()],
+            timeout: Duration::from_secs(5),
+            max_output_bytes: 4,
+        };
+
+        // act
+        let actual_slow = slow.read_text(Path::new("0"));
+        let actual_verbose = verbose.read_text(Path::new(""));
+
+        // assert
+        assert!(actual_slow.is_err());
+        assert!(actual_verbose.is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use super::{
+    plain_text_reader::decode_text,
+    reader_trait::{Document, Reader},
+};
+use encoding_rs::Encoding;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The position of the record in the file(starting from 0) and the cells of the row
+type Row = (usize, Vec<String>);
+
+/// Reader of the CSV and the TSV files. The first row is the header, and every other row is a
+/// document named like "data.csv#row=1" whose cells are the fields named by the header(e.g.
+/// "city:taipei").
+pub struct CsvReader {
+    /// ',' for CSV and '\t' for TSV
+    pub delimiter: char,
+    pub default_encoding: Option<&'static Encoding>,
+}
+
+impl Reader for CsvReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        Ok(self
+            .read_rows(file_path)?
+            .into_iter()
+            .map(|(_, row)| row.join(" "))
+            .collect::<Vec<String>>()
+            .join("\n"))
+    }
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let mut rows = self.read_rows(file_path)?.into_iter();
+        let (header_position, header) = rows.next().unwrap_or_default();
+
+        let mut documents = Vec::new();
+        for (position, row) in rows {
+            // NOTE: the rows are numbered by their records in the file, so the blank lines between
+            // them are counted too
+            let path = PathBuf::from(format!(
+                "{file_path}#row={row}",
+                file_path = file_path.display(),
+                row = position - header_position
+            ));
+            let mut document = Document::new(path, String::new());
+
+            // NOTE: the cells without a header are indexed as the plain text
+            let mut content = Vec::new();
+            for (j, cell) in row.into_iter().enumerate() {
+                if cell.trim().is_empty() {
+                    continue;
+                }
+
+                match header.get(j).map(|x| x.trim()) {
+                    Some(name) if !name.is_empty() => {
+                        document.fields.push((name.to_string(), cell))
+                    }
+                    _ => content.push(cell),
+                }
+            }
+            document.content = content.join(" ");
+            documents.push(document);
+        }
+
+        Ok(documents)
+    }
+}
+
+impl CsvReader {
+    fn read_rows(&self, file_path: &Path) -> Result<Vec<Row>, ()> {
+        let bytes = fs::read(file_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not open the file {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+        Ok(parse_rows(
+            &decode_text(&bytes, self.default_encoding),
+            self.delimiter,
+        ))
+    }
+}
+
+/// Split the text into the rows of cells with the positions of their records, the quoted cells
+/// could hold the delimiters, the line breaks and the escaped quotes(e.g. `"Andy ""A"" Lin"`). The
+/// empty lines are skipped, but their records are still counted in the positions.
+fn parse_rows(text: &str, delimiter: char) -> Vec<Row> {
+    let mut rows = Vec::new();
+    let mut position = 0;
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(x) = chars.next() {
+        if in_quotes {
+            match x {
+                '"' if chars.peek() == Some(&'"') => {
+                    cell.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => cell.push(x),
+            }
+            continue;
+        }
+
+        match x {
+            '"' if cell.is_empty() => in_quotes = true,
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut cell));
+                if row.iter().any(|x| !x.is_empty()) {
+                    rows.push((position, std::mem::take(&mut row)));
+                }
+                row.clear();
+                position += 1;
+            }
+            _ if x == delimiter => row.push(std::mem::take(&mut cell)),
+            _ => cell.push(x),
+        }
+    }
+
+    row.push(cell);
+    if row.iter().any(|x| !x.is_empty()) {
+        rows.push((position, row));
+    }
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_rows, CsvReader};
+    use crate::reader::reader_trait::Reader;
+    use std::{env, fs, path::PathBuf, process};
+
+    #[test]
+    fn read_documents_ok() -> Result<(), ()> {
+        // arrange
+        let file_path =
+            env::temp_dir().join(format!("loser-csv-test-{pid}.csv", pid = process::id()));
+        fs::write(&file_path, "\nname,city\nAndy,Taipei\n\nAmy,New York\n")
+            .map_err(|err| eprintln!("ERROR: {err}"))?;
+        let reader = CsvReader {
+            delimiter: ',',
+            default_encoding: None,
+        };
+
+        // act
+        let actual = reader.read_documents(&file_path);
+        fs::remove_file(&file_path).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let actual = actual?;
+
+        // assert
+        let row = |n: usize| PathBuf::from(format!("{}#row={n}", file_path.display()));
+        assert_eq!(actual.len(), 2);
+        assert_eq!(actual[0].path, row(1));
+        assert_eq!(actual[1].path, row(3));
+        assert_eq!(
+            actual[1].fields,
+            vec![
+                ("name".to_string(), "Amy".to_string()),
+                ("city".to_string(), "New York".to_string())
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rows_ok() {
+        // arrange
+        let inputs = [
+            (
+                "name,city\r\nAndy,Taipei\r\n\r\n\"Amy \"\"A\"\" Lin\",\"New\nYork, NY\"\r\n\r\n",
+                ',',
+                vec![
+                    (0, vec!["name", "city"]),
+                    (1, vec!["Andy", "Taipei"]),
+                    (3, vec!["Amy \"A\" Lin", "New\nYork, NY"]),
+                ],
+            ),
+            (
+                "name\tcity\nAndy\t",
+                '\t',
+                vec![(0, vec!["name", "city"]), (1, vec!["Andy", ""])],
+            ),
+        ];
+
+        for (text, delimiter, expected) in inputs {
+            // act
+            let rows = parse_rows(text, delimiter);
+            let actual = rows
+                .iter()
+                .map(|(position, row)| (*position, row.iter().map(|x| x.as_str()).collect()))
+                .collect::<Vec<(usize, Vec<&str>)>>();
+
+            // assert
+            assert_eq!(actual, expected);
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use super::{
+    html_reader::strip_html,
+    reader_trait::{Document, Reader},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use encoding_rs::{Encoding, UTF_8};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Reader of a single RFC 5322 message(.eml)
+pub struct EmailReader {}
+
+/// Reader of a mbox archive, every message in it is a document on its own
+pub struct MboxReader {}
+
+const INDEXED_HEADERS: [&str; 4] = ["subject", "from", "to", "date"];
+
+impl Reader for EmailReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        let raw = read_bytes(file_path)?;
+        let (headers, body) = split_message(&raw);
+        Ok(extract_body(&headers, &body))
+    }
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let raw = read_bytes(file_path)?;
+        Ok(vec![parse_message(file_path.to_path_buf(), &raw)])
+    }
+}
+
+impl Reader for MboxReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        Ok(self
+            .read_documents(file_path)?
+            .into_iter()
+            .map(|document| document.content)
+            .collect::<Vec<String>>()
+            .join("\n"))
+    }
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let raw = read_bytes(file_path)?;
+        let documents = split_mbox(&raw)
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                let path = PathBuf::from(format!(
+                    "{file_path}#{n}",
+                    file_path = file_path.display(),
+                    n = i + 1
+                ));
+                parse_message(path, message)
+            })
+            .collect();
+
+        Ok(documents)
+    }
+}
+
+fn read_bytes(file_path: &Path) -> Result<Vec<u8>, ()> {
+    fs::read(file_path).map_err(|err| {
+        eprintln!(
+            "ERROR: could not open the file {file_path}: {err}",
+            file_path = file_path.display()
+        )
+    })
+}
+
+fn parse_message(path: PathBuf, raw: &[u8]) -> Document {
+    let (headers, body) = split_message(raw);
+    let mut document = Document::new(path, extract_body(&headers, &body));
+    for name in INDEXED_HEADERS {
+        if let Some(value) = header(&headers, name) {
+            document
+                .fields
+                .push((name.to_string(), decode_header(value)));
+        }
+    }
+
+    document
+}
+
+/// Split the lines of the content, the trailing "\r" of every line is removed
+fn lines(raw: &[u8]) -> impl Iterator<Item = &[u8]> {
+    raw.split(|x| *x == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+}
+
+/// Split the message into the unfolded headers(with lowercase names) and the body
+fn split_message(raw: &[u8]) -> (Vec<(String, String)>, Vec<u8>) {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut body = Vec::new();
+    let mut in_body = false;
+
+    for line in lines(raw) {
+        if in_body {
+            body.extend_from_slice(line);
+            body.push(b'\n');
+            continue;
+        }
+
+        if line.is_empty() {
+            in_body = true;
+            continue;
+        }
+
+        let line = String::from_utf8_lossy(line);
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    (headers, body)
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Parse the header like `text/plain; charset="utf-8"` into the lowercase value and its parameters
+fn parse_header_params(value: &str) -> (String, HashMap<String, String>) {
+    let mut parts = value.split(';');
+    let value = parts.next().unwrap_or("").trim().to_lowercase();
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| {
+            (
+                key.trim().to_lowercase(),
+                value.trim().trim_matches('"').to_string(),
+            )
+        })
+        .collect();
+
+    (value, params)
+}
+
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    let encoding = Encoding::for_label(charset.as_bytes()).unwrap_or(UTF_8);
+    encoding.decode(bytes).0.replace("\r\n", "\n")
+}
+
+fn decode_transfer_encoding(body: &[u8], encoding: &str) -> Vec<u8> {
+    match encoding {
+        "base64" => {
+            let data: Vec<u8> = body
+                .iter()
+                .filter(|x| !x.is_ascii_whitespace())
+                .cloned()
+                .collect();
+            STANDARD.decode(data).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not decode the base64 part of the message: {err}");
+                Vec::new()
+            })
+        }
+        "quoted-printable" => quoted_printable::decode(body, quoted_printable::ParseMode::Robust)
+            .unwrap_or_else(|_| body.to_vec()),
+        _ => body.to_vec(),
+    }
+}
+
+/// Decode the RFC 2047 encoded words(e.g. `=?UTF-8?B?SGVsbG8=?=`) in the header
+fn decode_header(value: &str) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    let mut after_encoded_word = false;
+
+    while let Some(start) = rest.find("=?") {
+        let decoded = rest[start + 2..].splitn(3, '?').collect::<Vec<&str>>();
+        let word = match decoded.as_slice() {
+            [charset, encoding, text] => text.find("?=").map(|end| {
+                let bytes = match encoding.to_ascii_uppercase().as_str() {
+                    "B" => STANDARD.decode(&text[..end]).unwrap_or_default(),
+                    _ => quoted_printable::decode(
+                        text[..end].replace('_', " "),
+                        quoted_printable::ParseMode::Robust,
+                    )
+                    .unwrap_or_default(),
+                };
+                let length = charset.len() + encoding.len() + end + 6;
+                (decode_charset(&bytes, charset), length)
+            }),
+            _ => None,
+        };
+
+        match word {
+            Some((word, length)) => {
+                // NOTE: the whitespaces between two adjacent encoded words are ignored
+                let prefix = &rest[..start];
+                if !(after_encoded_word && prefix.trim().is_empty()) {
+                    result.push_str(prefix);
+                }
+                result.push_str(&word);
+                rest = &rest[start + length..];
+                after_encoded_word = true;
+            }
+            None => {
+                result.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                after_encoded_word = false;
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Extract the readable text from the body of the message or a MIME part
+fn extract_body(headers: &[(String, String)], body: &[u8]) -> String {
+    let (mime, params) =
+        parse_header_params(header(headers, "content-type").unwrap_or("text/plain"));
+    let (disposition, _) =
+        parse_header_params(header(headers, "content-disposition").unwrap_or(""));
+    let (encoding, _) =
+        parse_header_params(header(headers, "content-transfer-encoding").unwrap_or(""));
+
+    if let Some(subtype) = mime.strip_prefix("multipart/") {
+        let boundary = match params.get("boundary") {
+            Some(boundary) => boundary,
+            None => return String::from_utf8_lossy(body).to_string(),
+        };
+
+        let parts = split_multipart(body, boundary)
+            .iter()
+            .map(|part| split_message(part))
+            .collect::<Vec<(Vec<(String, String)>, Vec<u8>)>>();
+
+        // NOTE: the parts of multipart/alternative are the same content in different formats, so
+        // the plain text one is preferred.
+        if subtype == "alternative" {
+            let part = parts
+                .iter()
+                .find(|(headers, _)| {
+                    header(headers, "content-type")
+                        .is_none_or(|x| x.to_lowercase().starts_with("text/plain"))
+                })
+                .or_else(|| parts.first());
+            return part
+                .map(|(headers, body)| extract_body(headers, body))
+                .unwrap_or_default();
+        }
+
+        return parts
+            .iter()
+            .map(|(headers, body)| extract_body(headers, body).trim().to_string())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<String>>()
+            .join("\n");
+    }
+
+    if disposition == "attachment" && !mime.starts_with("text/") {
+        return String::new();
+    }
+
+    let decoded = decode_transfer_encoding(body, &encoding);
+    let charset = params.get("charset").map_or("utf-8", |x| x.as_str());
+
+    match mime.as_str() {
+        "message/rfc822" => {
+            let (headers, body) = split_message(&decoded);
+            extract_body(&headers, &body)
+        }
+        "text/html" => strip_html(&decode_charset(&decoded, charset)),
+        mime if mime.starts_with("text/") => decode_charset(&decoded, charset),
+        _ => String::new(),
+    }
+}
+
+fn split_multipart(body: &[u8], boundary: &str) -> Vec<Vec<u8>> {
+    let delimiter = format!("--{boundary}");
+    let mut parts = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+
+    for line in lines(body) {
+        if line.starts_with(delimiter.as_bytes()) {
+            if let Some(part) = current.take() {
+                parts.push(part);
+            }
+            if line[delimiter.len()..].starts_with(b"--") {
+                break;
+            }
+            current = Some(Vec::new());
+        } else if let Some(part) = current.as_mut() {
+            part.extend_from_slice(line);
+            part.push(b'\n');
+        }
+    }
+
+    if let Some(part) = current {
+        parts.push(part);
+    }
+
+    parts
+}
+
+/// Split the mbox archive into messages, every message starts with a "From " line
+fn split_mbox(raw: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    let mut previous_empty = true;
+
+    for line in lines(raw) {
+        if previous_empty && line.starts_with(b"From ") {
+            if let Some(message) = current.take() {
+                messages.push(message);
+            }
+            current = Some(Vec::new());
+            previous_empty = false;
+            continue;
+        }
+
+        previous_empty = line.is_empty();
+        if let Some(message) = current.as_mut() {
+            // NOTE: the lines starting with "From " in the message are escaped as ">From "
+            let unescaped = match line.iter().position(|x| *x != b'>') {
+                Some(n) if n > 0 && line[n..].starts_with(b"From ") => &line[1..],
+                _ => line,
+            };
+            message.extend_from_slice(unescaped);
+            message.push(b'\n');
+        }
+    }
+
+    if let Some(message) = current {
+        messages.push(message);
+    }
+
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_header, parse_message, split_mbox};
+    use std::path::PathBuf;
+
+    #[test]
+    fn decode_header_ok() {
+        // arrange
+        let input = "=?UTF-8?B?SGVsbG8=?= =?ISO-8859-1?Q?W=F6rld?= and Andy";
+        let expected = "HelloWörld and Andy";
+
+        // act
+        let actual = decode_header(input);
+
+        // assert
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_message_ok() {
+        // arrange
+        let raw = concat!(
+            "From: Andy <andy@example.com>\r\n",
+            "To: Amy <amy@example.com>\r\n",
+            "Subject: =?UTF-8?Q?Caf=C3=A9?=\r\n",
+            " menu\r\n",
+            "Date: Mon, 6 Feb 2023 10:00:00 +0800\r\n",
+            "Content-Type: multipart/mixed; boundary=\"XYZ\"\r\n",
+            "\r\n",
+            "--XYZ\r\n",
+            "Content-Type: text/plain; charset=iso-8859-1\r\n",
+            "Content-Transfer-Encoding: quoted-printable\r\n",
+            "\r\n",
+            "Caf=E9 au lait=\r\n",
+            " is good.\r\n",
+            "--XYZ\r\n",
+            "Content-Type: text/html; charset=utf-8\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "PHA+QW15PC9wPg==\r\n",
+            "--XYZ\r\n",
+            "Content-Type: application/octet-stream\r\n",
+            "Content-Disposition: attachment; filename=\"a.bin\"\r\n",
+            "\r\n",
+            "AAAA\r\n",
+            "--XYZ--\r\n",
+        );
+
+        // act
+        let actual = parse_message(PathBuf::from("test.eml"), raw.as_bytes());
+
+        // assert
+        assert_eq!(actual.content, "Café au lait is good.\nAmy");
+        assert_eq!(
+            actual.fields,
+            vec![
+                ("subject".to_string(), "Café menu".to_string()),
+                ("from".to_string(), "Andy <andy@example.com>".to_string()),
+                ("to".to_string(), "Amy <amy@example.com>".to_string()),
+                (
+                    "date".to_string(),
+                    "Mon, 6 Feb 2023 10:00:00 +0800".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_mbox_ok() {
+        // arrange
+        let raw = concat!(
+            "From andy@example.com Mon Feb  6 10:00:00 2023\n",
+            "Subject: first\n",
+            "\n",
+            ">From the beginning\n",
+            "\n",
+            "From amy@example.com Mon Feb  6 11:00:00 2023\n",
+            "Subject: second\n",
+            "\n",
+            "Hi\n",
+        );
+
+        // act
+        let actual = split_mbox(raw.as_bytes());
+
+        // assert
+        assert_eq!(actual.len(), 2);
+        assert_eq!(
+            String::from_utf8_lossy(&actual[0]),
+            "Subject: first\n\nFrom the beginning\n\n"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&actual[1]),
+            "Subject: second\n\nHi\n\n"
+        );
+    }
+}
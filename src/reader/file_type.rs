@@ -0,0 +1,204 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use std::{fs, io::Read, path::Path};
+
+/// The number of bytes at the beginning of a file used to sniff its type
+pub const SNIFF_LENGTH: usize = 8192;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FileType {
+    PlainText,
+    Html,
+    Pdf,
+    Email,
+    Mbox,
+    Zip,
+    Tar,
+    Gzip,
+    Binary,
+}
+
+impl FileType {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            FileType::PlainText => "text/plain",
+            FileType::Html => "text/html",
+            FileType::Pdf => "application/pdf",
+            FileType::Email => "message/rfc822",
+            FileType::Mbox => "application/mbox",
+            FileType::Zip => "application/zip",
+            FileType::Tar => "application/x-tar",
+            FileType::Gzip => "application/gzip",
+            FileType::Binary => "application/octet-stream",
+        }
+    }
+
+    /// Guess the type by the magic bytes, or by whether the content looks like text
+    pub fn sniff(head: &[u8]) -> Self {
+        if head.starts_with(b"%PDF-") {
+            return FileType::Pdf;
+        }
+
+        if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+            return FileType::Zip;
+        }
+
+        if head.starts_with(b"\x1f\x8b") {
+            return FileType::Gzip;
+        }
+
+        if is_tar(head) {
+            return FileType::Tar;
+        }
+
+        if !is_text(head) {
+            return FileType::Binary;
+        }
+
+        if head.starts_with(b"From ") {
+            return FileType::Mbox;
+        }
+
+        let first_line = head.split(|x| *x == b'\n').next().unwrap_or_default();
+        let email_headers: [&[u8]; 6] = [
+            b"Return-Path:",
+            b"Received:",
+            b"Message-ID:",
+            b"MIME-Version:",
+            b"Delivered-To:",
+            b"X-",
+        ];
+        if email_headers
+            .iter()
+            .any(|header| first_line.starts_with(header))
+        {
+            return FileType::Email;
+        }
+
+        let start = head
+            .iter()
+            .position(|x| !x.is_ascii_whitespace())
+            .unwrap_or(head.len());
+        let start = head[start..]
+            .iter()
+            .take(14)
+            .map(|x| x.to_ascii_lowercase())
+            .collect::<Vec<u8>>();
+        if start.starts_with(b"<!doctype html") || start.starts_with(b"<html") {
+            return FileType::Html;
+        }
+
+        FileType::PlainText
+    }
+
+    /// Sniff the type of the file by the bytes at the beginning of it
+    pub fn sniff_file(file_path: &Path) -> Result<Self, ()> {
+        let file = fs::File::open(file_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not open the file {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+        let mut head = Vec::with_capacity(SNIFF_LENGTH);
+        file.take(SNIFF_LENGTH as u64)
+            .read_to_end(&mut head)
+            .map_err(|err| {
+                eprintln!(
+                    "ERROR: could not read the file {file_path}: {err}",
+                    file_path = file_path.display()
+                )
+            })?;
+
+        Ok(Self::sniff(&head))
+    }
+}
+
+pub fn is_tar(head: &[u8]) -> bool {
+    head.len() > 262 && &head[257..262] == b"ustar"
+}
+
+/// The encoding of the UTF-16 text without a byte order mark, by the NUL bytes of the ASCII
+/// characters in it(e.g. "A\0m\0y\0" is UTF-16LE). The text of other scripts is not detected.
+pub fn utf16_without_bom(head: &[u8]) -> Option<&'static Encoding> {
+    let pairs = head.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+
+    let even_zeros = head
+        .iter()
+        .step_by(2)
+        .take(pairs)
+        .filter(|x| **x == 0)
+        .count();
+    let odd_zeros = head.iter().skip(1).step_by(2).filter(|x| **x == 0).count();
+    if odd_zeros * 2 >= pairs && even_zeros * 10 < pairs {
+        return Some(UTF_16LE);
+    }
+    if even_zeros * 2 >= pairs && odd_zeros * 10 < pairs {
+        return Some(UTF_16BE);
+    }
+
+    None
+}
+
+/// Whether the content looks like text: files with byte order marks are text, and other files
+/// should contain neither NUL nor too many control characters.
+fn is_text(head: &[u8]) -> bool {
+    if head.starts_with(b"\xef\xbb\xbf")
+        || head.starts_with(b"\xff\xfe")
+        || head.starts_with(b"\xfe\xff")
+    {
+        return true;
+    }
+
+    if utf16_without_bom(head).is_some() {
+        return true;
+    }
+
+    if head.contains(&0) {
+        return false;
+    }
+
+    let controls = head
+        .iter()
+        .filter(|x| x.is_ascii_control() && !matches!(x, b'\t' | b'\n' | b'\r' | b'\x0c' | b'\x1b'))
+        .count();
+
+    controls * 10 <= head.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileType;
+
+    #[test]
+    fn sniff_ok() {
+        // arrange
+        let mut tar = vec![0_u8; 512];
+        tar[..5].copy_from_slice(b"a.txt");
+        tar[257..262].copy_from_slice(b"ustar");
+
+        let inputs: [(&[u8], FileType); 11] = [
+            (b"%PDF-1.7\n", FileType::Pdf),
+            (b"PK\x03\x04\x14\x00", FileType::Zip),
+            (b"\x1f\x8b\x08\x00", FileType::Gzip),
+            (&tar, FileType::Tar),
+            (b"\x7fELF\x02\x01\x01\x00\x00", FileType::Binary),
+            (b"From andy@example.com Mon Feb  6\n", FileType::Mbox),
+            (b"Return-Path: <andy@example.com>\n", FileType::Email),
+            (b"\n<!DOCTYPE html>\n<html>", FileType::Html),
+            (b"all:\n\tcargo build\n", FileType::PlainText),
+            (b"\xff\xfeA\x00n\x00d\x00y\x00", FileType::PlainText),
+            (b"A\x00n\x00d\x00y\x00\n\x00", FileType::PlainText),
+        ];
+
+        for (input, expected) in inputs {
+            // act
+            let actual = FileType::sniff(input);
+
+            // assert
+            assert_eq!(expected, actual);
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use super::{
+    plain_text_reader::decode_text,
+    reader_trait::{Document, Reader},
+};
+use encoding_rs::Encoding;
+use std::{fs, path::Path};
+
+/// The names of the meta tags(e.g. `<meta name="author" content="Andy">`) kept as the metadata
+const META_NAMES: [&str; 3] = ["description", "keywords", "author"];
+
+/// Read the text of the HTML page, and the title and the meta tags as the metadata
+pub struct HtmlReader {
+    pub default_encoding: Option<&'static Encoding>,
+}
+
+impl Reader for HtmlReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        Ok(parse_html(&self.read_html(file_path)?).0)
+    }
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let (content, metadata) = parse_html(&self.read_html(file_path)?);
+        let mut document = Document::new(file_path.to_path_buf(), content);
+        document.metadata = metadata;
+
+        Ok(vec![document])
+    }
+}
+
+impl HtmlReader {
+    fn read_html(&self, file_path: &Path) -> Result<String, ()> {
+        let bytes = fs::read(file_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not open the file {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+        Ok(decode_text(&bytes, self.default_encoding))
+    }
+}
+
+/// Remove the tags, the scripts and the styles from the HTML, and keep the text only
+pub fn strip_html(html: &str) -> String {
+    parse_html(html).0
+}
+
+/// Split the HTML into the text and the metadata
+fn parse_html(html: &str) -> (String, Vec<(String, String)>) {
+    let mut text = String::new();
+    let mut metadata = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map(|end| &rest[end + 3..]).unwrap_or("");
+            continue;
+        }
+
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => {
+                rest = "";
+                break;
+            }
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let name = tag
+            .split(|x: char| x.is_whitespace() || x == '/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        match name.as_str() {
+            // NOTE: the scripts and the styles are not the text of the page
+            "script" | "style" => {
+                let end = rest
+                    .to_ascii_lowercase()
+                    .find(&format!("</{name}"))
+                    .unwrap_or(rest.len());
+                rest = &rest[end..];
+            }
+            "title" => {
+                let end = rest
+                    .to_ascii_lowercase()
+                    .find("</title")
+                    .unwrap_or(rest.len());
+                let title = decode_entities(rest[..end].trim());
+                if !title.is_empty() {
+                    metadata.push(("title".to_string(), title));
+                }
+            }
+            "meta" => {
+                let meta_name = attribute(tag, "name").map(|x| x.to_lowercase());
+                if let (Some(meta_name), Some(content)) = (meta_name, attribute(tag, "content")) {
+                    if META_NAMES.contains(&meta_name.as_str()) {
+                        metadata.push((meta_name, decode_entities(&content)));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        text.push(' ');
+    }
+    text.push_str(rest);
+
+    (decode_entities(&text), metadata)
+}
+
+/// Find the value of the attribute in the tag(e.g. `meta name="author"`)
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let chars = tag.chars().collect::<Vec<char>>();
+    let mut i = 0;
+
+    // NOTE: skip the name of the tag
+    while i < chars.len() && !chars[i].is_whitespace() {
+        i += 1;
+    }
+
+    while i < chars.len() {
+        while i < chars.len() && (chars[i].is_whitespace() || chars[i] == '/') {
+            i += 1;
+        }
+
+        let start = i;
+        while i < chars.len() && !chars[i].is_whitespace() && !matches!(chars[i], '=' | '/') {
+            i += 1;
+        }
+        let key = chars[start..i].iter().collect::<String>();
+
+        while i < chars.len() && chars[i].is_whitespace() {
+            i += 1;
+        }
+
+        let mut value = String::new();
+        if i < chars.len() && chars[i] == '=' {
+            i += 1;
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+
+            if i < chars.len() && matches!(chars[i], '"' | '\'') {
+                let quote = chars[i];
+                i += 1;
+                let start = i;
+                while i < chars.len() && chars[i] != quote {
+                    i += 1;
+                }
+                value = chars[start..i].iter().collect();
+                i += 1;
+            } else {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() {
+                    i += 1;
+                }
+                value = chars[start..i].iter().collect();
+            }
+        }
+
+        if key.eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+/// Decode the character references(e.g. "&amp;" and "&#233;")
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| decode_entity(&rest[1..end]).map(|x| (end, x)));
+        match entity {
+            Some((end, x)) => {
+                decoded.push(x);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(number) = entity.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+
+    match entity {
+        "nbsp" => Some(' '),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "amp" => Some('&'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_html;
+
+    #[test]
+    fn parse_html_ok() {
+        // arrange
+        let html = r#"<!DOCTYPE html>
+<html>
+<head>
+  <title>Andy &amp; Amy</title>
+  <meta charset="utf-8">
+  <meta name="Author" content='Andy'>
+  <meta name=keywords content="rust, search">
+  <style>body { color: red; }</style>
+</head>
+<body><!-- draft --><p>Caf&#233; is <b>open</b>.</p><script>alert("Andy")</script></body>
+</html>"#;
+
+        // act
+        let (text, metadata) = parse_html(html);
+
+        // assert
+        assert_eq!(
+            text.split_whitespace().collect::<Vec<&str>>(),
+            ["Andy", "&", "Amy", "Café", "is", "open", "."]
+        );
+        assert_eq!(
+            metadata,
+            vec![
+                ("title".to_string(), "Andy & Amy".to_string()),
+                ("author".to_string(), "Andy".to_string()),
+                ("keywords".to_string(), "rust, search".to_string()),
+            ]
+        );
+    }
+}
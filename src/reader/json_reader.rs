@@ -0,0 +1,84 @@
+use super::reader_trait::Reader;
+use serde_json::Value;
+use std::{fs, path::Path};
+
+/// Reader of the JSON files, every string value is indexed in a line with its key path as the
+/// context(e.g. "authors[0].name: Andy").
+pub struct JsonReader {}
+
+impl Reader for JsonReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        let bytes = fs::read(file_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not open the file {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+        let value: Value = serde_json::from_slice(&bytes).map_err(|err| {
+            eprintln!(
+                "ERROR: could not parse the JSON file {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+        let mut lines = Vec::new();
+        collect_strings(&value, "", &mut lines);
+
+        Ok(lines.join("\n"))
+    }
+}
+
+fn collect_strings(value: &Value, key_path: &str, lines: &mut Vec<String>) {
+    match value {
+        Value::String(text) if key_path.is_empty() => lines.push(text.to_string()),
+        Value::String(text) => lines.push(format!("{key_path}: {text}")),
+        Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                collect_strings(value, &format!("{key_path}[{i}]"), lines);
+            }
+        }
+        Value::Object(values) => {
+            for (key, value) in values {
+                let key_path = if key_path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{key_path}.{key}")
+                };
+                collect_strings(value, &key_path, lines);
+            }
+        }
+        // NOTE: the numbers, the booleans and the nulls are not the text to search
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::collect_strings;
+    use serde_json::Value;
+
+    #[test]
+    fn collect_strings_ok() -> Result<(), ()> {
+        // arrange
+        let value: Value = serde_json::from_str(
+            r#"{"title": "Notes", "authors": [{"name": "Andy", "age": 30}, {"name": "Amy"}], "draft": true}"#,
+        )
+        .map_err(|err| eprintln!("ERROR: {err}"))?;
+        let mut actual = Vec::new();
+
+        // act
+        collect_strings(&value, "", &mut actual);
+
+        // assert
+        assert_eq!(
+            actual,
+            vec![
+                "authors[0].name: Andy",
+                "authors[1].name: Amy",
+                "title: Notes",
+            ]
+        );
+
+        Ok(())
+    }
+}
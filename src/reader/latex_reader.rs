@@ -0,0 +1,299 @@
+use super::{
+    plain_text_reader::decode_text,
+    reader_trait::{Document, Reader},
+};
+use encoding_rs::Encoding;
+use std::{fs, path::Path};
+
+/// The sectioning commands, their titles are the "section" fields
+const SECTION_COMMANDS: [&str; 6] = [
+    "part",
+    "chapter",
+    "section",
+    "subsection",
+    "subsubsection",
+    "paragraph",
+];
+
+/// The commands referring to the citations(the "cite" fields) and the labels(the "ref" fields)
+const CITE_COMMANDS: [&str; 7] = [
+    "cite",
+    "citep",
+    "citet",
+    "citealp",
+    "citeauthor",
+    "citeyear",
+    "nocite",
+];
+const REF_COMMANDS: [&str; 6] = ["ref", "eqref", "autoref", "cref", "Cref", "pageref"];
+
+/// The commands whose arguments are not text(e.g. `\usepackage{amsmath}`), they are removed with
+/// the arguments
+const SKIPPED_COMMANDS: [&str; 16] = [
+    "begin",
+    "end",
+    "label",
+    "documentclass",
+    "usepackage",
+    "includegraphics",
+    "bibliography",
+    "bibliographystyle",
+    "newcommand",
+    "renewcommand",
+    "newenvironment",
+    "setlength",
+    "vspace",
+    "hspace",
+    "input",
+    "include",
+];
+
+/// Reader of the LaTeX files. The commands are removed and the text in their arguments is kept,
+/// the section titles are the "section" fields, and the keys of `\cite` and `\ref` are the "cite"
+/// and the "ref" fields. `\title` and `\author` are the metadata, and they're kept in the text.
+pub struct LatexReader {
+    pub default_encoding: Option<&'static Encoding>,
+}
+
+impl Reader for LatexReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        Ok(parse_latex(&self.read_source(file_path)?).content)
+    }
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let latex = parse_latex(&self.read_source(file_path)?);
+        let mut document = Document::new(file_path.to_path_buf(), latex.content);
+        document.fields = latex.fields;
+        document.metadata = latex.metadata;
+
+        Ok(vec![document])
+    }
+}
+
+impl LatexReader {
+    fn read_source(&self, file_path: &Path) -> Result<String, ()> {
+        let bytes = fs::read(file_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not open the file {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+        Ok(decode_text(&bytes, self.default_encoding))
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Latex {
+    content: String,
+    fields: Vec<(String, String)>,
+    metadata: Vec<(String, String)>,
+}
+
+fn parse_latex(source: &str) -> Latex {
+    let chars = remove_comments(source).chars().collect::<Vec<char>>();
+    let mut latex = Latex::default();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if chars.get(i + 1).is_some_and(|x| x.is_ascii_alphabetic()) => {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                let name = chars[start..i].iter().collect::<String>();
+                if chars.get(i) == Some(&'*') {
+                    i += 1;
+                }
+
+                i = parse_command(&name, &chars, i, &mut latex);
+            }
+            '\\' => {
+                // NOTE: "\\" breaks the line, and the others are the escaped characters(e.g. "\%")
+                match chars.get(i + 1) {
+                    Some('\\') => latex.content.push('\n'),
+                    Some(x) => latex.content.push(*x),
+                    None => {}
+                }
+                i += 2;
+            }
+            '{' | '}' | '$' => i += 1,
+            '~' => {
+                latex.content.push(' ');
+                i += 1;
+            }
+            x => {
+                latex.content.push(x);
+                i += 1;
+            }
+        }
+    }
+
+    latex
+}
+
+/// Handle the command whose arguments start at `i`, and return where the command ends
+fn parse_command(name: &str, chars: &[char], mut i: usize, latex: &mut Latex) -> usize {
+    if SKIPPED_COMMANDS.contains(&name) {
+        while let Some((_, end)) =
+            read_group(chars, i, '[', ']').or_else(|| read_group(chars, i, '{', '}'))
+        {
+            i = end;
+        }
+        return i;
+    }
+
+    let is_section = SECTION_COMMANDS.contains(&name);
+    let is_cite = CITE_COMMANDS.contains(&name);
+    let is_ref = REF_COMMANDS.contains(&name);
+    let is_metadata = name == "title" || name == "author";
+    if !is_section && !is_cite && !is_ref && !is_metadata {
+        // NOTE: the arguments of the other commands(e.g. `\emph{text}`) are the text
+        return i;
+    }
+
+    while let Some((_, end)) = read_group(chars, i, '[', ']') {
+        i = end;
+    }
+    let (argument, end) = match read_group(chars, i, '{', '}') {
+        Some(group) => group,
+        None => return i,
+    };
+
+    if is_cite || is_ref {
+        let keys = argument
+            .split(',')
+            .map(|key| key.trim())
+            .filter(|key| !key.is_empty())
+            .collect::<Vec<&str>>();
+        let field = if is_cite { "cite" } else { "ref" };
+        for key in keys {
+            latex.fields.push((field.to_string(), key.to_string()));
+        }
+        // NOTE: the fields are searchable by their plain terms too, so the keys and the section
+        // titles are not repeated in the text
+        latex.content.push(' ');
+        return end;
+    }
+
+    let text = parse_latex(&argument);
+    let text = text
+        .content
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if is_section {
+        latex.fields.push(("section".to_string(), text));
+        latex.content.push('\n');
+    } else {
+        latex.content.push_str(&format!("\n{text}\n"));
+        latex.metadata.push((name.to_string(), text));
+    }
+
+    end
+}
+
+/// Read the group like "{...}" starting at `i`, the nested groups are kept in it
+fn read_group(chars: &[char], i: usize, open: char, close: char) -> Option<(String, usize)> {
+    if chars.get(i) != Some(&open) {
+        return None;
+    }
+
+    let mut depth = 0;
+    for (j, x) in chars.iter().enumerate().skip(i) {
+        if *x == open {
+            depth += 1;
+        } else if *x == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some((chars[i + 1..j].iter().collect(), j + 1));
+            }
+        }
+    }
+
+    None
+}
+
+/// Remove the comments from "%" to the end of the line, "\%" is not a comment
+fn remove_comments(source: &str) -> String {
+    let mut lines = Vec::new();
+    for line in source.lines() {
+        let mut escaped = false;
+        let mut end = line.len();
+        for (i, x) in line.char_indices() {
+            match x {
+                '%' if !escaped => {
+                    end = i;
+                    break;
+                }
+                '\\' => escaped = !escaped,
+                _ => escaped = false,
+            }
+        }
+        lines.push(&line[..end]);
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_latex;
+
+    #[test]
+    fn parse_latex_ok() {
+        // arrange
+        let source = r"\documentclass[a4paper]{article}
+\usepackage{amsmath}
+\title{Searching \emph{Local} Files}
+\author{Andy}
+\begin{document}
+\section*{Introduction} % TODO: rewrite
+Andy is Andy, 100\% sure~\cite{knuth1984, lamport94}.
+\subsection{Ranking}\label{sec:rank}
+See Section~\ref{sec:rank}.
+\end{document}";
+
+        // act
+        let actual = parse_latex(source);
+
+        // assert
+        assert_eq!(
+            actual.content.split_whitespace().collect::<Vec<&str>>(),
+            [
+                "Searching",
+                "Local",
+                "Files",
+                "Andy",
+                "Andy",
+                "is",
+                "Andy,",
+                "100%",
+                "sure",
+                ".",
+                "See",
+                "Section",
+                "."
+            ]
+        );
+        assert_eq!(
+            actual.fields,
+            vec![
+                ("section".to_string(), "Introduction".to_string()),
+                ("cite".to_string(), "knuth1984".to_string()),
+                ("cite".to_string(), "lamport94".to_string()),
+                ("section".to_string(), "Ranking".to_string()),
+                ("ref".to_string(), "sec:rank".to_string()),
+            ]
+        );
+        assert_eq!(
+            actual.metadata,
+            vec![
+                ("title".to_string(), "Searching Local Files".to_string()),
+                ("author".to_string(), "Andy".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The metadata of the file from the filesystem: the size in bytes, and the creation time when the
+/// filesystem records it
+pub fn file_metadata(metadata: &fs::Metadata) -> Vec<(String, String)> {
+    let mut fields = vec![("size".to_string(), metadata.len().to_string())];
+    if let Ok(created) = metadata.created() {
+        fields.push(("created".to_string(), format_time(created)));
+    }
+
+    fields
+}
+
+/// Format the time in UTC like "2023-02-06T09:30:00Z"
+pub fn format_time(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    };
+    let days = secs.div_euclid(86400);
+    let secs_of_day = secs.rem_euclid(86400);
+
+    // NOTE: the civil date from the days since 1970-01-01, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z",
+        hour = secs_of_day / 3600,
+        minute = secs_of_day % 3600 / 60,
+        second = secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_time;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn format_time_ok() {
+        // arrange
+        let inputs = [
+            (0, "1970-01-01T00:00:00Z"),
+            (951782400, "2000-02-29T00:00:00Z"),
+            (1675675800, "2023-02-06T09:30:00Z"),
+        ];
+
+        for (secs, expected) in inputs {
+            // act
+            let actual = format_time(UNIX_EPOCH + Duration::from_secs(secs));
+
+            // assert
+            assert_eq!(expected, actual);
+        }
+    }
+}
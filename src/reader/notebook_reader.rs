@@ -0,0 +1,122 @@
+use super::reader_trait::Reader;
+use serde_json::Value;
+use std::{fs, path::Path};
+
+/// Reader of the Jupyter notebooks, the markdown and the code cells are indexed, and so are the
+/// outputs of the code cells when `outputs` is set.
+pub struct NotebookReader {
+    pub outputs: bool,
+}
+
+impl Reader for NotebookReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        let bytes = fs::read(file_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not open the file {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+        let notebook: Value = serde_json::from_slice(&bytes).map_err(|err| {
+            eprintln!(
+                "ERROR: could not parse the notebook {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+        notebook_text(&notebook, self.outputs).ok_or_else(|| {
+            eprintln!(
+                "ERROR: no cells are found in the notebook {file_path}",
+                file_path = file_path.display()
+            )
+        })
+    }
+}
+
+fn notebook_text(notebook: &Value, outputs: bool) -> Option<String> {
+    let mut text = Vec::new();
+    for cell in notebook.get("cells")?.as_array()? {
+        match cell.get("cell_type").and_then(|x| x.as_str()) {
+            Some("markdown") | Some("raw") => text.push(multiline_text(&cell["source"])),
+            Some("code") => {
+                text.push(multiline_text(&cell["source"]));
+                if !outputs {
+                    continue;
+                }
+
+                // NOTE: the streams(e.g. printed by `print`) are in "text", and the results of the
+                // expressions are in "data"
+                for output in cell["outputs"].as_array().into_iter().flatten() {
+                    let output = match output.get("text") {
+                        Some(output) => output,
+                        None => &output["data"]["text/plain"],
+                    };
+                    text.push(multiline_text(output));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(
+        text.into_iter()
+            .filter(|x| !x.is_empty())
+            .collect::<Vec<String>>()
+            .join("\n"),
+    )
+}
+
+/// The multiline strings of the notebook are either a string or a list of lines
+fn multiline_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.to_string(),
+        Value::Array(lines) => lines
+            .iter()
+            .filter_map(|line| line.as_str())
+            .collect::<String>(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::notebook_text;
+    use serde_json::Value;
+
+    #[test]
+    fn notebook_text_ok() -> Result<(), ()> {
+        // arrange
+        let notebook: Value = serde_json::from_str(
+            r##"{
+                "cells": [
+                    {"cell_type": "markdown", "source": ["# Andy\n", "Andy is Andy."]},
+                    {
+                        "cell_type": "code",
+                        "source": "print('Amy')",
+                        "outputs": [
+                            {"output_type": "stream", "name": "stdout", "text": ["Amy\n"]},
+                            {"output_type": "execute_result", "data": {"text/plain": "42"}}
+                        ]
+                    }
+                ],
+                "nbformat": 4
+            }"##,
+        )
+        .map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // act
+        let without_outputs = notebook_text(&notebook, false);
+        let with_outputs = notebook_text(&notebook, true);
+
+        // assert
+        assert_eq!(
+            without_outputs.as_deref(),
+            Some("# Andy\nAndy is Andy.\nprint('Amy')")
+        );
+        assert_eq!(
+            with_outputs.as_deref(),
+            Some("# Andy\nAndy is Andy.\nprint('Amy')\nAmy\n\n42")
+        );
+
+        Ok(())
+    }
+}
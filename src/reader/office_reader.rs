@@ -0,0 +1,245 @@
+use super::{
+    html_reader::decode_entities,
+    reader_trait::{Document, Reader},
+};
+use std::{
+    fs,
+    io::{Read, Seek},
+    path::Path,
+};
+use zip::ZipArchive;
+
+/// The tags of the paragraphs, a line break is put at the end of them
+const PARAGRAPH_TAGS: [&str; 6] = ["w:p", "a:p", "si", "text:p", "text:h", "table:table-row"];
+
+/// The tags of the tabs, the line breaks and the spaces, they are replaced with a space
+const SPACE_TAGS: [&str; 7] = [
+    "w:tab",
+    "w:br",
+    "a:br",
+    "text:s",
+    "text:tab",
+    "text:line-break",
+    "table:table-cell",
+];
+
+/// The elements of the core properties(e.g. "docProps/core.xml" of a docx file, or "meta.xml" of
+/// an odt file) and the names of the metadata
+const PROPERTIES: [(&str, &str); 7] = [
+    ("dc:title", "title"),
+    ("dc:subject", "subject"),
+    ("dc:creator", "author"),
+    ("meta:initial-creator", "author"),
+    ("cp:keywords", "keywords"),
+    ("meta:keyword", "keywords"),
+    ("dc:description", "description"),
+];
+
+/// Reader of the Office Open XML(docx, xlsx and pptx) and the OpenDocument(odt, ods and odp) files,
+/// the text is read from the XML parts and the metadata from the core properties.
+pub struct OfficeReader {}
+
+impl Reader for OfficeReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        Ok(read_office(file_path)?.0)
+    }
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let (content, metadata) = read_office(file_path)?;
+        let mut document = Document::new(file_path.to_path_buf(), content);
+        document.metadata = metadata;
+
+        Ok(vec![document])
+    }
+}
+
+fn read_office(file_path: &Path) -> Result<(String, Vec<(String, String)>), ()> {
+    let file = fs::File::open(file_path).map_err(|err| {
+        eprintln!(
+            "ERROR: could not open the file {file_path}: {err}",
+            file_path = file_path.display()
+        )
+    })?;
+    let mut archive = ZipArchive::new(file).map_err(|err| {
+        eprintln!(
+            "ERROR: could not read the office file {file_path}: {err}",
+            file_path = file_path.display()
+        )
+    })?;
+
+    parse_office(&mut archive).map_err(|err| {
+        eprintln!(
+            "ERROR: could not read the office file {file_path}: {err}",
+            file_path = file_path.display()
+        )
+    })
+}
+
+/// Read the text and the metadata of the office file, the format is told by the parts in it
+fn parse_office<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<(String, Vec<(String, String)>), String> {
+    let mut parts = archive
+        .file_names()
+        .filter(|name| {
+            *name == "word/document.xml"
+                || *name == "xl/sharedStrings.xml"
+                || *name == "content.xml"
+                || (name.starts_with("ppt/slides/slide") && name.ends_with(".xml"))
+        })
+        .map(|name| name.to_string())
+        .collect::<Vec<String>>();
+    if parts.is_empty() {
+        return Err("no text is found in it".to_string());
+    }
+
+    // NOTE: the slides are sorted by their numbers, so "slide10.xml" comes after "slide9.xml"
+    parts.sort_by_key(|name| {
+        let number = name
+            .trim_start_matches("ppt/slides/slide")
+            .trim_end_matches(".xml")
+            .parse::<usize>()
+            .unwrap_or(0);
+        (number, name.clone())
+    });
+
+    let mut text = String::new();
+    for part in parts {
+        text.push_str(&xml_text(&read_part(archive, &part)?));
+    }
+
+    let mut metadata: Vec<(String, String)> = Vec::new();
+    for part in ["docProps/core.xml", "meta.xml"] {
+        if archive.file_names().all(|name| name != part) {
+            continue;
+        }
+
+        let xml = read_part(archive, part)?;
+        for (element, name) in PROPERTIES {
+            let values = xml_elements(&xml, element);
+            if values.is_empty() || metadata.iter().any(|(x, _)| x == name) {
+                continue;
+            }
+            metadata.push((name.to_string(), values.join(", ")));
+        }
+    }
+
+    Ok((text, metadata))
+}
+
+fn read_part<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<String, String> {
+    let mut xml = String::new();
+    archive
+        .by_name(name)
+        .map_err(|err| err.to_string())?
+        .read_to_string(&mut xml)
+        .map_err(|err| format!("{name}: {err}"))?;
+
+    Ok(xml)
+}
+
+/// Remove the tags from the XML, and break the lines at the end of the paragraphs
+fn xml_text(xml: &str) -> String {
+    let mut text = String::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => {
+                rest = "";
+                break;
+            }
+        };
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+
+        let name = tag
+            .trim_start_matches('/')
+            .split(|x: char| x.is_whitespace() || x == '/')
+            .next()
+            .unwrap_or_default();
+        if tag.starts_with('/') && PARAGRAPH_TAGS.contains(&name) {
+            text.push('\n');
+        } else if !tag.starts_with('/') && SPACE_TAGS.contains(&name) {
+            text.push(' ');
+        }
+    }
+    text.push_str(rest);
+
+    decode_entities(&text)
+}
+
+/// The text of all the elements named `name` in the XML
+fn xml_elements(xml: &str, name: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find(&format!("<{name}")) {
+        rest = &rest[start + name.len() + 1..];
+        if !rest.starts_with(['>', ' ', '\t', '\r', '\n']) {
+            continue;
+        }
+
+        let (open_end, close) = match (rest.find('>'), rest.find(&format!("</{name}>"))) {
+            (Some(open_end), Some(close)) if open_end < close => (open_end, close),
+            _ => continue,
+        };
+        let value = xml_text(&rest[open_end + 1..close]).trim().to_string();
+        if !value.is_empty() {
+            values.push(value);
+        }
+        rest = &rest[close..];
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_office;
+    use std::io::{Cursor, Write};
+    use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+    #[test]
+    fn parse_office_ok() -> Result<(), ()> {
+        // arrange
+        let parts = [
+            (
+                "word/document.xml",
+                r#"<w:document><w:body><w:p><w:r><w:t>Andy</w:t></w:r><w:r><w:tab/><w:t>is Andy.</w:t></w:r></w:p><w:p><w:r><w:t>Amy &amp; Andy</w:t></w:r></w:p></w:body></w:document>"#,
+            ),
+            (
+                "docProps/core.xml",
+                r#"<cp:coreProperties><dc:title>Notes</dc:title><dc:creator>Andy</dc:creator><cp:keywords>rust</cp:keywords><cp:lastModifiedBy>Amy</cp:lastModifiedBy></cp:coreProperties>"#,
+            ),
+        ];
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, xml) in parts {
+            writer
+                .start_file(name, FileOptions::default())
+                .and_then(|_| Ok(writer.write_all(xml.as_bytes())?))
+                .map_err(|err| eprintln!("ERROR: {err}"))?;
+        }
+        let data = writer.finish().map_err(|err| eprintln!("ERROR: {err}"))?;
+        let mut archive = ZipArchive::new(data).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // act
+        let (text, metadata) =
+            parse_office(&mut archive).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert_eq!(text, "Andy is Andy.\nAmy & Andy\n");
+        assert_eq!(
+            metadata,
+            vec![
+                ("title".to_string(), "Notes".to_string()),
+                ("author".to_string(), "Andy".to_string()),
+                ("keywords".to_string(), "rust".to_string()),
+            ]
+        );
+
+        Ok(())
+    }
+}
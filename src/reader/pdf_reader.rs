@@ -1,31 +1,265 @@
-use super::reader_trait::Reader;
-use std::panic;
-use std::path::Path;
+use super::reader_trait::{Document, Reader};
+use encoding_rs::{UTF_16BE, WINDOWS_1252};
+use pdf_extract::{
+    ColorSpace, MediaBox, OutputDev, OutputError, Path as PdfPath, PlainTextOutput, Transform,
+};
+use std::{
+    cell::RefCell,
+    io, panic,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
+/// The entries of the document information dictionary and the names of the metadata
+const INFO_ENTRIES: [(&[u8], &str); 4] = [
+    (b"Title", "title"),
+    (b"Author", "author"),
+    (b"Subject", "subject"),
+    (b"Keywords", "keywords"),
+];
+
+/// The text of the pages and the metadata of the PDF
+type Pages = (Vec<String>, Vec<(String, String)>);
+
+/// Read the PDF page by page, every page is a document named like "file.pdf#page=3". The title, the
+/// author, the subject and the keywords of the PDF are the metadata of every page.
 pub struct PDFReader {}
 
 impl Reader for PDFReader {
-    fn read_text(file_path: &Path) -> Result<String, ()> {
-        // NOTE: panic::catch_unwind is used to catch panics from the pdf_extract crate, and I
-        // think it's the best way to handle this but in this crate.
-        let result = panic::catch_unwind(|| pdf_extract::extract_text(file_path));
-        match result {
-            Ok(Ok(content)) => Ok(content),
-            Ok(Err(err)) => {
-                eprintln!(
-                    "ERROR: could not extract text from PDF {file_path}: {err}",
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        Ok(extract_pages(file_path)?.0.join("\n"))
+    }
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let (pages, metadata) = extract_pages(file_path)?;
+        let documents = pages
+            .into_iter()
+            .enumerate()
+            .map(|(i, content)| {
+                let path = PathBuf::from(format!(
+                    "{file_path}#page={page}",
                     file_path = file_path.display(),
-                    err = err
-                );
-                Err(())
-            }
-            Err(_) => {
-                eprintln!(
-                    "ERROR: could not extract text from PDF {file_path}",
-                    file_path = file_path.display()
-                );
-                Err(())
-            }
+                    page = i + 1
+                ));
+                let mut document = Document::new(path, content);
+                document.metadata = metadata.clone();
+                document
+            })
+            .collect();
+
+        Ok(documents)
+    }
+}
+
+/// Extract the text of every page, and the metadata from the document information dictionary
+fn extract_pages(file_path: &Path) -> Result<Pages, ()> {
+    // NOTE: panic::catch_unwind is used to catch panics from the pdf_extract crate, and I
+    // think it's the best way to handle this but in this crate.
+    let result = panic::catch_unwind(|| -> Result<Pages, OutputError> {
+        let doc = lopdf::Document::load(file_path)?;
+        let metadata = read_info(&doc);
+        let pages = Rc::new(RefCell::new(Vec::new()));
+        let mut writer = PageWriter {
+            pages: Rc::clone(&pages),
+        };
+        {
+            let mut output = PageOutput {
+                pages: Rc::clone(&pages),
+                output: PlainTextOutput::new(&mut writer as &mut dyn io::Write),
+            };
+            pdf_extract::output_doc(&doc, &mut output)?;
+        }
+
+        let pages = pages.borrow().clone();
+        Ok((pages, metadata))
+    });
+    match result {
+        Ok(Ok(pages)) => Ok(pages),
+        Ok(Err(err)) => {
+            eprintln!(
+                "ERROR: could not extract text from PDF {file_path}: {err}",
+                file_path = file_path.display(),
+                err = err
+            );
+            Err(())
+        }
+        Err(_) => {
+            eprintln!(
+                "ERROR: could not extract text from PDF {file_path}",
+                file_path = file_path.display()
+            );
+            Err(())
+        }
+    }
+}
+
+/// Read the metadata from the document information dictionary, which is optional in a PDF
+fn read_info(doc: &lopdf::Document) -> Vec<(String, String)> {
+    let info = match doc
+        .trailer
+        .get(b"Info")
+        .and_then(|info| doc.dereference(info))
+        .and_then(|(_, info)| info.as_dict())
+    {
+        Ok(info) => info,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut metadata = Vec::new();
+    for (key, name) in INFO_ENTRIES {
+        let value = match info.get(key).and_then(|value| doc.dereference(value)) {
+            Ok((_, lopdf::Object::String(bytes, _))) => decode_pdf_string(bytes),
+            _ => continue,
+        };
+        let value = value.trim();
+        if !value.is_empty() {
+            metadata.push((name.to_string(), value.to_string()));
         }
     }
+
+    metadata
+}
+
+/// Decode the text string of PDF, which is either UTF-16BE with a byte order mark or
+/// PDFDocEncoding(close to Windows-1252)
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(b"\xfe\xff") {
+        Some(bytes) => UTF_16BE.decode_without_bom_handling(bytes).0.into_owned(),
+        None => WINDOWS_1252
+            .decode_without_bom_handling(bytes)
+            .0
+            .into_owned(),
+    }
+}
+
+/// Write the text into the last page
+struct PageWriter {
+    pages: Rc<RefCell<Vec<String>>>,
+}
+
+impl io::Write for PageWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(page) = self.pages.borrow_mut().last_mut() {
+            page.push_str(&String::from_utf8_lossy(buf));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The plain text output starting a new page in `pages` on every page of the PDF
+struct PageOutput<'a> {
+    pages: Rc<RefCell<Vec<String>>>,
+    output: PlainTextOutput<&'a mut dyn io::Write>,
+}
+
+impl OutputDev for PageOutput<'_> {
+    fn begin_page(
+        &mut self,
+        page_num: u32,
+        media_box: &MediaBox,
+        art_box: Option<(f64, f64, f64, f64)>,
+    ) -> Result<(), OutputError> {
+        self.pages.borrow_mut().push(String::new());
+        self.output.begin_page(page_num, media_box, art_box)
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        self.output.end_page()
+    }
+
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> Result<(), OutputError> {
+        self.output
+            .output_character(trm, width, spacing, font_size, char)
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        self.output.begin_word()
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        self.output.end_word()
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        self.output.end_line()
+    }
+
+    fn stroke(
+        &mut self,
+        ctm: &Transform,
+        colorspace: &ColorSpace,
+        color: &[f64],
+        path: &PdfPath,
+    ) -> Result<(), OutputError> {
+        self.output.stroke(ctm, colorspace, color, path)
+    }
+
+    fn fill(
+        &mut self,
+        ctm: &Transform,
+        colorspace: &ColorSpace,
+        color: &[f64],
+        path: &PdfPath,
+    ) -> Result<(), OutputError> {
+        self.output.fill(ctm, colorspace, color, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_pages, PDFReader};
+    use crate::reader::reader_trait::Reader;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn extract_pages_ok() -> Result<(), ()> {
+        // arrange
+        let file_path = Path::new("data/pdf-sample.pdf");
+
+        // act
+        let (pages, metadata) = extract_pages(file_path)?;
+        let missing = extract_pages(Path::new("data/missing.pdf"));
+
+        // assert
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].contains("Adobe Acrobat PDF Files"));
+        assert_eq!(
+            metadata,
+            vec![
+                ("title".to_string(), "This is a test PDF file".to_string()),
+                ("author".to_string(), "cdaily".to_string()),
+            ]
+        );
+        assert!(missing.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_documents_ok() -> Result<(), ()> {
+        // arrange
+        let reader = PDFReader {};
+
+        // act
+        let actual = reader.read_documents(Path::new("data/pdf-sample.pdf"))?;
+
+        // assert
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].path, PathBuf::from("data/pdf-sample.pdf#page=1"));
+        assert!(actual[0].content.contains("Portable Document Format"));
+        assert_eq!(actual[0].metadata.len(), 2);
+
+        Ok(())
+    }
 }
@@ -1,11 +1,85 @@
-use super::reader_trait::Reader;
+use super::{file_type::utf16_without_bom, reader_trait::Reader};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
 use std::{fs, path::Path};
 
-pub struct PlainTextReader {}
+/// Read the text file and transcode it into UTF-8
+pub struct PlainTextReader {
+    /// The encoding of the file which is neither marked by a byte order mark nor valid UTF-8, the
+    /// encoding is guessed when it's not provided.
+    pub default_encoding: Option<&'static Encoding>,
+}
 
 impl Reader for PlainTextReader {
-    fn read_text(file_path: &Path) -> Result<String, ()> {
-        fs::read_to_string(file_path)
-            .map_err(|err| eprintln!("ERROR: could not open the file {file_path:?}: {err}"))
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        let bytes = fs::read(file_path)
+            .map_err(|err| eprintln!("ERROR: could not open the file {file_path:?}: {err}"))?;
+
+        Ok(decode_text(&bytes, self.default_encoding))
+    }
+}
+
+/// Decode the text by the byte order mark, as UTF-8, or by the default or the guessed encoding
+pub fn decode_text(bytes: &[u8], default_encoding: Option<&'static Encoding>) -> String {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return encoding
+            .decode_without_bom_handling(&bytes[bom_length..])
+            .0
+            .into_owned();
+    }
+
+    // NOTE: UTF-16 text of ASCII characters is valid UTF-8 too, so it's checked first
+    if let Some(encoding) = utf16_without_bom(bytes) {
+        return encoding.decode_without_bom_handling(bytes).0.into_owned();
+    }
+
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+
+    let encoding = default_encoding.unwrap_or_else(|| {
+        let mut detector = EncodingDetector::new();
+        detector.feed(bytes, true);
+        detector.guess(None, true)
+    });
+
+    // NOTE: UTF-8 here means the guess failed, and the malformed bytes are replaced
+    if encoding == UTF_8 {
+        return String::from_utf8_lossy(bytes).to_string();
+    }
+
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_text;
+    use encoding_rs::{BIG5, WINDOWS_1252};
+
+    #[test]
+    fn decode_text_ok() {
+        // arrange
+        let inputs: [(&[u8], _, &str); 8] = [
+            (b"Andy is Andy.", None, "Andy is Andy."),
+            (b"\xef\xbb\xbfAndy", None, "Andy"),
+            (b"\xff\xfeA\x00m\x00y\x00", None, "Amy"),
+            (b"A\x00m\x00y\x00", None, "Amy"),
+            (b"\x00A\x00n\x00d\x00y", None, "Andy"),
+            (
+                b"Caf\xe9 au lait et cr\xe8me br\xfbl\xe9e \xe0 la fran\xe7aise",
+                None,
+                "Café au lait et crème brûlée à la française",
+            ),
+            (b"Caf\xe9 au lait", Some(WINDOWS_1252), "Café au lait"),
+            (b"\xa4\xa4\xa4\xe5", Some(BIG5), "中文"),
+        ];
+
+        for (input, default_encoding, expected) in inputs {
+            // act
+            let actual = decode_text(input, default_encoding);
+
+            // assert
+            assert_eq!(expected, actual);
+        }
     }
 }
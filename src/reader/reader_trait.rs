@@ -1,5 +1,53 @@
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
-pub trait Reader {
-    fn read_text(file_path: &Path) -> Result<String, ()>;
+/// A searchable unit read from a file
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Document {
+    /// The path to the document, it could be a virtual one(e.g. "inbox.mbox#3") when a file holds
+    /// several documents.
+    pub path: PathBuf,
+    pub content: String,
+    /// The structured text of the document(e.g. the subject of an email), searchable by the plain
+    /// terms and by the terms prefixed with the field name(e.g. "subject:hello")
+    pub fields: Vec<(String, String)>,
+    /// The information about the document(e.g. the author of a PDF or the size of the file), only
+    /// searchable by the terms prefixed with its name(e.g. "author:andy")
+    #[serde(default)]
+    pub metadata: Vec<(String, String)>,
+    /// The parts of the content located by their anchors(e.g. the cues of a subtitle located by
+    /// their timestamps), so a search hit could tell where it's matched best
+    #[serde(default)]
+    pub passages: Vec<Passage>,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Passage {
+    pub anchor: String,
+    pub text: String,
+}
+
+impl Document {
+    pub fn new(path: PathBuf, content: String) -> Self {
+        Document {
+            path,
+            content,
+            fields: Vec::new(),
+            metadata: Vec::new(),
+            passages: Vec::new(),
+        }
+    }
+}
+
+/// Extract the text from files. The readers are looked up in the `ReaderRegistry` by the extensions
+/// or the MIME types of the files.
+pub trait Reader: Send + Sync {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()>;
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        Ok(vec![Document::new(
+            file_path.to_path_buf(),
+            self.read_text(file_path)?,
+        )])
+    }
 }
@@ -0,0 +1,203 @@
+use super::super::config::FolderConfig;
+use super::{
+    archive_reader::ArchiveReader,
+    command_reader::CommandReader,
+    csv_reader::CsvReader,
+    email_reader::{EmailReader, MboxReader},
+    file_type::FileType,
+    html_reader::HtmlReader,
+    json_reader::JsonReader,
+    latex_reader::LatexReader,
+    notebook_reader::NotebookReader,
+    office_reader::OfficeReader,
+    pdf_reader::PDFReader,
+    plain_text_reader::PlainTextReader,
+    reader_trait::{Document, Reader},
+    rst_reader::RstReader,
+    sandboxed_reader::SandboxedReader,
+    subtitle_reader::SubtitleReader,
+};
+use std::{collections::HashMap, path::Path};
+
+/// The names, the extensions and the file types of the built-in readers
+const BUILTIN_READERS: [(&str, &[&str], &[FileType]); 14] = [
+    ("text", &["txt"], &[FileType::PlainText]),
+    ("html", &["html", "htm", "xhtml"], &[FileType::Html]),
+    ("pdf", &["pdf"], &[FileType::Pdf]),
+    (
+        "office",
+        &["docx", "xlsx", "pptx", "odt", "ods", "odp"],
+        &[],
+    ),
+    ("notebook", &["ipynb"], &[]),
+    ("json", &["json"], &[]),
+    ("csv", &["csv"], &[]),
+    ("tsv", &["tsv", "tab"], &[]),
+    ("subtitle", &["srt", "vtt"], &[]),
+    ("latex", &["tex", "ltx"], &[]),
+    ("rst", &["rst", "rest"], &[]),
+    ("email", &["eml"], &[FileType::Email]),
+    ("mbox", &["mbox"], &[FileType::Mbox]),
+    (
+        "archive",
+        &["zip", "tar", "gz", "tgz"],
+        &[FileType::Zip, FileType::Tar, FileType::Gzip],
+    ),
+];
+
+/// Create the built-in reader by its name
+pub fn builtin_reader(name: &str, config: &FolderConfig) -> Option<Box<dyn Reader>> {
+    match name {
+        "text" => Some(Box::new(PlainTextReader {
+            default_encoding: config.encoding(),
+        })),
+        "html" => Some(Box::new(HtmlReader {
+            default_encoding: config.encoding(),
+        })),
+        "pdf" => Some(Box::new(PDFReader {})),
+        "office" => Some(Box::new(OfficeReader {})),
+        "notebook" => Some(Box::new(NotebookReader {
+            outputs: config.notebook_outputs.unwrap_or(false),
+        })),
+        "json" => Some(Box::new(JsonReader {})),
+        "csv" => Some(Box::new(CsvReader {
+            delimiter: ',',
+            default_encoding: config.encoding(),
+        })),
+        "tsv" => Some(Box::new(CsvReader {
+            delimiter: '\t',
+            default_encoding: config.encoding(),
+        })),
+        "subtitle" => Some(Box::new(SubtitleReader {
+            default_encoding: config.encoding(),
+        })),
+        "latex" => Some(Box::new(LatexReader {
+            default_encoding: config.encoding(),
+        })),
+        "rst" => Some(Box::new(RstReader {
+            default_encoding: config.encoding(),
+        })),
+        "email" => Some(Box::new(EmailReader {})),
+        "mbox" => Some(Box::new(MboxReader {})),
+        "archive" => Some(Box::new(ArchiveReader {
+            config: config.clone(),
+        })),
+        _ => None,
+    }
+}
+
+/// The readers looked up by the extensions or the MIME types of the files
+pub struct ReaderRegistry {
+    readers: Vec<Box<dyn Reader>>,
+    extensions: HashMap<String, usize>,
+    mime_types: HashMap<String, usize>,
+}
+
+impl ReaderRegistry {
+    /// Create the registry with the built-in readers and the command readers in the config
+    pub fn new(config: &FolderConfig) -> Self {
+        let mut registry = ReaderRegistry {
+            readers: Vec::new(),
+            extensions: HashMap::new(),
+            mime_types: HashMap::new(),
+        };
+
+        let sandbox = config.sandbox();
+        for (name, extensions, file_types) in BUILTIN_READERS {
+            let reader: Box<dyn Reader> = if sandbox.readers.iter().any(|x| x == name) {
+                Box::new(SandboxedReader {
+                    reader: name.to_string(),
+                    config: config.clone(),
+                })
+            } else {
+                match builtin_reader(name, config) {
+                    Some(reader) => reader,
+                    None => continue,
+                }
+            };
+
+            registry.register(
+                reader,
+                extensions,
+                &file_types
+                    .iter()
+                    .map(|x| x.mime_type())
+                    .collect::<Vec<&str>>(),
+            );
+        }
+
+        for command in &config.commands {
+            if let Ok(reader) = CommandReader::from_config(command) {
+                registry.register(
+                    Box::new(reader),
+                    &command
+                        .extensions
+                        .iter()
+                        .map(|x| x.as_str())
+                        .collect::<Vec<&str>>(),
+                    &command
+                        .mime_types
+                        .iter()
+                        .map(|x| x.as_str())
+                        .collect::<Vec<&str>>(),
+                );
+            }
+        }
+
+        registry
+    }
+
+    /// Register the reader, it replaces the readers registered before for the same extensions or
+    /// MIME types.
+    pub fn register(&mut self, reader: Box<dyn Reader>, extensions: &[&str], mime_types: &[&str]) {
+        let index = self.readers.len();
+        self.readers.push(reader);
+        for extension in extensions {
+            self.extensions
+                .insert(extension.trim_start_matches('.').to_lowercase(), index);
+        }
+        for mime_type in mime_types {
+            self.mime_types.insert(mime_type.to_lowercase(), index);
+        }
+    }
+
+    /// Remove the built-in reader by its name, the files it reads are skipped like the binary files
+    pub fn unregister_builtin(&mut self, name: &str) {
+        for (builtin, extensions, file_types) in BUILTIN_READERS {
+            if builtin == name {
+                for extension in extensions {
+                    self.extensions.remove(*extension);
+                }
+                for file_type in file_types {
+                    self.mime_types.remove(file_type.mime_type());
+                }
+            }
+        }
+    }
+
+    /// Find the reader by the extension of the file, and fallback to sniff the MIME type of the file
+    /// when the extension is missing or unknown. Nothing is found for binary files.
+    pub fn find(&self, file_path: &Path) -> Result<Option<&dyn Reader>, ()> {
+        let index = file_path.extension().and_then(|extension| {
+            self.extensions
+                .get(&extension.to_string_lossy().to_lowercase())
+        });
+
+        let index = match index {
+            Some(index) => Some(index),
+            None => self
+                .mime_types
+                .get(FileType::sniff_file(file_path)?.mime_type()),
+        };
+
+        Ok(index.map(|index| self.readers[*index].as_ref()))
+    }
+
+    pub fn read_from_file(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        match self.find(file_path)? {
+            Some(reader) => reader.read_documents(file_path),
+            // NOTE: binary files are skipped quietly, there is nothing to search in them
+            None => Ok(Vec::new()),
+        }
+    }
+}
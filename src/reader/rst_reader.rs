@@ -0,0 +1,256 @@
+use super::{
+    plain_text_reader::decode_text,
+    reader_trait::{Document, Reader},
+};
+use encoding_rs::Encoding;
+use std::{fs, path::Path};
+
+/// The characters used to underline(and overline) the section titles
+const ADORNMENTS: &str = "=-`:'\"~^_*+#<>.";
+
+/// The roles referring to the other documents or the labels, their targets are the "ref" fields
+const REF_ROLES: [&str; 3] = ["ref", "doc", "numref"];
+
+/// Reader of the reStructuredText files. The directives, the comments and the inline markups are
+/// removed, the section titles are the "section" fields, and the targets of `:ref:` are the "ref"
+/// fields.
+pub struct RstReader {
+    pub default_encoding: Option<&'static Encoding>,
+}
+
+impl Reader for RstReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        Ok(parse_rst(&self.read_source(file_path)?).0)
+    }
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let (content, fields) = parse_rst(&self.read_source(file_path)?);
+        let mut document = Document::new(file_path.to_path_buf(), content);
+        document.fields = fields;
+
+        Ok(vec![document])
+    }
+}
+
+impl RstReader {
+    fn read_source(&self, file_path: &Path) -> Result<String, ()> {
+        let bytes = fs::read(file_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not open the file {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+        Ok(decode_text(&bytes, self.default_encoding))
+    }
+}
+
+fn parse_rst(source: &str) -> (String, Vec<(String, String)>) {
+    let lines = source.lines().collect::<Vec<&str>>();
+    let mut content = String::new();
+    let mut fields = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        // NOTE: the title is either underlined, or overlined and underlined
+        let title = if is_adornment(line)
+            && i + 2 < lines.len()
+            && is_adornment(lines[i + 2])
+            && !lines[i + 1].trim().is_empty()
+        {
+            Some((lines[i + 1].trim(), 3))
+        } else if i + 1 < lines.len()
+            && !line.trim().is_empty()
+            && !line.starts_with(char::is_whitespace)
+            && is_adornment(lines[i + 1])
+            && lines[i + 1].trim_end().chars().count() >= line.trim_end().chars().count()
+        {
+            Some((line.trim(), 2))
+        } else {
+            None
+        };
+        if let Some((title, length)) = title {
+            // NOTE: the fields are searchable by their plain terms too, so the section titles and
+            // the targets of the references are not repeated in the text
+            let title = parse_inline(title, &mut fields);
+            content.push('\n');
+            fields.push(("section".to_string(), title));
+            i += length;
+            continue;
+        }
+
+        // NOTE: the transitions(e.g. "----") are not text
+        if is_adornment(line) {
+            i += 1;
+            continue;
+        }
+
+        // NOTE: the explicit markups start with "..": the directives(e.g. ".. note::") whose
+        // options are removed and content is kept, and the comments and the targets(e.g.
+        // ".. _label:") which are removed with their indented blocks
+        if line.starts_with("..") {
+            let is_directive = line.contains("::");
+            i += 1;
+            while i < lines.len() && lines[i].starts_with(char::is_whitespace) {
+                let is_option = lines[i].trim_start().starts_with(':');
+                if is_directive && !is_option {
+                    break;
+                }
+                i += 1;
+            }
+            continue;
+        }
+
+        content.push_str(&parse_inline(line, &mut fields));
+        content.push('\n');
+        i += 1;
+    }
+
+    (content, fields)
+}
+
+/// Whether the line is made of a single adornment character(e.g. "=====")
+fn is_adornment(line: &str) -> bool {
+    let line = line.trim_end();
+    let mut chars = line.chars();
+    match chars.next() {
+        Some(first) if ADORNMENTS.contains(first) => {
+            line.chars().count() >= 2 && chars.all(|x| x == first)
+        }
+        _ => false,
+    }
+}
+
+/// Remove the inline markups(e.g. "**bold**", "``code``" and "`Python <https://python.org>`_"),
+/// and keep the text. The targets of the reference roles(e.g. ":ref:`Ranking <ranking>`") are the
+/// "ref" fields instead.
+fn parse_inline(line: &str, fields: &mut Vec<(String, String)>) -> String {
+    let mut text = String::new();
+    let mut rest = line;
+
+    while let Some(start) = rest.find('`') {
+        let (before, role) = split_role(&rest[..start]);
+        text.push_str(before);
+
+        let quoted = &rest[start..];
+        let ticks = if quoted.starts_with("``") { "``" } else { "`" };
+        let end = match quoted[ticks.len()..].find(ticks) {
+            Some(end) => ticks.len() + end,
+            None => {
+                text.push_str(quoted);
+                rest = "";
+                break;
+            }
+        };
+        let inner = &quoted[ticks.len()..end];
+        rest = quoted[end + ticks.len()..].trim_start_matches('_');
+
+        let (label, target) = match inner.trim_end().strip_suffix('>') {
+            Some(inner) => match inner.rsplit_once('<') {
+                Some((label, target)) => (label.trim(), target.trim()),
+                None => (inner, inner),
+            },
+            None => (inner, inner),
+        };
+        if role.is_some_and(|role| REF_ROLES.contains(&role)) {
+            fields.push(("ref".to_string(), target.to_string()));
+            if label != target {
+                text.push_str(label);
+            }
+            continue;
+        }
+
+        text.push_str(label);
+        if label != target {
+            text.push(' ');
+            text.push_str(target);
+        }
+    }
+    text.push_str(rest);
+
+    text.replace("**", "")
+}
+
+/// Split the role(e.g. ":ref:" or ":py:func:") from the end of the text before the backquote
+fn split_role(text: &str) -> (&str, Option<&str>) {
+    let body = match text.strip_suffix(':') {
+        Some(body) => body,
+        None => return (text, None),
+    };
+
+    let start = body
+        .rfind(|x: char| !(x.is_alphanumeric() || matches!(x, ':' | '-' | '_')))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    match body[start..].strip_prefix(':') {
+        Some(role) if !role.is_empty() => (&text[..start], Some(role)),
+        _ => (text, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_rst;
+
+    #[test]
+    fn parse_rst_ok() {
+        // arrange
+        let source = "=========
+User Guide
+=========
+
+.. contents::
+   :depth: 2
+
+Ranking
+-------
+
+.. _ranking:
+
+Andy is **Andy**, see :ref:`Search <searching>` and :py:func:`search`.
+
+.. note::
+   Amy is ``Amy``.
+
+.. This is a comment
+   spanning two lines.
+
+----
+
+Visit `LOSER <https://github.com/twandylue/LOSER>`_.
+";
+
+        // act
+        let (content, fields) = parse_rst(source);
+
+        // assert
+        assert_eq!(
+            content.split_whitespace().collect::<Vec<&str>>(),
+            [
+                "Andy",
+                "is",
+                "Andy,",
+                "see",
+                "Search",
+                "and",
+                "search.",
+                "Amy",
+                "is",
+                "Amy.",
+                "Visit",
+                "LOSER",
+                "https://github.com/twandylue/LOSER."
+            ]
+        );
+        assert_eq!(
+            fields,
+            vec![
+                ("section".to_string(), "User Guide".to_string()),
+                ("section".to_string(), "Ranking".to_string()),
+                ("ref".to_string(), "searching".to_string()),
+            ]
+        );
+    }
+}
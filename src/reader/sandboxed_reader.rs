@@ -0,0 +1,189 @@
+use super::super::config::FolderConfig;
+use super::{
+    command_reader::run_command,
+    reader_trait::{Document, Reader},
+};
+use std::{env, path::Path, process::Command, time::Duration};
+
+/// Run a built-in reader in a subprocess of LOSER(the internal `extract` subcommand), so a file
+/// which hangs the reader, eats up the memory or crashes the reader won't take the indexer down.
+pub struct SandboxedReader {
+    /// The name of the built-in reader(e.g. "pdf")
+    pub reader: String,
+    pub config: FolderConfig,
+}
+
+impl Reader for SandboxedReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        Ok(self
+            .read_documents(file_path)?
+            .into_iter()
+            .map(|document| document.content)
+            .collect::<Vec<String>>()
+            .join("\n"))
+    }
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let program = env::current_exe()
+            .map_err(|err| eprintln!("ERROR: could not find the path to the program: {err}"))?;
+        let config = serde_json::to_string(&self.config)
+            .map_err(|err| eprintln!("ERROR: could not serialize the folder config: {err}"))?;
+
+        let mut command = Command::new(program);
+        command
+            .arg("extract")
+            .arg(&self.reader)
+            .arg(file_path)
+            .arg(config);
+
+        self.run(command, file_path)
+    }
+}
+
+impl SandboxedReader {
+    /// Run the subprocess within the limits of the sandbox, and parse the documents it writes
+    fn run(&self, mut command: Command, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let sandbox = self.config.sandbox();
+        limit_memory(&mut command, sandbox.max_memory_bytes);
+
+        let output = run_command(
+            &mut command,
+            file_path,
+            Duration::from_secs(sandbox.timeout_secs),
+            sandbox.max_output_bytes,
+        )?;
+
+        // NOTE: the documents are written in the last line, the lines before it could be printed
+        // by the crates used in the reader.
+        let output = String::from_utf8_lossy(&output);
+        let last_line = output.lines().last().unwrap_or_default();
+        serde_json::from_str(last_line).map_err(|err| {
+            eprintln!(
+                "ERROR: could not parse the documents extracted from {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })
+    }
+}
+
+#[cfg(unix)]
+fn limit_memory(command: &mut Command, max_memory_bytes: u64) {
+    use std::{io, os::unix::process::CommandExt};
+
+    // SAFETY: only setrlimit, which is async-signal-safe, is called between fork and exec
+    unsafe {
+        command.pre_exec(move || {
+            let limit = libc::rlimit {
+                rlim_cur: max_memory_bytes as libc::rlim_t,
+                rlim_max: max_memory_bytes as libc::rlim_t,
+            };
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn limit_memory(_command: &mut Command, _max_memory_bytes: u64) {}
+
+#[cfg(test)]
+mod tests {
+    use super::SandboxedReader;
+    use crate::{
+        config::{FolderConfig, SandboxConfig},
+        reader::reader_trait::Document,
+    };
+    use std::{
+        path::{Path, PathBuf},
+        process::Command,
+        time::{Duration, Instant},
+    };
+
+    fn sandboxed_reader(sandbox: SandboxConfig) -> SandboxedReader {
+        SandboxedReader {
+            reader: "text".to_string(),
+            config: FolderConfig {
+                sandbox: Some(sandbox),
+                ..FolderConfig::default()
+            },
+        }
+    }
+
+    #[test]
+    fn run_ok() -> Result<(), ()> {
+        // arrange
+        let reader = sandboxed_reader(SandboxConfig::default());
+        let expected = vec![Document::new(
+            PathBuf::from("test/a.txt"),
+            "Andy is Andy.".to_string(),
+        )];
+        let json = serde_json::to_string(&expected).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo 'noise from the reader'; echo \"$0\"", &json]);
+
+        // act
+        let actual = reader.run(command, Path::new("test/a.txt"))?;
+
+        // assert
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_timeout_ok() {
+        // arrange
+        let reader = sandboxed_reader(SandboxConfig {
+            timeout_secs: 1,
+            ..SandboxConfig::default()
+        });
+        let mut command = Command::new("sleep");
+        command.arg("10");
+        let started = Instant::now();
+
+        // act
+        let actual = reader.run(command, Path::new("test/a.txt"));
+
+        // assert
+        assert!(actual.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn run_failure_ok() {
+        // arrange
+        let reader = sandboxed_reader(SandboxConfig::default());
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo '[]'; exit 3"]);
+
+        // act
+        let actual = reader.run(command, Path::new("test/a.txt"));
+
+        // assert
+        assert!(actual.is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_memory_limit_ok() {
+        // arrange
+        let reader = sandboxed_reader(SandboxConfig {
+            max_memory_bytes: 64 * 1024 * 1024,
+            ..SandboxConfig::default()
+        });
+        // NOTE: the shell holds the whole output of the pipe(256 MiB) in a variable
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            "x=$(head -c 268435456 /dev/zero | tr '\\0' a); echo '[]'",
+        ]);
+
+        // act
+        let actual = reader.run(command, Path::new("test/a.txt"));
+
+        // assert
+        assert!(actual.is_err());
+    }
+}
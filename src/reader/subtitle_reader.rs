@@ -0,0 +1,166 @@
+use super::{
+    html_reader::decode_entities,
+    plain_text_reader::decode_text,
+    reader_trait::{Document, Passage, Reader},
+};
+use encoding_rs::Encoding;
+use std::{fs, path::Path};
+
+/// Reader of the SubRip(.srt) and the WebVTT(.vtt) subtitles, every cue is a passage located by its
+/// start time(e.g. "00:01:23.000").
+pub struct SubtitleReader {
+    pub default_encoding: Option<&'static Encoding>,
+}
+
+impl Reader for SubtitleReader {
+    fn read_text(&self, file_path: &Path) -> Result<String, ()> {
+        Ok(self
+            .read_cues(file_path)?
+            .into_iter()
+            .map(|cue| cue.text)
+            .collect::<Vec<String>>()
+            .join("\n"))
+    }
+
+    fn read_documents(&self, file_path: &Path) -> Result<Vec<Document>, ()> {
+        let cues = self.read_cues(file_path)?;
+        let mut document = Document::new(
+            file_path.to_path_buf(),
+            cues.iter()
+                .map(|cue| cue.text.as_str())
+                .collect::<Vec<&str>>()
+                .join("\n"),
+        );
+        document.passages = cues;
+
+        Ok(vec![document])
+    }
+}
+
+impl SubtitleReader {
+    fn read_cues(&self, file_path: &Path) -> Result<Vec<Passage>, ()> {
+        let bytes = fs::read(file_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not open the file {file_path}: {err}",
+                file_path = file_path.display()
+            )
+        })?;
+
+        Ok(parse_cues(&decode_text(&bytes, self.default_encoding)))
+    }
+}
+
+/// Split the subtitle into the cues. Both formats are blocks separated by empty lines, and a cue is
+/// the block with a timing line like "00:01:23,000 --> 00:01:25,000", the lines after it are the
+/// text. The other blocks(e.g. the "WEBVTT" header and the "NOTE" comments) are skipped.
+fn parse_cues(text: &str) -> Vec<Passage> {
+    let text = text.replace("\r\n", "\n");
+    let mut cues = Vec::new();
+
+    for block in text.split("\n\n") {
+        let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+        let timing = match lines.next() {
+            Some(timing) => timing,
+            None => continue,
+        };
+        let start = match timing.split("-->").next().map(normalize_timestamp) {
+            Some(Some(start)) => start,
+            _ => continue,
+        };
+
+        // NOTE: the tags(e.g. "<i>" and the voice "<v Andy>") are removed from the text
+        let text = lines
+            .map(|line| strip_tags(line).trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<String>>()
+            .join("\n");
+        if !text.is_empty() {
+            cues.push(Passage {
+                anchor: start,
+                text,
+            });
+        }
+    }
+
+    cues
+}
+
+fn strip_tags(line: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for x in line.chars() {
+        match x {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(x),
+            _ => {}
+        }
+    }
+
+    decode_entities(&text)
+}
+
+/// Normalize the timestamp of SubRip("00:01:23,000") and WebVTT("01:23.000" or "00:01:23.000")
+/// into "00:01:23.000"
+fn normalize_timestamp(timestamp: &str) -> Option<String> {
+    let timestamp = timestamp.trim().replace(',', ".");
+    let parts = timestamp.split(':').collect::<Vec<&str>>();
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [minutes, seconds] => ("0", *minutes, *seconds),
+        [hours, minutes, seconds] => (*hours, *minutes, *seconds),
+        _ => return None,
+    };
+    let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    if !fraction.chars().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+
+    // NOTE: the fraction is of a second, so "1.5" is 500 milliseconds, and the digits after the
+    // milliseconds are dropped
+    let millis = format!("{fraction:0<3}")
+        .chars()
+        .take(3)
+        .collect::<String>();
+
+    Some(format!(
+        "{hours:02}:{minutes:02}:{seconds:02}.{millis}",
+        hours = hours.parse::<u32>().ok()?,
+        minutes = minutes.parse::<u32>().ok()?,
+        seconds = seconds.parse::<u32>().ok()?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_cues;
+    use crate::reader::reader_trait::Passage;
+
+    #[test]
+    fn parse_cues_ok() {
+        // arrange
+        let inputs = [
+            "1\r\n00:00:01,000 --> 00:00:03,500\r\nHello, <i>Andy</i>.\r\n\r\n2\r\n00:01:04,250 --> 00:01:06,000\r\nAmy is here,\r\nand Andy is here.\r\n",
+            "WEBVTT\n\nNOTE recorded on Monday\n\n00:01.000 --> 00:03.500 align:start\n<v Amy>Hello, Andy.\n\nintro\n01:04.250 --> 01:06.000\nAmy is here,\nand Andy is here.\n",
+            "WEBVTT\n\n00:01 --> 00:03.5\nHello, Andy.\n\n01:04.25 --> 01:06.0\nAmy is here,\nand Andy is here.\n",
+            "1\n00:00:01,0004 --> 00:00:03,5\nHello, Andy.\n\n2\n00:01:04,2509 --> 00:01:06,0\nAmy is here,\nand Andy is here.\n",
+        ];
+        let expected = vec![
+            Passage {
+                anchor: "00:00:01.000".to_string(),
+                text: "Hello, Andy.".to_string(),
+            },
+            Passage {
+                anchor: "00:01:04.250".to_string(),
+                text: "Amy is here,\nand Andy is here.".to_string(),
+            },
+        ];
+
+        for input in inputs {
+            // act
+            let actual = parse_cues(input);
+
+            // assert
+            assert_eq!(actual, expected);
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use super::model::{
+    binary_index,
+    in_memory_index_model::{InMemoryIndexModel, Model},
+    index_header::{Compatibility, IndexHeader, FORMAT_VERSION, JSON_FORMAT_VERSION},
+    persistent_model::{ModelStore, PersistentModel},
+};
+use super::segments::{self, SegmentStore};
+use std::{
+    fs,
+    io::{BufReader, BufWriter, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    thread,
+    time::Duration,
+};
+
+/// Saver of the index of the server. The model is saved only after it's changed, and the changes
+/// made within `interval` are saved at once.
+pub struct IndexSaver<M: PersistentModel> {
+    model: Arc<RwLock<M>>,
+    store: Arc<M::Store>,
+    interval: Duration,
+    /// The generation of the model in the index
+    saved_generation: Mutex<u64>,
+}
+
+impl<M: PersistentModel> IndexSaver<M> {
+    /// The model is regarded as saved, since it's loaded from the index(or it's empty)
+    pub fn new(model: Arc<RwLock<M>>, store: Arc<M::Store>, interval: Duration) -> Self {
+        let saved_generation = Mutex::new(model.read().unwrap().generation());
+
+        IndexSaver {
+            model,
+            store,
+            interval,
+            saved_generation,
+        }
+    }
+
+    /// Save the changes since the last save
+    pub fn flush(&self) -> Result<(), ()> {
+        let mut saved_generation = self.saved_generation.lock().unwrap();
+        let generation = self.model.read().unwrap().generation();
+        if generation == *saved_generation {
+            return Ok(());
+        }
+
+        self.store.save(&self.model)?;
+        *saved_generation = generation;
+
+        Ok(())
+    }
+
+    /// Keep saving the changes at most once every interval, it never returns
+    pub fn run(&self) {
+        loop {
+            thread::sleep(self.interval);
+
+            // NOTE: the changes are kept in the model after the failure, so they are saved again
+            // later
+            if self.flush().is_err() {
+                eprintln!("ERROR: could not save the changes of the index, retry later...");
+            }
+        }
+    }
+}
+
+impl PersistentModel for InMemoryIndexModel {
+    type Store = SegmentStore;
+
+    const EXTENSION: &'static str = "loser";
+
+    fn create_index(_index_path: &Path) -> Result<Self, ()> {
+        Ok(InMemoryIndexModel::new())
+    }
+
+    fn save_index(self, index_path: &Path, backup: bool) -> Result<(), ()> {
+        SegmentStore::create(index_path, &self, backup).map(|_| ())
+    }
+
+    // NOTE: the index made by the older version is migrated by saving it in the current format
+    // right away, and the index files saved before the binary format(e.g. "data.loser.json") are
+    // loaded when there's no index
+    fn open_index(
+        index_path: &Path,
+        roots: &[PathBuf],
+        backup: bool,
+    ) -> Result<(Self, SegmentStore), ()> {
+        if let Some(version) = segments::format_version(index_path) {
+            if version > FORMAT_VERSION {
+                eprintln!(
+                    "ERROR: the index file {index_path} is made by a newer version of LOSER(the format version {version}), the supported version is {FORMAT_VERSION}",
+                    index_path = index_path.display()
+                );
+                return Err(());
+            }
+        }
+
+        let legacy_path = sibling_path(index_path, "json");
+        let loaded = match load_model(index_path) {
+            None if !index_path.exists() && legacy_path.exists() => load_model(&legacy_path),
+            loaded => loaded,
+        };
+        let (model, rewrite) = match loaded {
+            Some(loaded) => match loaded.header.compatibility(roots) {
+                Compatibility::Current => (loaded.model, loaded.from_backup),
+                Compatibility::Outdated => {
+                    println!(
+                        "INFO: migrate the index {index_path} to the format version {FORMAT_VERSION}",
+                        index_path = index_path.display()
+                    );
+                    (loaded.model, true)
+                }
+                Compatibility::Incompatible(reason) => {
+                    println!("INFO: {reason}, index the folder from scratch...");
+                    let mut model = InMemoryIndexModel::new();
+                    model.set_rules(loaded.model.rules);
+                    (model, true)
+                }
+            },
+            None => {
+                if index_path.exists() {
+                    println!("INFO: the index is corrupt, index the folder from scratch...");
+                }
+                (InMemoryIndexModel::new(), true)
+            }
+        };
+
+        let store = if rewrite {
+            SegmentStore::create(index_path, &model, backup)?
+        } else {
+            SegmentStore::open(index_path, backup)?
+        };
+
+        Ok((model, store))
+    }
+}
+
+/// Export the model as JSON(e.g. for the other tools), it's never loaded by the search
+pub fn export_model_as_json(model: &InMemoryIndexModel, file_path: &Path) -> Result<(), ()> {
+    write_atomically(file_path, false, |writer| {
+        serde_json::to_writer(writer, model)
+            .map_err(|err| eprintln!("ERROR: could not serialize index into the JSON file: {err}"))
+    })
+}
+
+/// Write the file atomically. The file is written into a temporary file first, which is synced to
+/// the disk and renamed to the file, so a crash during the write never leaves a truncated file. The
+/// previous file is kept as the backup(e.g. "manifest.json.bak") when `backup` is set.
+pub fn write_atomically<F>(file_path: &Path, backup: bool, write: F) -> Result<(), ()>
+where
+    F: FnOnce(&mut BufWriter<fs::File>) -> Result<(), ()>,
+{
+    let temp_path = sibling_path(file_path, "tmp");
+    let file = fs::File::create(&temp_path).map_err(|err| {
+        eprintln!(
+            "ERROR: could not create the file {temp_path}: {err}",
+            temp_path = temp_path.display()
+        )
+    })?;
+
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    let file = writer.into_inner().map_err(|err| {
+        eprintln!(
+            "ERROR: could not write the file {temp_path}: {err}",
+            temp_path = temp_path.display()
+        )
+    })?;
+    file.sync_all().map_err(|err| {
+        eprintln!(
+            "ERROR: could not sync the file {temp_path}: {err}",
+            temp_path = temp_path.display()
+        )
+    })?;
+
+    // NOTE: the file is missing for a moment after it's moved to the backup, the backup is loaded
+    // if it crashes right then
+    if backup && file_path.exists() {
+        let backup_path = sibling_path(file_path, "bak");
+        fs::rename(file_path, &backup_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not back up the file {file_path} to {backup_path}: {err}",
+                file_path = file_path.display(),
+                backup_path = backup_path.display()
+            )
+        })?;
+    }
+
+    fs::rename(&temp_path, file_path).map_err(|err| {
+        eprintln!(
+            "ERROR: could not replace the file {file_path}: {err}",
+            file_path = file_path.display()
+        )
+    })?;
+
+    sync_parent(file_path);
+
+    Ok(())
+}
+
+/// Sync the folder holding the file, so the file renamed into it survives a crash
+// NOTE: the rename is durable only after the folder holding the file is synced, it's not supported
+// on every platform(e.g. Windows), so the failure is ignored
+pub fn sync_parent(file_path: &Path) {
+    let dir_path = match file_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = fs::File::open(dir_path) {
+        dir.sync_all().ok();
+    }
+}
+
+pub struct LoadedModel {
+    pub model: InMemoryIndexModel,
+    pub header: IndexHeader,
+    /// Whether the model is loaded from the backup, so the index file should be saved again
+    pub from_backup: bool,
+}
+
+/// Load the model from the index folder, or from the index file of the older formats(a single
+/// binary or JSON file). The backup is loaded when the index file is corrupt(e.g.
+/// truncated by a crash) or missing, and nothing is returned when neither of them could be loaded,
+/// so the folder should be indexed again.
+pub fn load_model(index_path: &Path) -> Option<LoadedModel> {
+    if index_path.is_dir() {
+        return segments::load_model(index_path);
+    }
+
+    let backup_path = sibling_path(index_path, "bak");
+    if index_path.exists() {
+        if let Ok((header, model)) = read_model(index_path) {
+            return Some(LoadedModel {
+                model,
+                header,
+                from_backup: false,
+            });
+        }
+    }
+    if !backup_path.exists() {
+        return None;
+    }
+
+    let (header, model) = read_model(&backup_path).ok()?;
+    println!(
+        "INFO: the index file {index_path} is recovered from the backup {backup_path}",
+        index_path = index_path.display(),
+        backup_path = backup_path.display()
+    );
+
+    Some(LoadedModel {
+        model,
+        header,
+        from_backup: true,
+    })
+}
+
+fn read_model(index_path: &Path) -> Result<(IndexHeader, InMemoryIndexModel), ()> {
+    let index_file = fs::File::open(index_path).map_err(|err| {
+        eprintln!(
+            "ERROR: could not open the index file {index_path}: {err}",
+            index_path = index_path.display()
+        )
+    })?;
+
+    let mut bytes = Vec::new();
+    BufReader::new(index_file)
+        .read_to_end(&mut bytes)
+        .map_err(|err| {
+            eprintln!(
+                "ERROR: could not read the index file {index_path}: {err}",
+                index_path = index_path.display()
+            )
+        })?;
+
+    // NOTE: the index files saved before the binary format are JSON, they are still loaded
+    if binary_index::is_binary(&bytes) {
+        return binary_index::decode(&bytes);
+    }
+
+    let model: InMemoryIndexModel = serde_json::from_slice(&bytes).map_err(|err| {
+        eprintln!(
+            "ERROR: could not parse the index file {index_path}: {err}",
+            index_path = index_path.display()
+        )
+    })?;
+
+    Ok((
+        IndexHeader::legacy(JSON_FORMAT_VERSION, model.roots.clone()),
+        model,
+    ))
+}
+
+/// The path next to the file with the extension appended(e.g. "data.loser.tmp")
+pub fn sibling_path(file_path: &Path, extension: &str) -> PathBuf {
+    let mut path = file_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export_model_as_json, load_model, sibling_path, IndexSaver};
+    use crate::model::analyzer::AnalyzedDocument;
+    use crate::model::in_memory_index_model::{InMemoryIndexModel, Model};
+    use crate::model::index_header::Compatibility;
+    use crate::model::persistent_model::ModelStore;
+    use crate::reader::reader_trait::Document;
+    use crate::segments::SegmentStore;
+    use std::{
+        env, fs,
+        path::PathBuf,
+        process,
+        sync::{Arc, RwLock},
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn flush_ok() -> Result<(), ()> {
+        // arrange
+        let index_path =
+            env::temp_dir().join(format!("loser-saver-test-{pid}.loser", pid = process::id()));
+        let model = Arc::new(RwLock::new(InMemoryIndexModel::new()));
+        let store = Arc::new(SegmentStore::open(&index_path, false)?);
+        let saver = IndexSaver::new(
+            Arc::clone(&model),
+            Arc::clone(&store),
+            Duration::from_secs(1),
+        );
+        let file_path = PathBuf::from("test/test1.txt");
+        let time = SystemTime::now();
+
+        // act
+        saver.flush()?;
+        let saved_unchanged = store.segment_paths().len();
+
+        model.write().unwrap().add_documents(
+            file_path.clone(),
+            vec![AnalyzedDocument::new(
+                Document::new(file_path, "Andy is Andy.".to_string()),
+                time,
+            )],
+            time,
+        )?;
+        saver.flush()?;
+        let saved_changed = store.segment_paths().len();
+
+        saver.flush()?;
+        let saved_again = store.segment_paths().len();
+        let loaded = load_model(&index_path).ok_or(())?;
+        fs::remove_dir_all(&index_path).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert_eq!(saved_unchanged, 0);
+        assert_eq!(saved_changed, 1);
+        assert_eq!(saved_again, 1);
+        assert_eq!(loaded.model, *model.read().unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_model_ok() -> Result<(), ()> {
+        // arrange
+        let index_path =
+            env::temp_dir().join(format!("loser-load-test-{pid}.loser", pid = process::id()));
+        let json_path = sibling_path(&index_path, "json");
+        let mut model = InMemoryIndexModel::new();
+        let file_path = PathBuf::from("test/test1.txt");
+        let time = SystemTime::now();
+        let store = SegmentStore::create(&index_path, &model, true)?;
+        model.add_documents(
+            file_path.clone(),
+            vec![AnalyzedDocument::new(
+                Document::new(file_path, "Andy is Andy.".to_string()),
+                time,
+            )],
+            time,
+        )?;
+        let model = RwLock::new(model);
+        store.save(&model)?;
+        let model = model.into_inner().unwrap();
+        let manifest_path = index_path.join("manifest.json");
+
+        // act
+        let loaded = load_model(&index_path).ok_or(())?;
+        export_model_as_json(&model, &json_path)?;
+        let exported = load_model(&json_path).ok_or(())?;
+        fs::remove_file(&json_path).map_err(|err| eprintln!("ERROR: {err}"))?;
+        // NOTE: the manifest is truncated like a crash during the write
+        fs::write(&manifest_path, "{\"segments\":[").map_err(|err| eprintln!("ERROR: {err}"))?;
+        let recovered = load_model(&index_path).ok_or(())?;
+        fs::remove_file(sibling_path(&manifest_path, "bak"))
+            .map_err(|err| eprintln!("ERROR: {err}"))?;
+        let missing = load_model(&index_path);
+        fs::remove_dir_all(&index_path).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert!(!loaded.from_backup);
+        assert_eq!(loaded.model, model);
+        assert_eq!(exported.model, model);
+        assert!(recovered.from_backup);
+        assert_eq!(recovered.model, InMemoryIndexModel::new());
+        assert!(missing.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_legacy_json_ok() -> Result<(), ()> {
+        // arrange
+        let index_path =
+            env::temp_dir().join(format!("loser-legacy-test-{pid}.json", pid = process::id()));
+        // NOTE: the index file saved by the first version of LOSER, before the header is added
+        let json = r#"{
+            "docs": {
+                "data/test.txt": {
+                    "tf": {"ANDY": 2, "IS": 1},
+                    "total_tokens": 3,
+                    "last_modified": {"secs_since_epoch": 1700000000, "nanos_since_epoch": 0}
+                }
+            },
+            "df": {"ANDY": 1, "IS": 1}
+        }"#;
+        fs::write(&index_path, json).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // act
+        let loaded = load_model(&index_path);
+        fs::remove_file(&index_path).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        let loaded = loaded.ok_or(())?;
+        assert!(matches!(
+            loaded.header.compatibility(&[PathBuf::from("data")]),
+            Compatibility::Incompatible(_)
+        ));
+
+        Ok(())
+    }
+}
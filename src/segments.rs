@@ -0,0 +1,537 @@
+use super::model::{
+    binary_index,
+    in_memory_index_model::{InMemoryIndexModel, Model, Tombstones},
+    index_header::IndexHeader,
+    persistent_model::ModelStore,
+};
+use super::saver::{sibling_path, write_atomically, LoadedModel};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs,
+    io::Write,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+const MANIFEST_NAME: &str = "manifest.json";
+const SEGMENT_PREFIX: &str = "segment-";
+const SEGMENT_EXTENSION: &str = ".loser";
+
+/// How many segments are kept before they are merged, and how many segments are merged at once
+const MERGE_FACTOR: usize = 4;
+
+/// How often the merge policy is checked
+const MERGE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The list of the segments in the index folder, the index folder holds the segments it lists
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+struct Manifest {
+    /// From the oldest segment to the newest one
+    segments: Vec<SegmentEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct SegmentEntry {
+    name: String,
+    bytes: u64,
+}
+
+/// The index folder(e.g. "data.loser") holding the segments of the index. The changes are saved as
+/// the new small segments, and the segments are never modified, so saving the changes doesn't
+/// rewrite the whole index. The segments are merged in the background to keep their number small.
+pub struct SegmentStore {
+    dir_path: PathBuf,
+    /// Whether the previous manifest is kept as the backup, with the segments it lists
+    backup: bool,
+    manifest: Mutex<Manifest>,
+    next_id: AtomicU64,
+    /// Held while the segment is written until it's listed by the manifest, so the segment isn't
+    /// removed as an unlisted one by the merge in the meantime
+    writing: Mutex<()>,
+}
+
+impl SegmentStore {
+    /// Open the index folder, it's created when it's missing
+    pub fn open(dir_path: &Path, backup: bool) -> Result<Self, ()> {
+        fs::create_dir_all(dir_path).map_err(|err| {
+            eprintln!(
+                "ERROR: could not create the index folder {dir_path}: {err}",
+                dir_path = dir_path.display()
+            )
+        })?;
+
+        let manifest_path = dir_path.join(MANIFEST_NAME);
+        let manifest = if manifest_path.exists() {
+            read_manifest(&manifest_path)?
+        } else {
+            Manifest::default()
+        };
+
+        // NOTE: the ids continue after the segments in the folder, so the segments listed by the
+        // backup are never overwritten
+        let next_id = segment_files(dir_path)
+            .iter()
+            .filter_map(|name| segment_id(name))
+            .max()
+            .map_or(1, |id| id + 1);
+
+        Ok(SegmentStore {
+            dir_path: dir_path.to_path_buf(),
+            backup,
+            manifest: Mutex::new(manifest),
+            next_id: AtomicU64::new(next_id),
+            writing: Mutex::new(()),
+        })
+    }
+
+    /// Replace the index with the model as a single segment, e.g. when the folder is indexed from
+    /// scratch. The index file of the older format(a single file) is replaced by the index folder,
+    /// it's kept as the backup(e.g. "data.loser.bak") when `backup` is set.
+    pub fn create(dir_path: &Path, model: &InMemoryIndexModel, backup: bool) -> Result<Self, ()> {
+        if dir_path.is_file() {
+            let result = if backup {
+                fs::rename(dir_path, sibling_path(dir_path, "bak"))
+            } else {
+                fs::remove_file(dir_path)
+            };
+            result.map_err(|err| {
+                eprintln!(
+                    "ERROR: could not replace the index file {dir_path}: {err}",
+                    dir_path = dir_path.display()
+                )
+            })?;
+        }
+
+        // NOTE: the corrupt manifest is replaced
+        let manifest_path = dir_path.join(MANIFEST_NAME);
+        if manifest_path.exists() && read_manifest(&manifest_path).is_err() {
+            fs::remove_file(&manifest_path).map_err(|err| {
+                eprintln!(
+                    "ERROR: could not remove the manifest {manifest_path}: {err}",
+                    manifest_path = manifest_path.display()
+                )
+            })?;
+        }
+
+        let store = SegmentStore::open(dir_path, backup)?;
+        let len = store.manifest.lock().unwrap().segments.len();
+        store.replace(0..len, model, &Tombstones::default())?;
+
+        Ok(store)
+    }
+
+    /// The paths of the segments, from the oldest one to the newest one
+    pub fn segment_paths(&self) -> Vec<PathBuf> {
+        self.manifest
+            .lock()
+            .unwrap()
+            .segments
+            .iter()
+            .map(|segment| self.dir_path.join(&segment.name))
+            .collect()
+    }
+
+    /// Save the changes as a new segment
+    pub fn append(&self, model: &InMemoryIndexModel, tombstones: &Tombstones) -> Result<(), ()> {
+        let _writing = self.writing.lock().unwrap();
+        let entry = self.write_segment(model, tombstones)?;
+        let mut manifest = self.manifest.lock().unwrap();
+        let mut changed = manifest.clone();
+        changed.segments.push(entry);
+        self.write_manifest(&changed)?;
+        *manifest = changed;
+
+        Ok(())
+    }
+
+    /// Merge the segments chosen by the merge policy, it returns whether the segments are merged
+    pub fn merge(&self) -> Result<bool, ()> {
+        let segments = self.manifest.lock().unwrap().segments.clone();
+        let sizes = segments
+            .iter()
+            .map(|segment| segment.bytes)
+            .collect::<Vec<u64>>();
+        let range = match merge_range(&sizes) {
+            Some(range) => range,
+            None => return Ok(false),
+        };
+
+        let mut merged = InMemoryIndexModel::new();
+        let mut tombstones = Tombstones::default();
+        for segment in &segments[range.clone()] {
+            let (_, model, segment_tombstones) = read_segment(&self.dir_path.join(&segment.name))?;
+            merged.apply_segment(model, &segment_tombstones);
+            tombstones.docs.extend(segment_tombstones.docs);
+            tombstones.files.extend(segment_tombstones.files);
+        }
+        merged.clear_pending();
+
+        // NOTE: the tombstones only hide the older segments, there's nothing to hide when the
+        // oldest segment is merged
+        if range.start == 0 {
+            tombstones = Tombstones::default();
+        }
+
+        // NOTE: the segments are only appended while they are merged, so the merged range is
+        // still at the same position
+        let count = range.len();
+        self.replace(range, &merged, &tombstones)?;
+        println!(
+            "INFO: merged {count} segments of the index folder {dir_path}",
+            dir_path = self.dir_path.display()
+        );
+
+        Ok(true)
+    }
+
+    /// Keep merging the segments in the background, it never returns
+    pub fn run_merger(&self) {
+        loop {
+            thread::sleep(MERGE_INTERVAL);
+
+            // NOTE: the segments are kept as they are after the failure, so they are merged again
+            // later
+            loop {
+                match self.merge() {
+                    Ok(true) => continue,
+                    Ok(false) => break,
+                    Err(()) => {
+                        eprintln!(
+                            "ERROR: could not merge the segments of the index folder {dir_path}, retry later...",
+                            dir_path = self.dir_path.display()
+                        );
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replace the segments in the range with a new segment, and remove the segments which are
+    /// not listed anymore
+    fn replace(
+        &self,
+        range: Range<usize>,
+        model: &InMemoryIndexModel,
+        tombstones: &Tombstones,
+    ) -> Result<(), ()> {
+        let _writing = self.writing.lock().unwrap();
+        let entry = self.write_segment(model, tombstones)?;
+        {
+            let mut manifest = self.manifest.lock().unwrap();
+            let mut changed = manifest.clone();
+            changed.segments.splice(range, [entry]);
+            self.write_manifest(&changed)?;
+            *manifest = changed;
+        }
+
+        self.remove_unlisted_segments();
+
+        Ok(())
+    }
+
+    fn write_segment(
+        &self,
+        model: &InMemoryIndexModel,
+        tombstones: &Tombstones,
+    ) -> Result<SegmentEntry, ()> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let name = format!("{SEGMENT_PREFIX}{id:08}{SEGMENT_EXTENSION}");
+        let bytes = binary_index::encode_segment(model, tombstones);
+        let segment_path = self.dir_path.join(&name);
+        write_atomically(&segment_path, false, |writer| {
+            writer.write_all(&bytes).map_err(|err| {
+                eprintln!(
+                    "ERROR: could not write the segment {segment_path}: {err}",
+                    segment_path = segment_path.display()
+                )
+            })
+        })?;
+
+        Ok(SegmentEntry {
+            name,
+            bytes: bytes.len() as u64,
+        })
+    }
+
+    fn write_manifest(&self, manifest: &Manifest) -> Result<(), ()> {
+        write_atomically(&self.dir_path.join(MANIFEST_NAME), self.backup, |writer| {
+            serde_json::to_writer(writer, manifest)
+                .map_err(|err| eprintln!("ERROR: could not serialize the manifest: {err}"))
+        })
+    }
+
+    /// Remove the segments listed by neither the manifest nor its backup, the failures are
+    /// ignored since they are removed again later
+    fn remove_unlisted_segments(&self) {
+        let mut listed = self
+            .manifest
+            .lock()
+            .unwrap()
+            .segments
+            .iter()
+            .map(|segment| segment.name.clone())
+            .collect::<HashSet<String>>();
+        let backup_path = sibling_path(&self.dir_path.join(MANIFEST_NAME), "bak");
+        if backup_path.exists() {
+            match read_manifest(&backup_path) {
+                Ok(backup) => listed.extend(backup.segments.into_iter().map(|x| x.name)),
+                // NOTE: the segments listed by the broken backup are unknown, keep them all
+                Err(()) => return,
+            }
+        }
+
+        for name in segment_files(&self.dir_path) {
+            if !listed.contains(&name) {
+                fs::remove_file(self.dir_path.join(name)).ok();
+            }
+        }
+    }
+}
+
+// NOTE: the segment holds the rules and the roots too, so it's saved even when no file is changed
+// (e.g. the rules are replaced)
+impl ModelStore<InMemoryIndexModel> for SegmentStore {
+    /// Save the changes as a new segment
+    fn save(&self, model: &RwLock<InMemoryIndexModel>) -> Result<(), ()> {
+        let (segment, tombstones, generation) = {
+            let model = model.read().unwrap();
+            let (segment, tombstones) = model.pending_segment();
+            (segment, tombstones, model.generation())
+        };
+        self.append(&segment, &tombstones)?;
+
+        // NOTE: the changes made while the segment is written are kept with the saved ones, they
+        // are saved again with the next segment, which replaces the same files
+        let mut model = model.write().unwrap();
+        if model.generation() == generation {
+            model.clear_pending();
+        }
+        println!(
+            "INFO: saved {count} changed files into the index",
+            count = tombstones.files.len()
+        );
+
+        Ok(())
+    }
+
+    fn maintain(&self) {
+        self.run_merger();
+    }
+}
+
+/// Load the model from the index folder. The backup of the manifest is loaded when the manifest
+/// or its segments are corrupt, and nothing is returned when neither of them could be loaded.
+pub fn load_model(dir_path: &Path) -> Option<LoadedModel> {
+    let manifest_path = dir_path.join(MANIFEST_NAME);
+    let backup_path = sibling_path(&manifest_path, "bak");
+    for (path, from_backup) in [(&manifest_path, false), (&backup_path, true)] {
+        if !path.exists() {
+            continue;
+        }
+
+        let paths = match read_manifest(path) {
+            Ok(manifest) => manifest
+                .segments
+                .iter()
+                .map(|segment| dir_path.join(&segment.name))
+                .collect::<Vec<PathBuf>>(),
+            Err(()) => continue,
+        };
+        if let Ok((header, model)) = load_segments(&paths) {
+            if from_backup {
+                println!(
+                    "INFO: the index folder {dir_path} is recovered from the backup {backup_path}",
+                    dir_path = dir_path.display(),
+                    backup_path = backup_path.display()
+                );
+            }
+
+            return Some(LoadedModel {
+                model,
+                header,
+                from_backup,
+            });
+        }
+    }
+
+    None
+}
+
+/// The newest format version of the segments in the index folder, or of the index file of the
+/// older format
+pub fn format_version(index_path: &Path) -> Option<u32> {
+    if !index_path.is_dir() {
+        return binary_index::format_version(index_path);
+    }
+
+    segment_files(index_path)
+        .iter()
+        .filter_map(|name| binary_index::format_version(&index_path.join(name)))
+        .max()
+}
+
+/// Choose the segments to be merged. The newest segments are merged once there are more than
+/// `MERGE_FACTOR` segments, up to `MERGE_FACTOR` of them, and a much larger older segment stops the
+/// merge, so the large segments are rewritten rarely.
+fn merge_range(sizes: &[u64]) -> Option<Range<usize>> {
+    if sizes.len() <= MERGE_FACTOR {
+        return None;
+    }
+
+    let mut start = sizes.len() - 1;
+    let mut total = sizes[start];
+    while start > 0
+        && sizes.len() - start < MERGE_FACTOR
+        && sizes[start - 1] <= total * MERGE_FACTOR as u64
+    {
+        start -= 1;
+        total += sizes[start];
+    }
+
+    (sizes.len() - start >= 2).then_some(start..sizes.len())
+}
+
+fn load_segments(paths: &[PathBuf]) -> Result<(IndexHeader, InMemoryIndexModel), ()> {
+    let mut header = None;
+    let mut model = InMemoryIndexModel::new();
+    for path in paths {
+        let (segment_header, segment, tombstones) = read_segment(path)?;
+        // NOTE: the oldest segment is the least compatible one
+        header.get_or_insert(segment_header);
+        model.apply_segment(segment, &tombstones);
+    }
+    model.clear_pending();
+
+    let header = header.unwrap_or_else(|| IndexHeader::current(model.roots.clone()));
+
+    Ok((header, model))
+}
+
+fn read_segment(segment_path: &Path) -> Result<(IndexHeader, InMemoryIndexModel, Tombstones), ()> {
+    let bytes = fs::read(segment_path).map_err(|err| {
+        eprintln!(
+            "ERROR: could not read the segment {segment_path}: {err}",
+            segment_path = segment_path.display()
+        )
+    })?;
+
+    binary_index::decode_segment(&bytes)
+}
+
+fn read_manifest(manifest_path: &Path) -> Result<Manifest, ()> {
+    let bytes = fs::read(manifest_path).map_err(|err| {
+        eprintln!(
+            "ERROR: could not read the manifest {manifest_path}: {err}",
+            manifest_path = manifest_path.display()
+        )
+    })?;
+
+    serde_json::from_slice(&bytes).map_err(|err| {
+        eprintln!(
+            "ERROR: could not parse the manifest {manifest_path}: {err}",
+            manifest_path = manifest_path.display()
+        )
+    })
+}
+
+/// The names of the segment files in the index folder
+fn segment_files(dir_path: &Path) -> Vec<String> {
+    let entries = match fs::read_dir(dir_path) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| segment_id(name).is_some())
+        .collect()
+}
+
+/// The id of the segment file(e.g. 3 of "segment-00000003.loser")
+fn segment_id(name: &str) -> Option<u64> {
+    name.strip_prefix(SEGMENT_PREFIX)?
+        .strip_suffix(SEGMENT_EXTENSION)?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_model, merge_range, SegmentStore};
+    use crate::model::analyzer::AnalyzedDocument;
+    use crate::model::in_memory_index_model::{InMemoryIndexModel, Model};
+    use crate::reader::reader_trait::Document;
+    use std::{env, fs, path::PathBuf, process, time::SystemTime};
+
+    #[test]
+    fn merge_range_ok() {
+        // arrange
+        let inputs: [&[u64]; 4] = [
+            &[100, 10, 10, 10],
+            &[1000, 1, 1, 1, 1, 1],
+            &[1000, 100, 1, 1, 1],
+            &[1000, 100, 50, 1, 2],
+        ];
+
+        // act
+        let actual = inputs.map(merge_range);
+
+        // assert
+        assert_eq!(actual, [None, Some(2..6), Some(2..5), Some(3..5)]);
+    }
+
+    #[test]
+    fn merge_ok() -> Result<(), ()> {
+        // arrange
+        let index_path =
+            env::temp_dir().join(format!("loser-merge-test-{pid}.loser", pid = process::id()));
+        let mut model = InMemoryIndexModel::new();
+        let store = SegmentStore::create(&index_path, &model, false)?;
+        let time = SystemTime::now();
+        for i in 0..6 {
+            let file_path = PathBuf::from(format!("test/test{n}.txt", n = i % 3));
+            model.add_documents(
+                file_path.clone(),
+                vec![AnalyzedDocument::new(
+                    Document::new(file_path, format!("Andy is Andy {i}.")),
+                    time,
+                )],
+                time,
+            )?;
+            if i == 4 {
+                model.remove_document(&PathBuf::from("test/test0.txt"));
+            }
+            let (segment, tombstones) = model.pending_segment();
+            store.append(&segment, &tombstones)?;
+            model.clear_pending();
+        }
+
+        // act
+        let before = store.segment_paths().len();
+        while store.merge()? {}
+        let after = store.segment_paths().len();
+        let files = fs::read_dir(&index_path)
+            .map_err(|err| eprintln!("ERROR: {err}"))?
+            .count();
+        let loaded = load_model(&index_path).ok_or(())?;
+        fs::remove_dir_all(&index_path).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert_eq!(before, 7);
+        assert!(after <= 4);
+        // NOTE: the segments merged are removed, only the manifest is left besides the segments
+        assert_eq!(files, after + 1);
+        assert_eq!(loaded.model, model);
+
+        Ok(())
+    }
+}
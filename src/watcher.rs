@@ -0,0 +1,205 @@
+use super::config::{is_index_path, relative_index_path};
+use notify::{Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::BTreeSet,
+    env,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+/// How long the folder should be quiet before the changes are reported, so the bursts of the
+/// events(e.g. the temporary files written by the editors) are reported once
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(500);
+
+/// The longest delay of the changes while the events keep coming
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// How often the folder is scanned when the changes are polled
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Watcher of the changes in the folder and its sub-folders. The events of the file system(e.g.
+/// inotify) are used, and the folder is polled when they are not available(e.g. too many folders
+/// are watched) or `poll` is set(e.g. the network shares).
+pub struct FolderWatcher {
+    dir_path: PathBuf,
+    /// The absolute path of the folder as it's watched. The events report the paths under it
+    /// without resolving the symlinks or "..", so it's not canonicalized.
+    absolute_path: PathBuf,
+    /// The index file relative to the folder, when it's saved inside the folder. Its changes are
+    /// written by the server itself, so they're dropped.
+    index_path: Option<PathBuf>,
+    // NOTE: the events stop once the watcher is dropped
+    _watcher: Box<dyn Watcher + Send>,
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl FolderWatcher {
+    pub fn new(dir_path: &Path, index_path: &Path, poll: bool) -> Result<Self, ()> {
+        let (sender, events) = mpsc::channel();
+
+        let watcher: Result<Box<dyn Watcher + Send>, notify::Error> = if poll {
+            Err(notify::Error::generic("polling is requested"))
+        } else {
+            RecommendedWatcher::new(sender.clone(), Config::default())
+                .and_then(|mut watcher| {
+                    watcher.watch(dir_path, RecursiveMode::Recursive)?;
+                    Ok(watcher)
+                })
+                .map(|watcher| Box::new(watcher) as Box<dyn Watcher + Send>)
+        };
+
+        let watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                if !poll {
+                    println!(
+                        "INFO: could not watch the events in {dir_path}, poll the changes instead: {err}",
+                        dir_path = dir_path.display()
+                    );
+                }
+
+                let mut watcher =
+                    PollWatcher::new(sender, Config::default().with_poll_interval(POLL_INTERVAL))
+                        .map_err(|err| eprintln!("ERROR: could not poll the changes: {err}"))?;
+                watcher
+                    .watch(dir_path, RecursiveMode::Recursive)
+                    .map_err(|err| {
+                        eprintln!(
+                            "ERROR: could not poll the changes in {dir_path}: {err}",
+                            dir_path = dir_path.display()
+                        )
+                    })?;
+                Box::new(watcher)
+            }
+        };
+
+        let absolute_path = env::current_dir()
+            .map(|current_dir| current_dir.join(dir_path))
+            .map_err(|err| {
+                eprintln!(
+                    "ERROR: could not get the absolute path of {dir_path}: {err}",
+                    dir_path = dir_path.display()
+                )
+            })?;
+
+        Ok(FolderWatcher {
+            dir_path: dir_path.to_path_buf(),
+            absolute_path,
+            index_path: relative_index_path(dir_path, index_path),
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Wait for the changes and return the changed paths(the created, modified and removed files
+    /// and folders) once the folder is quiet for a while
+    pub fn changed_paths(&self) -> Result<Vec<PathBuf>, ()> {
+        let mut paths = BTreeSet::new();
+        // NOTE: the events of the index file are dropped, wait for the first change of the folder
+        while paths.is_empty() {
+            let event = self
+                .events
+                .recv()
+                .map_err(|_| eprintln!("ERROR: the watcher is stopped unexpectedly"))?;
+            self.collect_paths(event, &mut paths);
+        }
+
+        let started = Instant::now();
+        while started.elapsed() < MAX_DELAY {
+            match self.events.recv_timeout(DEBOUNCE_DELAY) {
+                Ok(event) => self.collect_paths(event, &mut paths),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    eprintln!("ERROR: the watcher is stopped unexpectedly");
+                    return Err(());
+                }
+            }
+        }
+
+        Ok(paths.into_iter().collect())
+    }
+
+    fn collect_paths(&self, event: notify::Result<Event>, paths: &mut BTreeSet<PathBuf>) {
+        match event {
+            // NOTE: some events are lost(e.g. the queue of inotify overflows), the whole folder is
+            // checked again
+            Ok(event) if event.need_rescan() => {
+                paths.insert(self.dir_path.clone());
+            }
+            Ok(Event {
+                kind: EventKind::Access(_),
+                ..
+            }) => {}
+            // NOTE: the paths are relative to the folder like the indexed files. The polled paths
+            // are under the folder as it's given, which could be relative.
+            Ok(event) => {
+                for path in event.paths {
+                    let prefix = if path.is_absolute() {
+                        &self.absolute_path
+                    } else {
+                        &self.dir_path
+                    };
+                    match path.strip_prefix(prefix) {
+                        Ok(relative_path)
+                            if self.index_path.as_deref().is_some_and(|index_path| {
+                                is_index_path(relative_path, index_path)
+                            }) => {}
+                        Ok(relative_path) => {
+                            paths.insert(self.dir_path.join(relative_path));
+                        }
+                        Err(_) => {
+                            paths.insert(path);
+                        }
+                    }
+                }
+            }
+            Err(err) => eprintln!(
+                "ERROR: could not watch the changes in {dir_path}: {err}",
+                dir_path = self.dir_path.display()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FolderWatcher;
+    use std::{
+        env, fs,
+        path::{Component, PathBuf},
+        process,
+    };
+
+    #[test]
+    fn changed_paths_ok() -> Result<(), ()> {
+        // arrange
+        let dir = env::temp_dir().join(format!("loser-watcher-test-{pid}", pid = process::id()));
+        fs::create_dir_all(&dir).map_err(|err| eprintln!("ERROR: {err}"))?;
+        // NOTE: the folder is given through "..", which the paths of the events keep
+        let current_dir = env::current_dir().map_err(|err| eprintln!("ERROR: {err}"))?;
+        let dir_path = current_dir
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .map(|_| Component::ParentDir)
+            .chain(
+                dir.components()
+                    .filter(|component| matches!(component, Component::Normal(_))),
+            )
+            .collect::<PathBuf>();
+        let index_path = dir_path.join("index.loser");
+        let watcher = FolderWatcher::new(&dir_path, &index_path, false)?;
+
+        // act
+        fs::write(dir.join("index.loser"), "index").map_err(|err| eprintln!("ERROR: {err}"))?;
+        fs::write(dir.join("a.txt"), "Andy is Andy.").map_err(|err| eprintln!("ERROR: {err}"))?;
+        let actual = watcher.changed_paths();
+        drop(watcher);
+        fs::remove_dir_all(&dir).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        assert_eq!(actual, Ok(vec![dir_path.join("a.txt")]));
+
+        Ok(())
+    }
+}
@@ -1,18 +1,28 @@
+use super::config::FolderConfig;
 use super::model::in_memory_index_model::Model;
+use super::progress::Progress;
+use super::reader::archive_reader::{read_archive_entry, ARCHIVE_SEPARATOR};
 use std::{
+    collections::HashMap,
     io,
-    sync::{Arc, Mutex},
+    path::Path,
+    sync::{Arc, RwLock},
 };
 use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
 
 pub struct WebServer<'a> {
     pub addr: &'a str,
-    pub model: Arc<Mutex<dyn Model>>,
+    pub model: Arc<RwLock<dyn Model>>,
+    pub progress: Arc<Progress>,
 }
 
 impl<'a> WebServer<'a> {
-    pub fn new(addr: &'a str, model: Arc<Mutex<dyn Model>>) -> Self {
-        WebServer { addr, model }
+    pub fn new(addr: &'a str, model: Arc<RwLock<dyn Model>>, progress: Arc<Progress>) -> Self {
+        WebServer {
+            addr,
+            model,
+            progress,
+        }
     }
 
     fn serve_404(request: Request) -> std::io::Result<()> {
@@ -57,22 +67,29 @@ impl<'a> WebServer<'a> {
 
         println!("Request body(query): {query}");
 
-        let results = match self
-            .model
-            .lock()
-            .unwrap()
-            .search(&query.chars().collect::<Vec<char>>())
-        {
+        let query = query.chars().collect::<Vec<char>>();
+        // NOTE: the searches share the read lock, and wait only for the batch being written by
+        // the indexer
+        let model = self.model.read().unwrap();
+        let results = match model.search(&query) {
             Ok(result) => result,
             Err(_) => return Self::serve_500(request),
         };
 
-        let mut data = Vec::<(String, f32)>::new();
+        // NOTE: the anchor of the best matched passage(e.g. the timestamp of a subtitle cue) is null
+        // for the documents without passages
+        let mut data = Vec::<(String, f32, HashMap<String, String>, Option<String>)>::new();
         for (path, rank) in results.iter().take(15) {
             println!("File Path: {path} | Rank: {rank}", path = path.display());
 
-            data.push((format!("{path}", path = path.display()), *rank));
+            data.push((
+                format!("{path}", path = path.display()),
+                *rank,
+                model.document_metadata(path).into_iter().collect(),
+                model.best_passage(path, &query),
+            ));
         }
+        drop(model);
 
         let json = match serde_json::to_string(&data) {
             Ok(json) => json,
@@ -94,6 +111,69 @@ impl<'a> WebServer<'a> {
         Ok(())
     }
 
+    /// Serve the progress of the indexing(e.g. the processed files and the ETA) as JSON
+    fn serve_progress(&self, request: Request) -> io::Result<()> {
+        let report = self.progress.report();
+        let json = match serde_json::to_string(&report) {
+            Ok(json) => json,
+            Err(err) => {
+                eprintln!(
+                    "ERROR: could not convert the progress to JSON, progress: {report:?}: {err}"
+                );
+                return Self::serve_500(request);
+            }
+        };
+
+        let header = Header::from_bytes("Content-Type", "application/json")
+            .expect("Response header should not be empty.");
+
+        request.respond(Response::from_string(json).with_header(header))
+    }
+
+    /// Serve the indexed file, so the search results could be opened in the browser(e.g.
+    /// "/file?path=a.pdf#page=3" for the third page of the PDF). The files in the archives are read
+    /// from the archives(e.g. "/file?path=backup.zip!/notes/a.txt").
+    fn serve_indexed_file(&self, request: Request) -> io::Result<()> {
+        let file_path = request
+            .url()
+            .split_once('?')
+            .and_then(|(_, query)| {
+                query
+                    .split('&')
+                    .find_map(|param| param.strip_prefix("path="))
+            })
+            .map(decode_url_component);
+
+        let file_path = match file_path {
+            Some(file_path) => file_path,
+            None => return Self::serve_404(request),
+        };
+        let content_type = content_type(Path::new(&file_path));
+
+        let model = self.model.read().unwrap();
+        if model.contains_file(Path::new(&file_path)) {
+            drop(model);
+            return Self::serve_static_file(request, &file_path, content_type);
+        }
+
+        let (archive_path, name) = match file_path.split_once(ARCHIVE_SEPARATOR) {
+            Some((archive_path, name)) if model.contains_file(Path::new(archive_path)) => {
+                (archive_path, name)
+            }
+            _ => return Self::serve_404(request),
+        };
+        drop(model);
+
+        match read_archive_entry(Path::new(archive_path), name, &FolderConfig::user()) {
+            Ok(content) => {
+                let content_type_header = Header::from_bytes("Content-Type", content_type)
+                    .expect("Response header should not be empty.");
+                request.respond(Response::from_data(content).with_header(content_type_header))
+            }
+            Err(_) => Self::serve_404(request),
+        }
+    }
+
     fn serve_request(&self, request: Request) -> io::Result<()> {
         println!(
             "INFO: received request! method: {method}, url: {url}",
@@ -114,6 +194,12 @@ impl<'a> WebServer<'a> {
             (Method::Post, "/api/search") => {
                 self.serve_search(request)?;
             }
+            (Method::Get, "/api/progress") => {
+                self.serve_progress(request)?;
+            }
+            (Method::Get, url) if url.starts_with("/file?") => {
+                self.serve_indexed_file(request)?;
+            }
             _ => {
                 println!("Other url: {url}", url = request.url());
                 return Self::serve_404(request);
@@ -148,3 +234,195 @@ impl<'a> WebServer<'a> {
         Err(())
     }
 }
+
+/// The content type of the file served by its extension
+fn content_type(file_path: &Path) -> &'static str {
+    match file_path
+        .extension()
+        .map(|x| x.to_string_lossy().to_lowercase())
+        .as_deref()
+    {
+        Some("pdf") => "application/pdf",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("eml") => "message/rfc822",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Decode the percent-encoded component of the URL(e.g. "a%20b.txt" into "a b.txt")
+fn decode_url_component(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|x| std::str::from_utf8(x).ok())
+            .and_then(|x| u8::from_str_radix(x, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(x)) => {
+                decoded.push(x);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (x, _) => decoded.push(x),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_url_component, WebServer};
+    use crate::{
+        model::{
+            analyzer::AnalyzedDocument,
+            in_memory_index_model::{InMemoryIndexModel, Model},
+        },
+        progress::Progress,
+        reader::reader_trait::Document,
+    };
+    use std::{
+        env, fs,
+        io::{Read, Write},
+        net::TcpStream,
+        path::PathBuf,
+        process,
+        sync::{Arc, RwLock},
+        thread,
+        time::{Duration, SystemTime},
+    };
+    use tiny_http::Server;
+
+    /// Send a GET request to the web server, and read the whole response
+    fn get(web_server: &WebServer, url: &str) -> Result<String, ()> {
+        let server = Server::http("127.0.0.1:0").map_err(|err| eprintln!("ERROR: {err}"))?;
+        let addr = server.server_addr();
+        let request = format!("GET {url} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        let client = thread::spawn(move || -> Result<String, ()> {
+            let mut stream = TcpStream::connect(addr).map_err(|err| eprintln!("ERROR: {err}"))?;
+            let mut response = String::new();
+            stream
+                .write_all(request.as_bytes())
+                .and_then(|_| stream.read_to_string(&mut response))
+                .map_err(|err| eprintln!("ERROR: {err}"))?;
+            Ok(response)
+        });
+
+        // NOTE: the test fails instead of hanging when the request is never received
+        let request = server
+            .recv_timeout(Duration::from_secs(5))
+            .map_err(|err| eprintln!("ERROR: {err}"))?
+            .ok_or_else(|| eprintln!("ERROR: the request to {url} is not received"))?;
+        web_server
+            .serve_request(request)
+            .map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        client.join().map_err(|_| ())?
+    }
+
+    #[test]
+    fn decode_url_component_ok() {
+        // arrange
+        let components = [
+            "a%20b.txt",
+            "Andy+is+Andy.txt",
+            "%E4%B8%AD%E6%96%87.txt",
+            "100%.txt",
+            "a%2Fb%23page%3D1",
+        ];
+
+        // act
+        let actual: Vec<String> = components.into_iter().map(decode_url_component).collect();
+
+        // assert
+        assert_eq!(
+            actual,
+            vec![
+                "a b.txt",
+                "Andy is Andy.txt",
+                "中文.txt",
+                "100%.txt",
+                "a/b#page=1"
+            ]
+        );
+    }
+
+    #[test]
+    fn serve_indexed_file_ok() -> Result<(), ()> {
+        // arrange
+        let dir = env::temp_dir().join(format!("loser-web-test-{pid}", pid = process::id()));
+        fs::create_dir_all(&dir).map_err(|err| eprintln!("ERROR: {err}"))?;
+        let text_path = dir.join("Andy #1.txt");
+        let archive_path = dir.join("backup.tar");
+        let secret_path = dir.join("secret.txt");
+        fs::write(&text_path, "Andy is Andy.").map_err(|err| eprintln!("ERROR: {err}"))?;
+        fs::write(&secret_path, "Amy is Amy.").map_err(|err| eprintln!("ERROR: {err}"))?;
+        let mut builder = tar::Builder::new(
+            fs::File::create(&archive_path).map_err(|err| eprintln!("ERROR: {err}"))?,
+        );
+        let mut header = tar::Header::new_gnu();
+        header.set_size(11);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "notes/a.txt", "Amy is Amy.".as_bytes())
+            .and_then(|_| builder.finish())
+            .map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        let mut model = InMemoryIndexModel::new();
+        let time = SystemTime::now();
+        let entry_path = PathBuf::from(format!(
+            "{archive}!/notes/a.txt",
+            archive = archive_path.display()
+        ));
+        for (file_path, document_path) in [
+            (text_path.clone(), text_path.clone()),
+            (archive_path.clone(), entry_path.clone()),
+        ] {
+            let document = Document::new(document_path, "Andy is Andy.".to_string());
+            model.add_documents(file_path, vec![AnalyzedDocument::new(document, time)], time)?;
+        }
+        let model: Arc<RwLock<dyn Model>> = Arc::new(RwLock::new(model));
+        let web_server = WebServer::new("127.0.0.1:0", model, Arc::new(Progress::new(false)));
+        let url = |path: &PathBuf| {
+            format!(
+                "/file?path={path}",
+                path = path
+                    .display()
+                    .to_string()
+                    .replace(' ', "%20")
+                    .replace('#', "%23")
+            )
+        };
+
+        // act
+        let text = get(&web_server, &url(&text_path));
+        let entry = get(&web_server, &url(&entry_path));
+        let missing_entry = get(
+            &web_server,
+            &url(&PathBuf::from(format!(
+                "{archive}!/notes/b.txt",
+                archive = archive_path.display()
+            ))),
+        );
+        let secret = get(&web_server, &url(&secret_path));
+        fs::remove_dir_all(&dir).map_err(|err| eprintln!("ERROR: {err}"))?;
+
+        // assert
+        let text = text?;
+        assert!(text.starts_with("HTTP/1.1 200"));
+        assert!(text.ends_with("\r\n\r\nAndy is Andy."));
+        let entry = entry?;
+        assert!(entry.starts_with("HTTP/1.1 200"));
+        assert!(entry.contains("text/plain; charset=utf-8"));
+        assert!(entry.ends_with("\r\n\r\nAmy is Amy."));
+        assert!(missing_entry?.starts_with("HTTP/1.1 404"));
+        assert!(secret?.starts_with("HTTP/1.1 404"));
+
+        Ok(())
+    }
+}